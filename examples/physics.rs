@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use bevy::{app::ScheduleRunnerSettings, log::LogPlugin, math::DVec3, prelude::*};
 
@@ -14,25 +14,51 @@ fn main() {
     .add_plugins(MinimalPlugins)
     .add_plugin(LogPlugin::default())
     // required (Tesseract)
+    .add_systems(
+        PreUpdate,
+        (
+            level::chunk::queue_scheduled_ticks,
+            level::chunk::queue_random_ticks,
+        ),
+    )
     .add_systems(
         PostUpdate,
-        (level::chunk::update_hierarchy, level::chunk::queue_updates),
+        (
+            level::chunk::update_hierarchy,
+            level::chunk::queue_updates,
+            level::chunk::queue_neighbor_updates.after(level::chunk::queue_updates),
+            level::light::initialize_light,
+            level::light::update_light.after(level::chunk::queue_updates),
+            level::chunk::initialize_heightmaps,
+            level::chunk::update_heightmaps.after(level::chunk::queue_updates),
+        ),
     )
     // required (Java Edition)
     .add_plugin(tesseract_java::RegistryPlugin::default())
     .add_plugin(tesseract_java::ReplicationPlugin::default())
+    .add_plugin(tesseract_java::WorldGenPlugin {
+        generators: [(
+            "minecraft:overworld".to_string(),
+            Arc::new(tesseract_java::worldgen::SuperflatGenerator::default()) as _,
+        )]
+        .into(),
+        ..default()
+    })
     // gameplay
+    .add_plugin(tesseract_java::GameplayPlugin)
+    .init_resource::<tesseract_physics::FluidRng>()
     .add_systems(
         Update,
-        (level::update_time, tesseract_physics::update_fluids),
+        (
+            level::update_time,
+            tesseract_physics::update_fluids,
+            tesseract_physics::update_actors,
+        ),
     )
     // gameplay (custom)
     .add_systems(PreStartup, register_blocks_and_items)
     .add_systems(Startup, spawn_levels)
-    .add_systems(
-        First,
-        (spawn_players, spawn_chunks).after(replication::UpdateFlush),
-    );
+    .add_systems(First, spawn_players.after(replication::UpdateFlush));
 
     app.run();
 }
@@ -40,6 +66,12 @@ fn main() {
 fn register_blocks_and_items(mut commands: Commands) {
     commands.spawn((
         block::Base,
+        block::Air,
+        block::Replaceable,
+        block::Light {
+            emission: 0,
+            opacity: 0,
+        },
         tesseract_java::block::Name::new("minecraft:air"),
     ));
     commands.spawn((
@@ -57,18 +89,47 @@ fn register_blocks_and_items(mut commands: Commands) {
         item::Base,
         tesseract_java::block::Name::new("minecraft:grass_block"),
     ));
-    commands.spawn((
-        block::Base,
-        tesseract_physics::Fluid { volume: 7 },
-        tesseract_java::block::Name::new("minecraft:water[level=0]"),
-    ));
-    commands.spawn_batch((0..7).map(|volume| {
+    let water = commands
+        .spawn(tesseract_physics::FluidKind {
+            volume_max: 8,
+            flow_delay: 5,
+            infinite_source: true,
+        })
+        .id();
+    commands.spawn_batch((0..8).map(move |volume| {
         (
             block::Base,
-            tesseract_physics::Fluid { volume },
+            block::Light {
+                emission: 0,
+                opacity: 1,
+            },
+            block::Replaceable,
+            tesseract_physics::Fluid {
+                kind: water,
+                volume,
+            },
             tesseract_java::block::Name::new(format!("minecraft:water[level={}]", 7 - volume)),
         )
     }));
+    let lava = commands
+        .spawn(tesseract_physics::FluidKind {
+            volume_max: 4,
+            flow_delay: 30,
+            infinite_source: false,
+        })
+        .id();
+    commands.spawn_batch((0..4).map(move |volume| {
+        (
+            block::Base,
+            block::Light {
+                emission: 15,
+                opacity: 1,
+            },
+            block::Replaceable,
+            tesseract_physics::Fluid { kind: lava, volume },
+            tesseract_java::block::Name::new(format!("minecraft:lava[level={}]", (3 - volume) * 2)),
+        )
+    }));
 }
 
 fn spawn_levels(mut commands: Commands) {
@@ -109,32 +170,3 @@ fn spawn_players(
             .set_parent(level_access.single());
     }
 }
-
-fn spawn_chunks(mut commands: Commands, for_chunks: Query<Entity, Added<level::chunk::Base>>) {
-    if for_chunks.is_empty() {
-        return;
-    }
-
-    let air_id = 0;
-    let bedrock_id = 1;
-    let dirt_id = 2;
-    let grass_block_id = 3;
-    for chunk in for_chunks.iter() {
-        let mut chunk_data = level::chunk::Data::new(24, 4, air_id, 0);
-
-        for x in 0..16 {
-            for z in 0..16 {
-                chunk_data.set(x, 0, z, bedrock_id);
-                for y in 1..4 {
-                    chunk_data.set(x, y, z, dirt_id);
-                }
-                chunk_data.set(x, 4, z, grass_block_id);
-            }
-        }
-        for section in &mut chunk_data.sections {
-            section.block_state_changes.clear();
-        }
-
-        commands.entity(chunk).insert(chunk_data);
-    }
-}
@@ -0,0 +1,63 @@
+use crate::TagType;
+
+// Newtype struct names, which are understood by the serializers and
+// deserializers of this crate, and are transparent for every other format
+pub(crate) const BYTE_ARRAY: &str = "__tesseract_nbt_byte_array";
+pub(crate) const INT_ARRAY: &str = "__tesseract_nbt_int_array";
+pub(crate) const LONG_ARRAY: &str = "__tesseract_nbt_long_array";
+/// Used by values to tell arrays and lists apart
+pub(crate) const VALUE: &str = "__tesseract_nbt_value";
+
+/// Returns the array and element type for the newtype struct name
+pub(crate) fn array_type(name: &str) -> Option<(TagType, TagType)> {
+    match name {
+        BYTE_ARRAY => Some((TagType::ByteArray, TagType::Byte)),
+        INT_ARRAY => Some((TagType::IntArray, TagType::Int)),
+        LONG_ARRAY => Some((TagType::LongArray, TagType::Long)),
+        _ => None,
+    }
+}
+
+macro_rules! array_with {
+    ($module:ident, $name:ident, $doc:literal) => {
+        #[doc = $doc]
+        ///
+        /// Lists and arrays of every type are accepted when reading.
+        pub mod $module {
+            pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                T: serde::Serialize + ?Sized,
+                S: serde::Serializer,
+            {
+                serializer.serialize_newtype_struct(super::$name, value)
+            }
+
+            pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+            where
+                T: serde::Deserialize<'de>,
+                D: serde::Deserializer<'de>,
+            {
+                T::deserialize(deserializer)
+            }
+        }
+    };
+}
+
+array_with!(
+    byte_array,
+    BYTE_ARRAY,
+    "Writes a sequence of bytes as byte array, use with `#[serde(with = \
+     \"tesseract_nbt::byte_array\")]`"
+);
+array_with!(
+    int_array,
+    INT_ARRAY,
+    "Writes a sequence of ints as int array, use with `#[serde(with = \
+     \"tesseract_nbt::int_array\")]`"
+);
+array_with!(
+    long_array,
+    LONG_ARRAY,
+    "Writes a sequence of longs as long array, use with `#[serde(with = \
+     \"tesseract_nbt::long_array\")]`"
+);
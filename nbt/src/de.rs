@@ -1,53 +1,212 @@
-use byteorder::{BigEndian, ReadBytesExt};
-use serde::forward_to_deserialize_any;
+use std::io::Read;
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use serde::{de::IntoDeserializer, forward_to_deserialize_any};
 
 use crate::{
+    array,
     error::{Error, Result},
-    TagType,
+    reader::{self, Limits, Reader},
+    Encoding, Endian, TagType,
 };
 
 pub fn from_slice<'a, T>(input: &mut &'a [u8]) -> Result<T>
 where
     T: serde::de::Deserialize<'a>,
 {
-    let mut deserializer = Deserializer::from_slice(input)?;
+    from_slice_with(input, Encoding::JAVA)
+}
+
+pub fn from_slice_with<'a, T>(input: &mut &'a [u8], encoding: Encoding) -> Result<T>
+where
+    T: serde::de::Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_slice(input, encoding)?;
     let value = T::deserialize(&mut deserializer);
     *input = deserializer.data;
     value
 }
 
+/// Deserializes from the reader without buffering the whole input, reads only
+/// as far as the value goes
+pub fn from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+{
+    from_reader_with(reader, Encoding::JAVA, Limits::default())
+}
+
+pub fn from_reader_with<R, T>(reader: R, encoding: Encoding, limits: Limits) -> Result<T>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+{
+    let mut deserializer = reader::Deserializer::new(Reader::new(reader, encoding, limits)?);
+    T::deserialize(&mut deserializer)
+}
+
 struct Deserializer<'de> {
     data: &'de [u8],
+    encoding: Encoding,
 
     name: bool,
     current_type: TagType,
 }
 
 impl<'de> Deserializer<'de> {
-    fn from_slice(input: &'de [u8]) -> Result<Self> {
+    fn from_slice(input: &'de [u8], encoding: Encoding) -> Result<Self> {
         let mut self_ = Self {
             data: input,
+            encoding,
 
             name: false,
             current_type: TagType::default(),
         };
-        let type_ = TagType::try_from(self_.data.read_i8()?).unwrap();
-        if type_ != TagType::End {
-            let name_length = self_.data.read_i16::<BigEndian>()?;
-            let (_, data) = self_.data.split_at(name_length as usize);
-            self_.data = data;
+        let type_ = self_.read_type()?;
+        if type_ != TagType::End && encoding.root_name {
+            self_.read_str()?;
         }
         self_.current_type = type_;
         Ok(self_)
     }
+
+    fn read_type(&mut self) -> Result<TagType> {
+        let type_ = self.data.read_i8()?;
+        TagType::try_from(type_).map_err(|_| Error::UnknownTagType(type_))
+    }
+
+    fn read_i16(&mut self) -> Result<i16> {
+        read_i16(&mut self.data, self.encoding)
+    }
+
+    fn read_i32(&mut self) -> Result<i32> {
+        read_i32(&mut self.data, self.encoding)
+    }
+
+    fn read_i64(&mut self) -> Result<i64> {
+        read_i64(&mut self.data, self.encoding)
+    }
+
+    fn read_f32(&mut self) -> Result<f32> {
+        read_f32(&mut self.data, self.encoding)
+    }
+
+    fn read_f64(&mut self) -> Result<f64> {
+        read_f64(&mut self.data, self.encoding)
+    }
+
+    fn read_str(&mut self) -> Result<&'de str> {
+        let length = read_str_len(&mut self.data, self.encoding)?;
+        let bytes = self.read_bytes(length as i32)?;
+        Ok(std::str::from_utf8(bytes)?)
+    }
+
+    fn read_bytes(&mut self, length: i32) -> Result<&'de [u8]> {
+        if length < 0 || length as usize > self.data.len() {
+            return Err(Error::InvalidLength(length));
+        }
+
+        let (bytes, data) = self.data.split_at(length as usize);
+        self.data = data;
+        Ok(bytes)
+    }
+
+    fn read_count(&mut self, type_: TagType) -> Result<u32> {
+        let count = self.read_i32()?;
+        // every element is at least one byte long, except end tags
+        if count < 0 || (count as usize > self.data.len() && type_ != TagType::End) {
+            return Err(Error::InvalidLength(count));
+        }
+
+        Ok(count as u32)
+    }
+
+    fn read_end(&mut self) -> Result<()> {
+        match self.read_type()? {
+            TagType::End => Ok(()),
+            _ => Err(serde::de::Error::custom("expected end of compound")),
+        }
+    }
+}
+
+pub(crate) fn read_i16(input: &mut impl Read, encoding: Encoding) -> Result<i16> {
+    Ok(match encoding.endian {
+        Endian::Big => input.read_i16::<BigEndian>()?,
+        Endian::Little => input.read_i16::<LittleEndian>()?,
+    })
+}
+
+pub(crate) fn read_i32(input: &mut impl Read, encoding: Encoding) -> Result<i32> {
+    if encoding.var_int {
+        let value = read_var_u64(input, 32)? as u32;
+        return Ok((value >> 1) as i32 ^ -((value & 1) as i32));
+    }
+
+    Ok(match encoding.endian {
+        Endian::Big => input.read_i32::<BigEndian>()?,
+        Endian::Little => input.read_i32::<LittleEndian>()?,
+    })
+}
+
+pub(crate) fn read_i64(input: &mut impl Read, encoding: Encoding) -> Result<i64> {
+    if encoding.var_int {
+        let value = read_var_u64(input, 64)?;
+        return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+    }
+
+    Ok(match encoding.endian {
+        Endian::Big => input.read_i64::<BigEndian>()?,
+        Endian::Little => input.read_i64::<LittleEndian>()?,
+    })
+}
+
+pub(crate) fn read_f32(input: &mut impl Read, encoding: Encoding) -> Result<f32> {
+    Ok(match encoding.endian {
+        Endian::Big => input.read_f32::<BigEndian>()?,
+        Endian::Little => input.read_f32::<LittleEndian>()?,
+    })
+}
+
+pub(crate) fn read_f64(input: &mut impl Read, encoding: Encoding) -> Result<f64> {
+    Ok(match encoding.endian {
+        Endian::Big => input.read_f64::<BigEndian>()?,
+        Endian::Little => input.read_f64::<LittleEndian>()?,
+    })
+}
+
+fn read_var_u64(input: &mut impl Read, bits: u32) -> Result<u64> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = input.read_u8()?;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+
+        shift += 7;
+        if shift >= bits {
+            return Err(Error::VarIntTooLong);
+        }
+    }
+}
+
+pub(crate) fn read_str_len(input: &mut impl Read, encoding: Encoding) -> Result<u32> {
+    // strings are the only lengths which are not zigzag-encoded
+    Ok(if encoding.var_int {
+        read_var_u64(input, 32)? as u32
+    } else {
+        read_i16(input, encoding)? as u16 as u32
+    })
 }
 
 impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
     type Error = Error;
 
     forward_to_deserialize_any! {
-        i8 i16 i32 i64 f32 f64 char str string bytes byte_buf unit unit_struct
-        newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+        i8 i16 i32 i64 f32 f64 char str string bytes byte_buf unit seq tuple
+        tuple_struct map struct identifier ignored_any
     }
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
@@ -57,44 +216,39 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
         if self.name {
             self.name = false;
 
-            let length = self.data.read_i16::<BigEndian>()?;
-            let (bytes, data) = self.data.split_at(length as usize);
-            self.data = data;
-            visitor.visit_str(std::str::from_utf8(bytes).unwrap())
+            visitor.visit_borrowed_str(self.read_str()?)
         } else {
             match self.current_type {
                 TagType::End => visitor.visit_unit(),
                 TagType::Byte => visitor.visit_i8(self.data.read_i8()?),
-                TagType::Short => visitor.visit_i16(self.data.read_i16::<BigEndian>()?),
-                TagType::Int => visitor.visit_i32(self.data.read_i32::<BigEndian>()?),
-                TagType::Long => visitor.visit_i64(self.data.read_i64::<BigEndian>()?),
-                TagType::Float => visitor.visit_f32(self.data.read_f32::<BigEndian>()?),
-                TagType::Double => visitor.visit_f64(self.data.read_f64::<BigEndian>()?),
+                TagType::Short => visitor.visit_i16(self.read_i16()?),
+                TagType::Int => visitor.visit_i32(self.read_i32()?),
+                TagType::Long => visitor.visit_i64(self.read_i64()?),
+                TagType::Float => visitor.visit_f32(self.read_f32()?),
+                TagType::Double => visitor.visit_f64(self.read_f64()?),
                 TagType::ByteArray => visitor.visit_seq(SeqAccess {
                     type_: TagType::Byte,
-                    count: self.data.read_i32::<BigEndian>()? as u32,
+                    count: self.read_count(TagType::Byte)?,
                     de: self,
                 }),
-                TagType::String => {
-                    let length = self.data.read_i16::<BigEndian>()?;
-                    let (bytes, data) = self.data.split_at(length as usize);
-                    self.data = data;
-                    visitor.visit_str(std::str::from_utf8(bytes).unwrap())
+                TagType::String => visitor.visit_borrowed_str(self.read_str()?),
+                TagType::List => {
+                    let type_ = self.read_type()?;
+                    visitor.visit_seq(SeqAccess {
+                        type_,
+                        count: self.read_count(type_)?,
+                        de: self,
+                    })
                 }
-                TagType::List => visitor.visit_seq(SeqAccess {
-                    type_: TagType::try_from(self.data.read_i8()?).unwrap(),
-                    count: self.data.read_i32::<BigEndian>()? as u32,
-                    de: self,
-                }),
                 TagType::Compound => visitor.visit_map(self),
                 TagType::IntArray => visitor.visit_seq(SeqAccess {
                     type_: TagType::Int,
-                    count: self.data.read_i32::<BigEndian>()? as u32,
+                    count: self.read_count(TagType::Int)?,
                     de: self,
                 }),
                 TagType::LongArray => visitor.visit_seq(SeqAccess {
                     type_: TagType::Long,
-                    count: self.data.read_i32::<BigEndian>()? as u32,
+                    count: self.read_count(TagType::Long)?,
                     de: self,
                 }),
             }
@@ -132,7 +286,7 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: serde::de::Visitor<'de>,
     {
         if !self.name && self.current_type == TagType::Short {
-            visitor.visit_u16(self.data.read_u16::<BigEndian>()?)
+            visitor.visit_u16(self.read_i16()? as u16)
         } else {
             self.deserialize_any(visitor)
         }
@@ -143,7 +297,7 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: serde::de::Visitor<'de>,
     {
         if !self.name && self.current_type == TagType::Int {
-            visitor.visit_u32(self.data.read_u32::<BigEndian>()?)
+            visitor.visit_u32(self.read_i32()? as u32)
         } else {
             self.deserialize_any(visitor)
         }
@@ -154,7 +308,7 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: serde::de::Visitor<'de>,
     {
         if !self.name && self.current_type == TagType::Long {
-            visitor.visit_u64(self.data.read_u64::<BigEndian>()?)
+            visitor.visit_u64(self.read_i64()? as u64)
         } else {
             self.deserialize_any(visitor)
         }
@@ -168,6 +322,59 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_some(self)
     }
 
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // unit structs are written as empty compounds
+        if !self.name && self.current_type == TagType::Compound {
+            self.read_end()?;
+            visitor.visit_unit()
+        } else {
+            Err(serde::de::Error::invalid_type(
+                serde::de::Unexpected::Other("non-empty tag"),
+                &name,
+            ))
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        if name != array::VALUE {
+            return visitor.visit_newtype_struct(self);
+        }
+
+        // values are told whether it is an array or a list, by visiting arrays as
+        // newtype variants named after the array type
+        match self.current_type {
+            _ if self.name => self.deserialize_any(visitor),
+            TagType::ByteArray => visitor.visit_enum(ArrayAccess("ByteArray", self)),
+            TagType::IntArray => visitor.visit_enum(ArrayAccess("IntArray", self)),
+            TagType::LongArray => visitor.visit_enum(ArrayAccess("LongArray", self)),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.current_type {
+            _ if self.name => self.deserialize_any(visitor),
+            // unit variants are written as strings, all others as single-key compounds
+            TagType::String => visitor.visit_enum(self.read_str()?.into_deserializer()),
+            TagType::Compound => visitor.visit_enum(self),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
     fn is_human_readable(&self) -> bool {
         false
     }
@@ -208,7 +415,7 @@ impl<'a, 'de> serde::de::MapAccess<'de> for &'a mut Deserializer<'de> {
     where
         K: serde::de::DeserializeSeed<'de>,
     {
-        self.current_type = TagType::try_from(self.data.read_i8()?).unwrap();
+        self.current_type = self.read_type()?;
         if !matches!(self.current_type, TagType::End) {
             self.name = true;
             seed.deserialize(&mut **self).map(Some)
@@ -224,3 +431,114 @@ impl<'a, 'de> serde::de::MapAccess<'de> for &'a mut Deserializer<'de> {
         seed.deserialize(&mut **self)
     }
 }
+
+impl<'a, 'de> serde::de::EnumAccess<'de> for &'a mut Deserializer<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        // the only key of the compound is the variant
+        self.current_type = self.read_type()?;
+        if self.current_type == TagType::End {
+            return Err(serde::de::Error::custom(
+                "expected variant, found empty compound",
+            ));
+        }
+        self.name = true;
+        Ok((seed.deserialize(&mut *self)?, self))
+    }
+}
+
+impl<'a, 'de> serde::de::VariantAccess<'de> for &'a mut Deserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Err(serde::de::Error::invalid_type(
+            serde::de::Unexpected::NewtypeVariant,
+            &"unit variant",
+        ))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        let value = seed.deserialize(&mut *self)?;
+        self.read_end()?;
+        Ok(value)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let value = serde::de::Deserializer::deserialize_any(&mut *self, visitor)?;
+        self.read_end()?;
+        Ok(value)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let value = serde::de::Deserializer::deserialize_any(&mut *self, visitor)?;
+        self.read_end()?;
+        Ok(value)
+    }
+}
+
+struct ArrayAccess<'a, 'de: 'a>(&'static str, &'a mut Deserializer<'de>);
+
+impl<'a, 'de> serde::de::EnumAccess<'de> for ArrayAccess<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(self.0))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'a, 'de> serde::de::VariantAccess<'de> for ArrayAccess<'a, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Err(serde::de::Error::invalid_type(
+            serde::de::Unexpected::NewtypeVariant,
+            &"unit variant",
+        ))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.1)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        Err(serde::de::Error::invalid_type(
+            serde::de::Unexpected::NewtypeVariant,
+            &"tuple variant",
+        ))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        Err(serde::de::Error::invalid_type(
+            serde::de::Unexpected::NewtypeVariant,
+            &"struct variant",
+        ))
+    }
+}
@@ -9,6 +9,23 @@ pub enum Error {
 
     #[error("IO error")]
     Io(#[from] std::io::Error),
+    #[error("UTF8 error")]
+    Utf8(#[from] std::str::Utf8Error),
+
+    #[error("Unknown tag type: {0}")]
+    UnknownTagType(i8),
+    #[error("Invalid length: {0}")]
+    InvalidLength(i32),
+    #[error("VarInt too long")]
+    VarIntTooLong,
+    #[error("Depth limit exceeded")]
+    DepthLimitExceeded,
+    #[error("Length limit exceeded: {0}")]
+    LengthLimitExceeded(usize),
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+    #[error("Invalid SNBT at {0}: {1}")]
+    InvalidSnbt(usize, &'static str),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -1,8 +1,69 @@
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
+pub use array::{byte_array, int_array, long_array};
+
+mod array;
 pub mod de;
 pub mod error;
+pub mod path;
+pub mod reader;
 pub mod ser;
+pub mod snbt;
+pub mod value;
+
+/// Binary encoding of NBT, which differs between editions and between files
+/// and network
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Encoding {
+    pub endian: Endian,
+    /// Whether the root tag has a name, it is always empty
+    pub root_name: bool,
+    /// Whether ints, longs and lengths are written as VarInts, ints and longs
+    /// are zigzag-encoded
+    pub var_int: bool,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+impl Encoding {
+    /// Files and network of Minecraft: Java Edition
+    pub const JAVA: Self = Self {
+        endian: Endian::Big,
+        root_name: true,
+        var_int: false,
+    };
+
+    /// Network of Minecraft: Java Edition since 1.20.2
+    pub const JAVA_NETWORK: Self = Self {
+        endian: Endian::Big,
+        root_name: false,
+        var_int: false,
+    };
+
+    /// Files of Minecraft: Bedrock Edition, like level.dat
+    pub const BEDROCK: Self = Self {
+        endian: Endian::Little,
+        root_name: true,
+        var_int: false,
+    };
+
+    /// Network of Minecraft: Bedrock Edition
+    pub const BEDROCK_NETWORK: Self = Self {
+        endian: Endian::Little,
+        root_name: true,
+        var_int: true,
+    };
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self::JAVA
+    }
+}
 
 #[derive(Default, Copy, Clone, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
 #[repr(i8)]
@@ -0,0 +1,225 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    error::{Error, Result},
+    value::Value,
+};
+
+/// NBT path, like `foo.bar[0]."baz qux"`, used by commands and text components
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NbtPath {
+    nodes: Vec<NbtPathNode>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum NbtPathNode {
+    /// Value of the key in a compound
+    Key(String),
+    /// Element of a list, negative indices count from the end
+    Index(i32),
+    /// All elements of a list
+    All,
+}
+
+impl NbtPath {
+    /// Returns all values matching the path, paths with [] can match multiple
+    /// values
+    pub fn get<'v>(&self, value: &'v Value) -> Vec<&'v Value> {
+        let mut values = vec![value];
+        for node in &self.nodes {
+            values = values
+                .into_iter()
+                .flat_map(|value| -> Vec<&'v Value> {
+                    match (node, value) {
+                        (NbtPathNode::Key(key), Value::Compound(compound)) => {
+                            compound.get(key).into_iter().collect()
+                        }
+                        (NbtPathNode::Index(index), Value::List(list)) => {
+                            let index = if *index < 0 {
+                                list.len() as i64 + *index as i64
+                            } else {
+                                *index as i64
+                            };
+                            usize::try_from(index)
+                                .ok()
+                                .and_then(|index| list.get(index))
+                                .into_iter()
+                                .collect()
+                        }
+                        (NbtPathNode::All, Value::List(list)) => list.iter().collect(),
+                        _ => vec![],
+                    }
+                })
+                .collect();
+        }
+        values
+    }
+}
+
+impl FromStr for NbtPath {
+    type Err = Error;
+
+    fn from_str(path: &str) -> Result<Self> {
+        let invalid_path = || Error::InvalidPath(path.to_string());
+
+        let mut nodes = vec![];
+        let mut chars = path.chars().peekable();
+        // the path can start with an index, otherwise it starts with a key
+        let mut expect_key = chars.peek() != Some(&'[');
+        while chars.peek().is_some() || expect_key {
+            if expect_key {
+                expect_key = false;
+
+                let mut key = String::new();
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    loop {
+                        match chars.next().ok_or_else(invalid_path)? {
+                            '"' => break,
+                            '\\' => key.push(chars.next().ok_or_else(invalid_path)?),
+                            char => key.push(char),
+                        }
+                    }
+                } else {
+                    while let Some(&char) = chars.peek() {
+                        if matches!(char, '.' | '[' | ']' | '{' | '}' | '"') || char.is_whitespace()
+                        {
+                            break;
+                        }
+                        key.push(char);
+                        chars.next();
+                    }
+                    if key.is_empty() {
+                        return Err(invalid_path());
+                    }
+                }
+                nodes.push(NbtPathNode::Key(key));
+                continue;
+            }
+
+            match chars.next().ok_or_else(invalid_path)? {
+                '.' => expect_key = true,
+                '[' => {
+                    let mut index = String::new();
+                    loop {
+                        match chars.next().ok_or_else(invalid_path)? {
+                            ']' => break,
+                            char => index.push(char),
+                        }
+                    }
+                    nodes.push(if index.is_empty() {
+                        NbtPathNode::All
+                    } else {
+                        NbtPathNode::Index(index.parse().map_err(|_| invalid_path())?)
+                    });
+                }
+                // compound filters ({...}) are not supported
+                _ => return Err(invalid_path()),
+            }
+        }
+        Ok(Self { nodes })
+    }
+}
+
+impl fmt::Display for NbtPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, node) in self.nodes.iter().enumerate() {
+            match node {
+                NbtPathNode::Key(key) => {
+                    if index != 0 {
+                        f.write_str(".")?;
+                    }
+                    if key.chars().all(|char| {
+                        !matches!(char, '.' | '[' | ']' | '{' | '}' | '"') && !char.is_whitespace()
+                    }) {
+                        f.write_str(key)?;
+                    } else {
+                        write!(f, "\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\""))?;
+                    }
+                }
+                NbtPathNode::Index(index) => write!(f, "[{index}]")?,
+                NbtPathNode::All => f.write_str("[]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{path::NbtPath, snbt, value::Value};
+
+    fn value() -> Value {
+        snbt::parse(r#"{foo: {bar: [{baz: 1}, {baz: 2}, {baz: 3}]}, "qux quux": 4b}"#).unwrap()
+    }
+
+    fn get(path: &str) -> Vec<Value> {
+        path.parse::<NbtPath>()
+            .unwrap()
+            .get(&value())
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn parse_and_display() {
+        for path in [
+            "foo",
+            "foo.bar",
+            "foo.bar[0].baz",
+            "foo.bar[-1]",
+            "foo.bar[].baz",
+            "[0]",
+            "\"qux quux\"",
+            "foo.\"a\\\"b\"",
+        ] {
+            assert_eq!(path.parse::<NbtPath>().unwrap().to_string(), path);
+        }
+        assert_eq!(
+            "\"foo\".\"bar\"".parse::<NbtPath>().unwrap().to_string(),
+            "foo.bar"
+        );
+    }
+
+    #[test]
+    fn get_compound_key() {
+        assert_eq!(get("foo.bar[0]"), [snbt::parse("{baz: 1}").unwrap()]);
+        assert_eq!(get("\"qux quux\""), [Value::Byte(4)]);
+        assert!(get("foo.qux").is_empty());
+        assert!(get("foo.bar.baz").is_empty());
+    }
+
+    #[test]
+    fn get_list_index() {
+        assert_eq!(get("foo.bar[1].baz"), [Value::Int(2)]);
+        assert_eq!(get("foo.bar[-1].baz"), [Value::Int(3)]);
+        assert!(get("foo.bar[3]").is_empty());
+        assert!(get("foo.bar[-4]").is_empty());
+        assert!(get("foo[0]").is_empty());
+    }
+
+    #[test]
+    fn get_list_all() {
+        assert_eq!(
+            get("foo.bar[].baz"),
+            [Value::Int(1), Value::Int(2), Value::Int(3)]
+        );
+        assert!(get("\"qux quux\"[]").is_empty());
+    }
+
+    #[test]
+    fn reject_filters() {
+        assert!("foo{bar: 1}".parse::<NbtPath>().is_err());
+        assert!("foo.bar[{baz: 1}]".parse::<NbtPath>().is_err());
+    }
+
+    #[test]
+    fn reject_invalid() {
+        for path in [
+            "", "foo.", "foo..bar", ".foo", "foo[", "foo[0", "foo[a]", "foo]", "\"foo",
+        ] {
+            assert!(path.parse::<NbtPath>().is_err(), "{path}");
+        }
+    }
+}
@@ -0,0 +1,740 @@
+use std::io::Read;
+
+use byteorder::ReadBytesExt;
+use serde::de::IntoDeserializer;
+
+use crate::{
+    array,
+    de::{read_f32, read_f64, read_i16, read_i32, read_i64, read_str_len},
+    error::{Error, Result},
+    value::{visit_seq, EnumDeserializer, Value},
+    Encoding, TagType,
+};
+
+/// Limits for reading untrusted data, like item NBT sent by clients
+#[derive(Copy, Clone, Debug)]
+pub struct Limits {
+    /// Maximum nesting of lists and compounds
+    pub max_depth: usize,
+    /// Maximum length of strings, arrays and lists
+    pub max_len: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            // same as Minecraft
+            max_depth: 512,
+            max_len: 1 << 21,
+        }
+    }
+}
+
+/// Part of an NBT document, lists and compounds are split into start, content
+/// and end
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// Name of the following value in a compound
+    Name(String),
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    /// Start of a list with the number of elements, followed by the elements
+    /// and [Event::ListEnd]
+    ListStart(usize),
+    ListEnd,
+    /// Start of a compound, followed by names and values and
+    /// [Event::CompoundEnd]
+    CompoundStart,
+    CompoundEnd,
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+/// Reads NBT event by event, without buffering the whole document
+pub struct Reader<R> {
+    reader: R,
+    encoding: Encoding,
+    limits: Limits,
+
+    stack: Vec<Container>,
+    /// Type of the value which is read next
+    next_type: Option<TagType>,
+}
+
+enum Container {
+    Compound,
+    List { type_: TagType, remaining: usize },
+}
+
+impl<R: Read> Reader<R> {
+    /// Reads the type and name of the root tag
+    pub fn new(reader: R, encoding: Encoding, limits: Limits) -> Result<Self> {
+        let mut self_ = Self {
+            reader,
+            encoding,
+            limits,
+
+            stack: vec![],
+            next_type: None,
+        };
+        let type_ = self_.read_type()?;
+        if type_ != TagType::End {
+            if encoding.root_name {
+                self_.read_string()?;
+            }
+            self_.next_type = Some(type_);
+        }
+        Ok(self_)
+    }
+
+    /// Returns the next event, or None at the end of the document
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<Event>> {
+        loop {
+            if let Some(type_) = self.next_type.take() {
+                return self.read_value(type_).map(Some);
+            }
+
+            match self.stack.last_mut() {
+                None => return Ok(None),
+                Some(Container::Compound) => {
+                    let type_ = self.read_type()?;
+                    if type_ == TagType::End {
+                        self.stack.pop();
+                        return Ok(Some(Event::CompoundEnd));
+                    }
+
+                    let name = self.read_string()?;
+                    self.next_type = Some(type_);
+                    return Ok(Some(Event::Name(name)));
+                }
+                Some(Container::List { type_, remaining }) => {
+                    if *remaining == 0 {
+                        self.stack.pop();
+                        return Ok(Some(Event::ListEnd));
+                    }
+
+                    *remaining -= 1;
+                    self.next_type = Some(*type_);
+                }
+            }
+        }
+    }
+
+    /// Skips the next event, and everything up to the matching end if it starts
+    /// a list or compound, values are skipped without being allocated
+    pub fn skip(&mut self) -> Result<()> {
+        let depth = self.stack.len();
+        loop {
+            match self.next_type.take() {
+                Some(type_ @ (TagType::List | TagType::Compound)) => {
+                    self.read_value(type_)?;
+                }
+                Some(type_) => self.skip_value(type_)?,
+                None => match self.stack.last_mut() {
+                    Some(Container::List { type_, remaining }) if *remaining != 0 => {
+                        *remaining -= 1;
+                        self.next_type = Some(*type_);
+                    }
+                    _ => {
+                        self.next()?;
+                    }
+                },
+            }
+
+            if self.stack.len() <= depth && self.next_type.is_none() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn read_value(&mut self, type_: TagType) -> Result<Event> {
+        Ok(match type_ {
+            TagType::End => return Err(Error::UnknownTagType(type_.into())),
+            TagType::Byte => Event::Byte(self.reader.read_i8()?),
+            TagType::Short => Event::Short(read_i16(&mut self.reader, self.encoding)?),
+            TagType::Int => Event::Int(read_i32(&mut self.reader, self.encoding)?),
+            TagType::Long => Event::Long(read_i64(&mut self.reader, self.encoding)?),
+            TagType::Float => Event::Float(read_f32(&mut self.reader, self.encoding)?),
+            TagType::Double => Event::Double(read_f64(&mut self.reader, self.encoding)?),
+            TagType::ByteArray => {
+                let len = self.read_len()?;
+                Event::ByteArray(
+                    self.read_bytes(len)?
+                        .into_iter()
+                        .map(|value| value as i8)
+                        .collect(),
+                )
+            }
+            TagType::String => Event::String(self.read_string()?),
+            TagType::List => {
+                let element_type = self.read_type()?;
+                let len = self.read_len()?;
+                self.push(Container::List {
+                    type_: element_type,
+                    remaining: len,
+                })?;
+                Event::ListStart(len)
+            }
+            TagType::Compound => {
+                self.push(Container::Compound)?;
+                Event::CompoundStart
+            }
+            TagType::IntArray => {
+                let len = self.read_len()?;
+                // the length is only trusted as far as it has been read
+                let mut values = Vec::with_capacity(len.min(4096));
+                for _ in 0..len {
+                    values.push(read_i32(&mut self.reader, self.encoding)?);
+                }
+                Event::IntArray(values)
+            }
+            TagType::LongArray => {
+                let len = self.read_len()?;
+                let mut values = Vec::with_capacity(len.min(4096));
+                for _ in 0..len {
+                    values.push(read_i64(&mut self.reader, self.encoding)?);
+                }
+                Event::LongArray(values)
+            }
+        })
+    }
+
+    fn skip_value(&mut self, type_: TagType) -> Result<()> {
+        match type_ {
+            TagType::ByteArray => {
+                let len = self.read_len()?;
+                self.discard(len as u64)?;
+            }
+            TagType::String => {
+                let len = read_str_len(&mut self.reader, self.encoding)?;
+                self.discard(len as u64)?;
+            }
+            // VarInts have no fixed size
+            TagType::IntArray | TagType::LongArray if self.encoding.var_int => {
+                let element_type = match type_ {
+                    TagType::IntArray => TagType::Int,
+                    _ => TagType::Long,
+                };
+                for _ in 0..self.read_len()? {
+                    self.skip_value(element_type)?;
+                }
+            }
+            TagType::IntArray => {
+                let len = self.read_len()?;
+                self.discard(len as u64 * 4)?;
+            }
+            TagType::LongArray => {
+                let len = self.read_len()?;
+                self.discard(len as u64 * 8)?;
+            }
+            _ => {
+                self.read_value(type_)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, container: Container) -> Result<()> {
+        if self.stack.len() >= self.limits.max_depth {
+            return Err(Error::DepthLimitExceeded);
+        }
+
+        self.stack.push(container);
+        Ok(())
+    }
+
+    fn read_type(&mut self) -> Result<TagType> {
+        let type_ = self.reader.read_i8()?;
+        TagType::try_from(type_).map_err(|_| Error::UnknownTagType(type_))
+    }
+
+    fn read_len(&mut self) -> Result<usize> {
+        let len = read_i32(&mut self.reader, self.encoding)?;
+        if len < 0 {
+            return Err(Error::InvalidLength(len));
+        }
+
+        self.check_len(len as usize)
+    }
+
+    fn check_len(&self, len: usize) -> Result<usize> {
+        if len > self.limits.max_len {
+            return Err(Error::LengthLimitExceeded(len));
+        }
+
+        Ok(len)
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        let len = read_str_len(&mut self.reader, self.encoding)?;
+        let len = self.check_len(len as usize)?;
+        String::from_utf8(self.read_bytes(len)?).map_err(|error| error.utf8_error().into())
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(len.min(4096));
+        (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+
+        Ok(bytes)
+    }
+
+    fn discard(&mut self, len: u64) -> Result<()> {
+        if std::io::copy(&mut (&mut self.reader).take(len), &mut std::io::sink())? != len {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Deserializer on top of the event reader, see [crate::de::from_reader]
+pub(crate) struct Deserializer<R> {
+    reader: Reader<R>,
+    peeked: Option<Event>,
+}
+
+impl<R: Read> Deserializer<R> {
+    pub(crate) fn new(reader: Reader<R>) -> Self {
+        Self {
+            reader,
+            peeked: None,
+        }
+    }
+
+    fn next(&mut self) -> Result<Option<Event>> {
+        match self.peeked.take() {
+            Some(event) => Ok(Some(event)),
+            None => self.reader.next(),
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<&Event>> {
+        if self.peeked.is_none() {
+            self.peeked = self.reader.next()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn expect_end(&mut self) -> Result<()> {
+        match self.next()? {
+            Some(Event::CompoundEnd) => Ok(()),
+            _ => Err(serde::de::Error::custom("expected end of compound")),
+        }
+    }
+
+    /// Skips everything up to and including the end of the current list or
+    /// compound
+    fn finish(&mut self) -> Result<()> {
+        let mut depth = 0usize;
+        loop {
+            match self.next()? {
+                Some(Event::ListStart(_) | Event::CompoundStart) => depth += 1,
+                Some(Event::ListEnd | Event::CompoundEnd) => {
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                    depth -= 1;
+                }
+                Some(_) => {}
+                None => return Err(serde::de::Error::custom("unexpected end of document")),
+            }
+        }
+    }
+
+    fn visit<'de, V>(&mut self, event: Option<Event>, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match event {
+            None => visitor.visit_unit(),
+            Some(Event::Byte(v)) => visitor.visit_i8(v),
+            Some(Event::Short(v)) => visitor.visit_i16(v),
+            Some(Event::Int(v)) => visitor.visit_i32(v),
+            Some(Event::Long(v)) => visitor.visit_i64(v),
+            Some(Event::Float(v)) => visitor.visit_f32(v),
+            Some(Event::Double(v)) => visitor.visit_f64(v),
+            Some(Event::ByteArray(v)) => visit_seq(v.into_iter().map(Value::Byte), visitor),
+            Some(Event::String(v)) => visitor.visit_string(v),
+            Some(Event::ListStart(len)) => {
+                let mut seq = SeqAccess {
+                    de: self,
+                    remaining: len,
+                    finished: false,
+                };
+                let value = visitor.visit_seq(&mut seq)?;
+                if !seq.finished {
+                    self.finish()?;
+                }
+                Ok(value)
+            }
+            Some(Event::CompoundStart) => {
+                let mut map = MapAccess {
+                    de: self,
+                    finished: false,
+                };
+                let value = visitor.visit_map(&mut map)?;
+                if !map.finished {
+                    self.finish()?;
+                }
+                Ok(value)
+            }
+            Some(Event::IntArray(v)) => visit_seq(v.into_iter().map(Value::Int), visitor),
+            Some(Event::LongArray(v)) => visit_seq(v.into_iter().map(Value::Long), visitor),
+            Some(Event::Name(_) | Event::ListEnd | Event::CompoundEnd) => {
+                Err(serde::de::Error::custom("expected value"))
+            }
+        }
+    }
+}
+
+impl<'de, 'a, R: Read> serde::de::Deserializer<'de> for &'a mut Deserializer<R> {
+    type Error = Error;
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 f32 f64 char str string bytes byte_buf unit seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let event = self.next()?;
+        self.visit(event, visitor)
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.next()? {
+            Some(Event::Byte(0)) => visitor.visit_bool(false),
+            Some(Event::Byte(1)) => visitor.visit_bool(true),
+            event => self.visit(event, visitor),
+        }
+    }
+
+    // unsigned values are reinterpreted, like when reading from slices
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.next()? {
+            Some(Event::Byte(v)) => visitor.visit_u8(v as u8),
+            event => self.visit(event, visitor),
+        }
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.next()? {
+            Some(Event::Short(v)) => visitor.visit_u16(v as u16),
+            event => self.visit(event, visitor),
+        }
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.next()? {
+            Some(Event::Int(v)) => visitor.visit_u32(v as u32),
+            event => self.visit(event, visitor),
+        }
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.next()? {
+            Some(Event::Long(v)) => visitor.visit_u64(v as u64),
+            event => self.visit(event, visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // this is only needed for support reading optional fields
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // unit structs are written as empty compounds
+        match self.next()? {
+            Some(Event::CompoundStart) => {
+                self.expect_end()?;
+                visitor.visit_unit()
+            }
+            _ => Err(serde::de::Error::invalid_type(
+                serde::de::Unexpected::Other("non-empty tag"),
+                &name,
+            )),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        if name != array::VALUE {
+            return visitor.visit_newtype_struct(self);
+        }
+
+        // same as for slices, arrays are visited as newtype variants
+        match self.next()? {
+            Some(Event::ByteArray(v)) => visitor.visit_enum(EnumDeserializer((
+                "ByteArray".to_string(),
+                Value::ByteArray(v),
+            ))),
+            Some(Event::IntArray(v)) => visitor.visit_enum(EnumDeserializer((
+                "IntArray".to_string(),
+                Value::IntArray(v),
+            ))),
+            Some(Event::LongArray(v)) => visitor.visit_enum(EnumDeserializer((
+                "LongArray".to_string(),
+                Value::LongArray(v),
+            ))),
+            event => self.visit(event, visitor),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // unit variants are written as strings, all others as single-key compounds
+        match self.next()? {
+            Some(Event::String(variant)) => {
+                visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(variant))
+            }
+            Some(Event::CompoundStart) => visitor.visit_enum(self),
+            event => self.visit(event, visitor),
+        }
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct SeqAccess<'a, R> {
+    de: &'a mut Deserializer<R>,
+
+    remaining: usize,
+    finished: bool,
+}
+
+impl<'de, 'a, R: Read> serde::de::SeqAccess<'de> for SeqAccess<'a, R> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        if let Some(Event::ListEnd) = self.de.peek()? {
+            self.de.next()?;
+            self.finished = true;
+            return Ok(None);
+        }
+
+        self.remaining = self.remaining.saturating_sub(1);
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+struct MapAccess<'a, R> {
+    de: &'a mut Deserializer<R>,
+
+    finished: bool,
+}
+
+impl<'de, 'a, R: Read> serde::de::MapAccess<'de> for MapAccess<'a, R> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: serde::de::DeserializeSeed<'de>,
+    {
+        match self.de.next()? {
+            Some(Event::Name(name)) => seed
+                .deserialize(IntoDeserializer::<Error>::into_deserializer(name))
+                .map(Some),
+            Some(Event::CompoundEnd) => {
+                self.finished = true;
+                Ok(None)
+            }
+            _ => Err(serde::de::Error::custom("expected name or end of compound")),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+impl<'de, 'a, R: Read> serde::de::EnumAccess<'de> for &'a mut Deserializer<R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        // the only key of the compound is the variant
+        match self.next()? {
+            Some(Event::Name(variant)) => Ok((
+                seed.deserialize(IntoDeserializer::<Error>::into_deserializer(variant))?,
+                self,
+            )),
+            _ => Err(serde::de::Error::custom(
+                "expected variant, found empty compound",
+            )),
+        }
+    }
+}
+
+impl<'de, 'a, R: Read> serde::de::VariantAccess<'de> for &'a mut Deserializer<R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Err(serde::de::Error::invalid_type(
+            serde::de::Unexpected::NewtypeVariant,
+            &"unit variant",
+        ))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        let value = seed.deserialize(&mut *self)?;
+        self.expect_end()?;
+        Ok(value)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let value = serde::de::Deserializer::deserialize_any(&mut *self, visitor)?;
+        self.expect_end()?;
+        Ok(value)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let value = serde::de::Deserializer::deserialize_any(&mut *self, visitor)?;
+        self.expect_end()?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::Error,
+        reader::{Event, Limits, Reader},
+        snbt, Encoding,
+    };
+
+    fn from_snbt(snbt: &str, limits: Limits) -> Reader<std::io::Cursor<Vec<u8>>> {
+        let data = crate::ser::to_vec(&snbt::parse(snbt).unwrap()).unwrap();
+        Reader::new(std::io::Cursor::new(data), Encoding::JAVA, limits).unwrap()
+    }
+
+    #[test]
+    fn events() {
+        let mut reader = from_snbt("{a:[1,2],b:{c:[L;3l]}}", Limits::default());
+        let mut events = vec![];
+        while let Some(event) = reader.next().unwrap() {
+            events.push(event);
+        }
+        assert_eq!(
+            events,
+            vec![
+                Event::CompoundStart,
+                Event::Name("a".to_string()),
+                Event::ListStart(2),
+                Event::Int(1),
+                Event::Int(2),
+                Event::ListEnd,
+                Event::Name("b".to_string()),
+                Event::CompoundStart,
+                Event::Name("c".to_string()),
+                Event::LongArray(vec![3]),
+                Event::CompoundEnd,
+                Event::CompoundEnd,
+            ]
+        );
+    }
+
+    #[test]
+    fn skip() {
+        let mut reader = from_snbt("{a:[{b:\"c\"}],d:[I;1,2],e:1b}", Limits::default());
+        assert_eq!(reader.next().unwrap(), Some(Event::CompoundStart));
+        assert_eq!(reader.next().unwrap(), Some(Event::Name("a".to_string())));
+        reader.skip().unwrap();
+        assert_eq!(reader.next().unwrap(), Some(Event::Name("d".to_string())));
+        reader.skip().unwrap();
+        assert_eq!(reader.next().unwrap(), Some(Event::Name("e".to_string())));
+        assert_eq!(reader.next().unwrap(), Some(Event::Byte(1)));
+        assert_eq!(reader.next().unwrap(), Some(Event::CompoundEnd));
+        assert_eq!(reader.next().unwrap(), None);
+    }
+
+    #[test]
+    fn limits() {
+        let limits = Limits {
+            max_depth: 4,
+            max_len: 4,
+        };
+
+        let mut reader = from_snbt("[[[[[]]]]]", limits);
+        let error = loop {
+            if let Err(error) = reader.next() {
+                break error;
+            }
+        };
+        assert!(matches!(error, Error::DepthLimitExceeded));
+
+        let mut reader = from_snbt("{a:[B;1b,2b,3b,4b,5b]}", limits);
+        reader.next().unwrap();
+        reader.next().unwrap();
+        assert!(matches!(reader.next(), Err(Error::LengthLimitExceeded(5))));
+    }
+}
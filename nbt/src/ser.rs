@@ -1,27 +1,37 @@
 use std::io::Write;
 
-use byteorder::{BigEndian, WriteBytesExt};
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
 
 use crate::{
+    array,
     error::{Error, Result},
-    TagType,
+    Encoding, Endian, TagType,
 };
 
 pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: serde::ser::Serialize,
+{
+    to_vec_with(value, Encoding::JAVA)
+}
+
+pub fn to_vec_with<T>(value: &T, encoding: Encoding) -> Result<Vec<u8>>
 where
     T: serde::ser::Serialize,
 {
     let mut ser = Serializer {
         data: Vec::new(),
+        encoding,
 
         last_type: TagType::default(),
+        array: None,
     };
     value.serialize(&mut ser)?;
 
     let mut header = Vec::new();
     header.write_i8(ser.last_type.into())?;
-    if ser.last_type != TagType::End {
-        header.write_i16::<BigEndian>(0)?;
+    if ser.last_type != TagType::End && encoding.root_name {
+        write_str(&mut header, encoding, "")?;
     }
     ser.data.splice(0..0, header);
 
@@ -30,20 +40,23 @@ where
 
 struct Serializer {
     data: Vec<u8>,
+    encoding: Encoding,
 
     last_type: TagType,
+    /// Array and element type for the next sequence, see [array]
+    array: Option<(TagType, TagType)>,
 }
 
 impl<'ser> serde::ser::Serializer for &'ser mut Serializer {
     type Ok = ();
     type Error = Error;
     type SerializeSeq = SerializeSeq<'ser>;
-    type SerializeTuple = Self;
-    type SerializeTupleStruct = Self;
-    type SerializeTupleVariant = Self;
+    type SerializeTuple = SerializeSeq<'ser>;
+    type SerializeTupleStruct = SerializeSeq<'ser>;
+    type SerializeTupleVariant = SerializeSeq<'ser>;
     type SerializeMap = SerializeMap<'ser>;
     type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
+    type SerializeStructVariant = SerializeStructVariant<'ser>;
 
     fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
         self.data.write_i8(match v {
@@ -61,19 +74,19 @@ impl<'ser> serde::ser::Serializer for &'ser mut Serializer {
     }
 
     fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
-        self.data.write_i16::<BigEndian>(v)?;
+        write_i16(&mut self.data, self.encoding, v)?;
         self.last_type = TagType::Short;
         Ok(())
     }
 
     fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
-        self.data.write_i32::<BigEndian>(v)?;
+        write_i32(&mut self.data, self.encoding, v)?;
         self.last_type = TagType::Int;
         Ok(())
     }
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
-        self.data.write_i64::<BigEndian>(v)?;
+        write_i64(&mut self.data, self.encoding, v)?;
         self.last_type = TagType::Long;
         Ok(())
     }
@@ -85,49 +98,53 @@ impl<'ser> serde::ser::Serializer for &'ser mut Serializer {
     }
 
     fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
-        self.data.write_i16::<BigEndian>(v as i16)?;
+        write_i16(&mut self.data, self.encoding, v as i16)?;
         self.last_type = TagType::Short;
         Ok(())
     }
 
     fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
-        self.data.write_i32::<BigEndian>(v as i32)?;
+        write_i32(&mut self.data, self.encoding, v as i32)?;
         self.last_type = TagType::Int;
         Ok(())
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
-        self.data.write_i64::<BigEndian>(v as i64)?;
+        write_i64(&mut self.data, self.encoding, v as i64)?;
         self.last_type = TagType::Long;
         Ok(())
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
-        self.data.write_f32::<BigEndian>(v)?;
+        match self.encoding.endian {
+            Endian::Big => self.data.write_f32::<BigEndian>(v)?,
+            Endian::Little => self.data.write_f32::<LittleEndian>(v)?,
+        }
         self.last_type = TagType::Float;
         Ok(())
     }
 
     fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
-        self.data.write_f64::<BigEndian>(v)?;
+        match self.encoding.endian {
+            Endian::Big => self.data.write_f64::<BigEndian>(v)?,
+            Endian::Little => self.data.write_f64::<LittleEndian>(v)?,
+        }
         self.last_type = TagType::Double;
         Ok(())
     }
 
-    fn serialize_char(self, _v: char) -> Result<Self::Ok> {
-        unimplemented!()
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok> {
-        let bytes = v.as_bytes();
-        self.data.write_i16::<BigEndian>(bytes.len() as i16)?;
-        self.data.write_all(bytes)?;
+        write_str(&mut self.data, self.encoding, v)?;
         self.last_type = TagType::String;
         Ok(())
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
-        self.data.write_i32::<BigEndian>(v.len() as i32)?;
+        write_i32(&mut self.data, self.encoding, v.len() as i32)?;
         self.data.write_all(v)?;
         self.last_type = TagType::ByteArray;
         Ok(())
@@ -152,7 +169,11 @@ impl<'ser> serde::ser::Serializer for &'ser mut Serializer {
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
-        unimplemented!()
+        // unit structs are written as empty compounds, unlike units they are not
+        // omitted
+        self.data.write_i8(TagType::End.into())?;
+        self.last_type = TagType::Compound;
+        Ok(())
     }
 
     fn serialize_unit_variant(
@@ -164,68 +185,79 @@ impl<'ser> serde::ser::Serializer for &'ser mut Serializer {
         self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(
-        self,
-        _name: &'static str,
-        _value: &T,
-    ) -> Result<Self::Ok>
+    fn serialize_newtype_struct<T: ?Sized>(self, name: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: serde::Serialize,
     {
-        unimplemented!()
+        // the array type is taken by the sequence, if the value is one, and
+        // must not be left for the next sequence otherwise, also on errors
+        self.array = array::array_type(name);
+        let result = value.serialize(&mut *self);
+        self.array = None;
+        result
     }
 
     fn serialize_newtype_variant<T: ?Sized>(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<Self::Ok>
     where
         T: serde::Serialize,
     {
-        unimplemented!()
+        // variants with data are written as single-key compounds
+        self.array = None;
+        let header_offset = self.data.len();
+        value.serialize(&mut *self)?;
+        write_variant(self, header_offset, variant)
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        // Write empty header, as type and length is not known yet
+        // Header is written at the end, as type and length is not known yet
         let header_offset = self.data.len();
-        self.data.write_i8(0)?;
-        self.data.write_i32::<BigEndian>(0)?;
 
         // Reset type_, so that an empty list is of type TagType::End
         self.last_type = TagType::End;
         Ok(SerializeSeq {
+            array: self.array.take(),
             ser: self,
             header_offset,
             count: 0,
+            variant: None,
         })
     }
 
-    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        unimplemented!()
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        // tuples (and fixed-size arrays) are written as lists
+        self.serialize_seq(Some(len))
     }
 
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        unimplemented!()
+        self.serialize_seq(Some(len))
     }
 
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        unimplemented!()
+        self.array = None;
+        let mut seq = self.serialize_seq(Some(len))?;
+        seq.variant = Some(variant);
+        Ok(seq)
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        // only sequences can be arrays, not the sequences in compounds
+        self.array = None;
         Ok(SerializeMap {
             ser: self,
             header_offset: 0,
@@ -233,6 +265,7 @@ impl<'ser> serde::ser::Serializer for &'ser mut Serializer {
     }
 
     fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        self.array = None;
         Ok(self)
     }
 
@@ -240,10 +273,15 @@ impl<'ser> serde::ser::Serializer for &'ser mut Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        unimplemented!()
+        self.array = None;
+        Ok(SerializeStructVariant {
+            header_offset: self.data.len(),
+            ser: self,
+            variant,
+        })
     }
 
     fn is_human_readable(&self) -> bool {
@@ -256,6 +294,8 @@ struct SerializeSeq<'a> {
 
     header_offset: usize,
     count: i32,
+    array: Option<(TagType, TagType)>,
+    variant: Option<&'static str>,
 }
 
 impl<'a> serde::ser::SerializeSeq for SerializeSeq<'a> {
@@ -266,66 +306,93 @@ impl<'a> serde::ser::SerializeSeq for SerializeSeq<'a> {
     where
         T: serde::Serialize,
     {
-        self.count += 1;
+        let element_type = self.ser.last_type;
+        value.serialize(&mut *self.ser)?;
 
-        value.serialize(&mut *self.ser)
+        // lists can only contain elements of the same type
+        if self.count != 0 && self.ser.last_type != element_type {
+            return Err(Error::Custom(
+                "list elements must be of the same type".to_string(),
+            ));
+        }
+        self.count += 1;
+        Ok(())
     }
 
     fn end(self) -> Result<Self::Ok> {
-        let mut header = &mut self.ser.data[self.header_offset..self.header_offset + 5];
-        header.write_i8(self.ser.last_type.into())?;
-        header.write_i32::<BigEndian>(self.count)?;
-
-        self.ser.last_type = TagType::List;
-        Ok(())
+        let mut header = Vec::new();
+        let type_ = match self.array {
+            Some((type_, element_type)) => {
+                if self.count != 0 && self.ser.last_type != element_type {
+                    return Err(Error::Custom(
+                        "array elements must be of the array's type".to_string(),
+                    ));
+                }
+                type_
+            }
+            None => {
+                header.write_i8(self.ser.last_type.into())?;
+                TagType::List
+            }
+        };
+        write_i32(&mut header, self.ser.encoding, self.count)?;
+        self.ser
+            .data
+            .splice(self.header_offset..self.header_offset, header);
+
+        self.ser.last_type = type_;
+        match self.variant {
+            Some(variant) => write_variant(self.ser, self.header_offset, variant),
+            None => Ok(()),
+        }
     }
 }
 
-impl<'a> serde::ser::SerializeTuple for &'a mut Serializer {
+impl<'a> serde::ser::SerializeTuple for SerializeSeq<'a> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, _value: &T) -> Result<Self::Ok>
+    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<Self::Ok>
     where
         T: serde::Serialize,
     {
-        unimplemented!()
+        serde::ser::SerializeSeq::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        unimplemented!()
+        serde::ser::SerializeSeq::end(self)
     }
 }
 
-impl<'a> serde::ser::SerializeTupleStruct for &'a mut Serializer {
+impl<'a> serde::ser::SerializeTupleStruct for SerializeSeq<'a> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, _value: &T) -> Result<Self::Ok>
+    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<Self::Ok>
     where
         T: serde::Serialize,
     {
-        unimplemented!()
+        serde::ser::SerializeSeq::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        unimplemented!()
+        serde::ser::SerializeSeq::end(self)
     }
 }
 
-impl<'a> serde::ser::SerializeTupleVariant for &'a mut Serializer {
+impl<'a> serde::ser::SerializeTupleVariant for SerializeSeq<'a> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, _value: &T) -> Result<Self::Ok>
+    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<Self::Ok>
     where
         T: serde::Serialize,
     {
-        unimplemented!()
+        serde::ser::SerializeSeq::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        unimplemented!()
+        serde::ser::SerializeSeq::end(self)
     }
 }
 
@@ -382,9 +449,7 @@ impl<'a> serde::ser::SerializeStruct for &'a mut Serializer {
         if self.last_type != TagType::End {
             let mut header = Vec::new();
             header.write_i8(self.last_type.into())?;
-            let name_bytes = key.as_bytes();
-            header.write_i16::<BigEndian>(name_bytes.len() as i16)?;
-            header.write_all(name_bytes)?;
+            write_str(&mut header, self.encoding, key)?;
             self.data.splice(header_offset..header_offset, header);
         }
         Ok(())
@@ -397,18 +462,344 @@ impl<'a> serde::ser::SerializeStruct for &'a mut Serializer {
     }
 }
 
-impl<'a> serde::ser::SerializeStructVariant for &'a mut Serializer {
+struct SerializeStructVariant<'a> {
+    ser: &'a mut Serializer,
+
+    header_offset: usize,
+    variant: &'static str,
+}
+
+impl<'a> serde::ser::SerializeStructVariant for SerializeStructVariant<'a> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, _value: &T) -> Result<Self::Ok>
+    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: serde::Serialize,
     {
-        unimplemented!()
+        serde::ser::SerializeStruct::serialize_field(&mut self.ser, key, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        unimplemented!()
+        serde::ser::SerializeStruct::end(&mut *self.ser)?;
+        write_variant(self.ser, self.header_offset, self.variant)
+    }
+}
+
+/// Wraps the last written value, starting at the header offset, into a
+/// single-key compound named after the variant
+fn write_variant(ser: &mut Serializer, header_offset: usize, variant: &'static str) -> Result<()> {
+    // empty values are omitted, like in structs, which leaves an empty compound
+    if ser.last_type != TagType::End {
+        let mut header = Vec::new();
+        header.write_i8(ser.last_type.into())?;
+        write_str(&mut header, ser.encoding, variant)?;
+        ser.data.splice(header_offset..header_offset, header);
+    }
+    ser.data.write_i8(TagType::End.into())?;
+    ser.last_type = TagType::Compound;
+    Ok(())
+}
+
+fn write_i16(data: &mut Vec<u8>, encoding: Encoding, v: i16) -> Result<()> {
+    match encoding.endian {
+        Endian::Big => data.write_i16::<BigEndian>(v)?,
+        Endian::Little => data.write_i16::<LittleEndian>(v)?,
+    }
+    Ok(())
+}
+
+fn write_i32(data: &mut Vec<u8>, encoding: Encoding, v: i32) -> Result<()> {
+    if encoding.var_int {
+        write_var_u64(data, ((v << 1) ^ (v >> 31)) as u32 as u64);
+        return Ok(());
+    }
+
+    match encoding.endian {
+        Endian::Big => data.write_i32::<BigEndian>(v)?,
+        Endian::Little => data.write_i32::<LittleEndian>(v)?,
+    }
+    Ok(())
+}
+
+fn write_i64(data: &mut Vec<u8>, encoding: Encoding, v: i64) -> Result<()> {
+    if encoding.var_int {
+        write_var_u64(data, ((v << 1) ^ (v >> 63)) as u64);
+        return Ok(());
+    }
+
+    match encoding.endian {
+        Endian::Big => data.write_i64::<BigEndian>(v)?,
+        Endian::Little => data.write_i64::<LittleEndian>(v)?,
+    }
+    Ok(())
+}
+
+fn write_var_u64(data: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        data.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    data.push(v as u8);
+}
+
+fn write_str(data: &mut Vec<u8>, encoding: Encoding, v: &str) -> Result<()> {
+    let bytes = v.as_bytes();
+    // strings are the only lengths which are not zigzag-encoded
+    if encoding.var_int {
+        write_var_u64(data, bytes.len() as u64);
+    } else {
+        let length =
+            u16::try_from(bytes.len()).map_err(|_| Error::InvalidLength(bytes.len() as i32))?;
+        write_i16(data, encoding, length as i16)?;
+    }
+    data.write_all(bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        de::{from_reader_with, from_slice_with},
+        reader::Limits,
+        ser::to_vec_with,
+        value, Encoding,
+    };
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Unit;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Newtype(i64);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Tuple(i16, i16);
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    enum Enum {
+        Unit,
+        Newtype(String),
+        Tuple(f32, f32, f32),
+        Struct { x: i32, y: Option<u8> },
+    }
+
+    // unit variants are strings and all others compounds, which can't be mixed in
+    // lists
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Field {
+        value: Enum,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Data {
+        bool: bool,
+        char: char,
+        unit: Unit,
+        newtype: Newtype,
+        tuple: (u8, u8),
+        tuple_struct: Tuple,
+        array: [f64; 3],
+        option: Option<i32>,
+        enums: Vec<Field>,
+        nested: Vec<Vec<u16>>,
+        #[serde(with = "crate::byte_array")]
+        byte_array: Vec<i8>,
+        #[serde(with = "crate::int_array")]
+        int_array: [i32; 4],
+        #[serde(
+            with = "crate::long_array",
+            skip_serializing_if = "Option::is_none",
+            default
+        )]
+        long_array: Option<Vec<u64>>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Arrays {
+        #[serde(with = "crate::byte_array")]
+        byte_array: Vec<i8>,
+        #[serde(with = "crate::int_array")]
+        int_array: [i32; 4],
+        #[serde(with = "crate::long_array")]
+        long_array: Option<Vec<u64>>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Lists {
+        byte_array: Vec<i8>,
+        int_array: [i32; 4],
+        long_array: Option<Vec<u64>>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Compound {
+        list: Vec<i64>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Mixed {
+        #[serde(with = "crate::int_array", default)]
+        int_array: Option<Vec<i32>>,
+        int_list: Vec<i32>,
+        // the array type only applies to sequences, not to the ones in the compound
+        #[serde(with = "crate::long_array")]
+        compound: Compound,
+        #[serde(with = "crate::byte_array")]
+        byte_array: Vec<i8>,
+        long_list: Vec<i64>,
+        #[serde(with = "crate::long_array", default)]
+        long_array: Option<Vec<i64>>,
+        byte_list: Vec<i8>,
+    }
+
+    prop_compose! {
+        fn arb_mixed()(
+            int_array in any::<Option<Vec<i32>>>(),
+            int_list in any::<Vec<i32>>(),
+            compound_list in any::<Vec<i64>>(),
+            byte_array in any::<Vec<i8>>(),
+            long_list in any::<Vec<i64>>(),
+            long_array in any::<Option<Vec<i64>>>(),
+            byte_list in any::<Vec<i8>>(),
+        ) -> Mixed {
+            Mixed {
+                int_array,
+                int_list,
+                compound: Compound { list: compound_list },
+                byte_array,
+                long_list,
+                long_array,
+                byte_list,
+            }
+        }
+    }
+
+    fn arb_enum() -> impl Strategy<Value = Enum> {
+        prop_oneof![
+            Just(Enum::Unit),
+            any::<String>().prop_map(Enum::Newtype),
+            any::<(f32, f32, f32)>().prop_map(|(x, y, z)| Enum::Tuple(x, y, z)),
+            any::<(i32, Option<u8>)>().prop_map(|(x, y)| Enum::Struct { x, y }),
+        ]
+    }
+
+    prop_compose! {
+        fn arb_data()(
+            bool in any::<bool>(),
+            char in any::<char>(),
+            newtype in any::<i64>(),
+            tuple in any::<(u8, u8)>(),
+            tuple_struct in any::<(i16, i16)>(),
+            array in any::<[f64; 3]>(),
+            option in any::<Option<i32>>(),
+            enums in prop::collection::vec(arb_enum().prop_map(|value| Field { value }), 0..8),
+            nested in prop::collection::vec(prop::collection::vec(any::<u16>(), 0..8), 0..8),
+            byte_array in any::<Vec<i8>>(),
+            int_array in any::<[i32; 4]>(),
+            long_array in any::<Option<Vec<u64>>>(),
+        ) -> Data {
+            Data {
+                bool,
+                char,
+                unit: Unit,
+                newtype: Newtype(newtype),
+                tuple,
+                tuple_struct: Tuple(tuple_struct.0, tuple_struct.1),
+                array,
+                option,
+                enums,
+                nested,
+                byte_array,
+                int_array,
+                long_array,
+            }
+        }
+    }
+
+    fn arb_encoding() -> impl Strategy<Value = Encoding> {
+        prop_oneof![
+            Just(Encoding::JAVA),
+            Just(Encoding::JAVA_NETWORK),
+            Just(Encoding::BEDROCK),
+            Just(Encoding::BEDROCK_NETWORK),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(data in arb_data(), encoding in arb_encoding()) {
+            let bytes = to_vec_with(&data, encoding).unwrap();
+            let mut input = bytes.as_slice();
+            let decoded: Data = from_slice_with(&mut input, encoding).unwrap();
+            prop_assert!(input.is_empty());
+            // NaN is not equal to itself, compare the encoded form instead
+            prop_assert_eq!(&to_vec_with(&decoded, encoding).unwrap(), &bytes);
+
+            let mut input = bytes.as_slice();
+            let decoded: Data = from_reader_with(&mut input, encoding, Limits::default()).unwrap();
+            prop_assert!(input.is_empty());
+            prop_assert_eq!(&to_vec_with(&decoded, encoding).unwrap(), &bytes);
+        }
+
+        #[test]
+        fn round_trip_value(data in arb_data()) {
+            let value = value::to_value(&data).unwrap();
+            let decoded: Data = value::from_value(value.clone()).unwrap();
+            prop_assert_eq!(to_vec_with(&decoded, Encoding::JAVA).unwrap(), to_vec_with(&data, Encoding::JAVA).unwrap());
+
+            // binary and value representations must match, compound keys are sorted in values
+            let bytes = to_vec_with(&data, Encoding::JAVA).unwrap();
+            let decoded: value::Value = from_slice_with(&mut bytes.as_slice(), Encoding::JAVA).unwrap();
+            prop_assert_eq!(to_vec_with(&decoded, Encoding::JAVA).unwrap(), to_vec_with(&value, Encoding::JAVA).unwrap());
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_mixed(mixed in arb_mixed(), encoding in arb_encoding()) {
+            let bytes = to_vec_with(&mixed, encoding).unwrap();
+            let decoded: Mixed = from_slice_with(&mut bytes.as_slice(), encoding).unwrap();
+            prop_assert_eq!(&decoded, &mixed);
+
+            let value: value::Value = from_slice_with(&mut bytes.as_slice(), encoding).unwrap();
+            prop_assert_eq!(
+                value.get("int_array").is_some(),
+                mixed.int_array.is_some()
+            );
+            if let Some(int_array) = value.get("int_array") {
+                prop_assert!(matches!(int_array, value::Value::IntArray(_)));
+            }
+            prop_assert!(matches!(value["int_list"], value::Value::List(_)));
+            prop_assert!(matches!(value["compound"]["list"], value::Value::List(_)));
+            prop_assert!(matches!(value["byte_array"], value::Value::ByteArray(_)));
+            prop_assert!(matches!(value["long_list"], value::Value::List(_)));
+            prop_assert!(matches!(value["byte_list"], value::Value::List(_)));
+            prop_assert_eq!(value::to_value(&mixed).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn arrays() {
+        let lists = Lists {
+            byte_array: vec![1, 2],
+            int_array: [1, 2, 3, 4],
+            long_array: Some(vec![1, 2]),
+        };
+
+        // arrays and lists are accepted both ways
+        let bytes = to_vec_with(&lists, Encoding::JAVA).unwrap();
+        let arrays: Arrays = from_slice_with(&mut bytes.as_slice(), Encoding::JAVA).unwrap();
+        let bytes = to_vec_with(&arrays, Encoding::JAVA).unwrap();
+        let decoded: Lists = from_slice_with(&mut bytes.as_slice(), Encoding::JAVA).unwrap();
+        assert_eq!(decoded, lists);
+
+        let value: value::Value = from_slice_with(&mut bytes.as_slice(), Encoding::JAVA).unwrap();
+        assert_eq!(value["byte_array"], value::Value::ByteArray(vec![1, 2]));
+        assert_eq!(value["int_array"], value::Value::IntArray(vec![1, 2, 3, 4]));
+        assert_eq!(value["long_array"], value::Value::LongArray(vec![1, 2]));
+        assert_eq!(value::to_value(&arrays).unwrap(), value);
     }
 }
@@ -0,0 +1,515 @@
+use std::{
+    fmt::{self, Write},
+    mem,
+};
+
+use crate::{
+    error::{Error, Result},
+    value::{Compound, Value},
+};
+
+/// Parses SNBT, like `{Count:1b,id:"minecraft:stone"}`, into a value
+///
+/// Parsing is more tolerant than Minecraft: whitespace and trailing commas
+/// are allowed everywhere. Elements of lists have to be of the same type, as
+/// NBT lists can't hold mixed types.
+pub fn parse(input: &str) -> Result<Value> {
+    let mut parser = Parser { input, position: 0 };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.position != input.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+/// Deserializes a typed value from SNBT
+pub fn from_str<T>(input: &str) -> Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    crate::value::from_value(parse(input)?)
+}
+
+/// Serializes a typed value into compact SNBT
+pub fn to_string<T>(value: &T) -> Result<String>
+where
+    T: serde::ser::Serialize,
+{
+    Ok(crate::value::to_value(value)?.to_string())
+}
+
+/// Serializes a typed value into indented SNBT
+pub fn to_string_pretty<T>(value: &T) -> Result<String>
+where
+    T: serde::ser::Serialize,
+{
+    Ok(format!("{:#}", crate::value::to_value(value)?))
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &'static str) -> Error {
+        Error::InvalidSnbt(self.position, message)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.position += char.len_utf8();
+        Some(char)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(char) if char.is_whitespace()) {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char, message: &'static str) -> Result<()> {
+        self.skip_whitespace();
+        if self.next() != Some(expected) {
+            return Err(self.error(message));
+        }
+        Ok(())
+    }
+
+    /// Skips whitespace and the separator, returns true if the end is reached
+    fn next_element(&mut self, end: char) -> Result<bool> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(',') => {
+                self.next();
+                self.skip_whitespace();
+                // trailing commas are allowed
+                if self.peek() == Some(end) {
+                    self.next();
+                    return Ok(true);
+                }
+                Ok(false)
+            }
+            Some(char) if char == end => {
+                self.next();
+                Ok(true)
+            }
+            _ => Err(self.error("expected separator or end")),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_compound().map(Value::Compound),
+            Some('[') => self.parse_list_or_array(),
+            Some('"' | '\'') => self.parse_quoted_string().map(Value::String),
+            Some(_) => {
+                let token = self.parse_unquoted_string()?;
+                Ok(parse_unquoted_value(token))
+            }
+            None => Err(self.error("expected value")),
+        }
+    }
+
+    fn parse_compound(&mut self) -> Result<Compound> {
+        self.expect('{', "expected compound")?;
+
+        let mut compound = Compound::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(compound);
+        }
+        loop {
+            self.skip_whitespace();
+            let key = match self.peek() {
+                Some('"' | '\'') => self.parse_quoted_string()?,
+                _ => self.parse_unquoted_string()?.to_string(),
+            };
+            self.expect(':', "expected key-value separator")?;
+            compound.insert(key, self.parse_value()?);
+            if self.next_element('}')? {
+                return Ok(compound);
+            }
+        }
+    }
+
+    fn parse_list_or_array(&mut self) -> Result<Value> {
+        self.expect('[', "expected list")?;
+
+        // arrays are prefixed with their type, like [I;1,2,3]
+        let rest = &self.input[self.position..];
+        let array_type = match (rest.chars().next(), rest.chars().nth(1)) {
+            (Some(array_type @ ('B' | 'I' | 'L')), Some(';')) => {
+                self.position += 2;
+                Some(array_type)
+            }
+            _ => None,
+        };
+
+        let mut elements = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.next();
+        } else {
+            loop {
+                self.skip_whitespace();
+                let element_position = self.position;
+                let element = self.parse_value()?;
+                if array_type.is_none()
+                    && elements.first().is_some_and(|first_element| {
+                        mem::discriminant(first_element) != mem::discriminant(&element)
+                    })
+                {
+                    return Err(Error::InvalidSnbt(
+                        element_position,
+                        "list contains different types",
+                    ));
+                }
+                elements.push(element);
+                if self.next_element(']')? {
+                    break;
+                }
+            }
+        }
+
+        let Some(array_type) = array_type else {
+            return Ok(Value::List(elements));
+        };
+        let elements = elements
+            .into_iter()
+            .map(|element| element.as_i64())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| self.error("array contains non-integers"))?;
+        Ok(match array_type {
+            'B' => Value::ByteArray(elements.into_iter().map(|v| v as i8).collect()),
+            'I' => Value::IntArray(elements.into_iter().map(|v| v as i32).collect()),
+            _ => Value::LongArray(elements),
+        })
+    }
+
+    fn parse_quoted_string(&mut self) -> Result<String> {
+        let quote = self.next().ok_or_else(|| self.error("expected string"))?;
+
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('\\') => match self.next() {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some(char) => string.push(char),
+                    None => return Err(self.error("unterminated string")),
+                },
+                Some(char) if char == quote => return Ok(string),
+                Some(char) => string.push(char),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn parse_unquoted_string(&mut self) -> Result<&'a str> {
+        let begin = self.position;
+        while matches!(self.peek(), Some(char) if is_unquoted_char(char)) {
+            self.next();
+        }
+        if begin == self.position {
+            return Err(self.error("expected string"));
+        }
+        Ok(&self.input[begin..self.position])
+    }
+}
+
+fn is_unquoted_char(char: char) -> bool {
+    char.is_ascii_alphanumeric() || matches!(char, '_' | '-' | '.' | '+')
+}
+
+/// Unquoted tokens are numbers or booleans if they can be parsed as such,
+/// otherwise they are strings
+fn parse_unquoted_value(token: &str) -> Value {
+    match token {
+        "true" => return Value::Byte(1),
+        "false" => return Value::Byte(0),
+        _ => {}
+    }
+
+    let (number, suffix) = match token.char_indices().last() {
+        Some((index, suffix)) if suffix.is_ascii_alphabetic() => (&token[..index], Some(suffix)),
+        _ => (token, None),
+    };
+    let digits = number.strip_prefix(['-', '+']).unwrap_or(number);
+    let is_integer = !digits.is_empty() && digits.chars().all(|char| char.is_ascii_digit());
+    let is_float = !is_integer
+        && digits.contains(|char: char| char.is_ascii_digit())
+        && digits
+            .chars()
+            .all(|char| char.is_ascii_digit() || matches!(char, '.' | 'e' | 'E' | '-' | '+'));
+
+    let value = match suffix.map(|suffix| suffix.to_ascii_lowercase()) {
+        Some('b') if is_integer => number.parse().ok().map(Value::Byte),
+        Some('s') if is_integer => number.parse().ok().map(Value::Short),
+        Some('l') if is_integer => number.parse().ok().map(Value::Long),
+        Some('f') if is_integer || is_float => number.parse().ok().map(Value::Float),
+        Some('d') if is_integer || is_float => number.parse().ok().map(Value::Double),
+        None if is_integer => number.parse().ok().map(Value::Int),
+        None if is_float => number.parse().ok().map(Value::Double),
+        _ => None,
+    };
+    value.unwrap_or_else(|| Value::String(token.to_string()))
+}
+
+/// Prints the value as SNBT, the alternate format (`{:#}`) is indented
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_value(f, self, f.alternate().then_some(0))
+    }
+}
+
+fn write_value(f: &mut fmt::Formatter<'_>, value: &Value, indent: Option<usize>) -> fmt::Result {
+    match value {
+        Value::Byte(v) => write!(f, "{v}b"),
+        Value::Short(v) => write!(f, "{v}s"),
+        Value::Int(v) => write!(f, "{v}"),
+        Value::Long(v) => write!(f, "{v}L"),
+        Value::Float(v) => write!(f, "{v}f"),
+        Value::Double(v) => write!(f, "{v}d"),
+        Value::ByteArray(v) => write_array(f, "B", v.iter().map(|v| format!("{v}b"))),
+        Value::String(v) => write_string(f, v),
+        Value::List(v) => {
+            if v.is_empty() {
+                return f.write_str("[]");
+            }
+
+            f.write_char('[')?;
+            for (index, element) in v.iter().enumerate() {
+                if index != 0 {
+                    f.write_char(',')?;
+                }
+                write_indent(f, indent.map(|indent| indent + 1))?;
+                write_value(f, element, indent.map(|indent| indent + 1))?;
+            }
+            write_indent(f, indent)?;
+            f.write_char(']')
+        }
+        Value::Compound(v) => {
+            if v.is_empty() {
+                return f.write_str("{}");
+            }
+
+            f.write_char('{')?;
+            for (index, (key, value)) in v.iter().enumerate() {
+                if index != 0 {
+                    f.write_char(',')?;
+                }
+                write_indent(f, indent.map(|indent| indent + 1))?;
+                if !key.is_empty() && key.chars().all(is_unquoted_char) {
+                    f.write_str(key)?;
+                } else {
+                    write_string(f, key)?;
+                }
+                f.write_str(if indent.is_some() { ": " } else { ":" })?;
+                write_value(f, value, indent.map(|indent| indent + 1))?;
+            }
+            write_indent(f, indent)?;
+            f.write_char('}')
+        }
+        Value::IntArray(v) => write_array(f, "I", v.iter().map(|v| v.to_string())),
+        Value::LongArray(v) => write_array(f, "L", v.iter().map(|v| format!("{v}L"))),
+    }
+}
+
+fn write_array(
+    f: &mut fmt::Formatter<'_>,
+    array_type: &str,
+    elements: impl Iterator<Item = String>,
+) -> fmt::Result {
+    write!(f, "[{array_type};")?;
+    for (index, element) in elements.enumerate() {
+        if index != 0 {
+            f.write_char(',')?;
+        }
+        f.write_str(&element)?;
+    }
+    f.write_char(']')
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    // prefer the quote which doesn't need escaping
+    let quote = if string.contains('"') && !string.contains('\'') {
+        '\''
+    } else {
+        '"'
+    };
+    f.write_char(quote)?;
+    for char in string.chars() {
+        match char {
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\t' => f.write_str("\\t")?,
+            char if char == quote => {
+                f.write_char('\\')?;
+                f.write_char(char)?;
+            }
+            char => f.write_char(char)?,
+        }
+    }
+    f.write_char(quote)
+}
+
+fn write_indent(f: &mut fmt::Formatter<'_>, indent: Option<usize>) -> fmt::Result {
+    if let Some(indent) = indent {
+        f.write_char('\n')?;
+        for _ in 0..indent {
+            f.write_str("    ")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        error::Error,
+        snbt,
+        value::{Compound, Value},
+    };
+
+    fn value() -> Value {
+        Value::Compound(Compound::from([
+            ("byte".to_string(), Value::Byte(-1)),
+            ("short".to_string(), Value::Short(300)),
+            ("int".to_string(), Value::Int(-70000)),
+            ("long".to_string(), Value::Long(i64::MAX)),
+            ("float".to_string(), Value::Float(0.1)),
+            ("double".to_string(), Value::Double(-1e100)),
+            (
+                "string".to_string(),
+                Value::String("a \"b\" \\ 'c'".to_string()),
+            ),
+            ("123".to_string(), Value::String("123".to_string())),
+            ("key with spaces".to_string(), Value::String(String::new())),
+            (
+                "list".to_string(),
+                Value::List(vec![
+                    Value::Compound(Compound::from([(
+                        "id".to_string(),
+                        Value::String("minecraft:stone".to_string()),
+                    )])),
+                    Value::Compound(Compound::new()),
+                ]),
+            ),
+            ("empty_list".to_string(), Value::List(vec![])),
+        ]))
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = value();
+        assert_eq!(snbt::parse(&value.to_string()).unwrap(), value);
+        assert_eq!(snbt::parse(&format!("{value:#}")).unwrap(), value);
+
+        // binary encoding of the parsed value
+        let snbt_value = snbt::parse(&value.to_string()).unwrap();
+        let data = crate::ser::to_vec(&snbt_value).unwrap();
+        let binary_value = crate::de::from_slice::<Value>(&mut data.as_slice()).unwrap();
+        assert_eq!(binary_value, value);
+        assert_eq!(binary_value.to_string(), value.to_string());
+    }
+
+    #[test]
+    fn round_trip_arrays() {
+        let value = Value::Compound(Compound::from([
+            ("a".to_string(), Value::ByteArray(vec![-128, 0, 127])),
+            ("b".to_string(), Value::IntArray(vec![])),
+            ("c".to_string(), Value::LongArray(vec![i64::MIN, 1])),
+        ]));
+        assert_eq!(
+            value.to_string(),
+            "{a:[B;-128b,0b,127b],b:[I;],c:[L;-9223372036854775808L,1L]}"
+        );
+        assert_eq!(snbt::parse(&value.to_string()).unwrap(), value);
+    }
+
+    #[test]
+    fn parse_tolerant() {
+        assert_eq!(
+            snbt::parse(" { Count : 1b , id : 'minecraft:stone', tag: {Damage: 3s,}, } ").unwrap(),
+            Value::Compound(Compound::from([
+                ("Count".to_string(), Value::Byte(1)),
+                (
+                    "id".to_string(),
+                    Value::String("minecraft:stone".to_string())
+                ),
+                (
+                    "tag".to_string(),
+                    Value::Compound(Compound::from([("Damage".to_string(), Value::Short(3))])),
+                ),
+            ]))
+        );
+        assert_eq!(snbt::parse("true").unwrap(), Value::Byte(1));
+        assert_eq!(snbt::parse("1.5").unwrap(), Value::Double(1.5));
+        assert_eq!(snbt::parse("2F").unwrap(), Value::Float(2.0));
+        assert_eq!(
+            snbt::parse("300b").unwrap(),
+            Value::String("300b".to_string())
+        );
+        assert_eq!(
+            snbt::parse("1.2.3").unwrap(),
+            Value::String("1.2.3".to_string())
+        );
+        assert!(snbt::parse("{a:1").is_err());
+        assert!(snbt::parse("{a:1}}").is_err());
+        assert!(snbt::parse("[I;1,a]").is_err());
+    }
+
+    #[test]
+    fn reject_mixed_lists() {
+        assert!(matches!(
+            snbt::parse("[1b, 2s]"),
+            Err(Error::InvalidSnbt(5, "list contains different types"))
+        ));
+        assert!(matches!(
+            snbt::parse("{a: [{}, [], {}]}"),
+            Err(Error::InvalidSnbt(9, "list contains different types"))
+        ));
+        assert_eq!(
+            snbt::parse("[[1b], [a, b]]").unwrap(),
+            Value::List(vec![
+                Value::List(vec![Value::Byte(1)]),
+                Value::List(vec![
+                    Value::String("a".to_string()),
+                    Value::String("b".to_string())
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn serde() {
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Item {
+            #[serde(rename = "Count")]
+            count: i8,
+            id: String,
+        }
+
+        let item = Item {
+            count: 1,
+            id: "minecraft:stone".to_string(),
+        };
+        let snbt = snbt::to_string(&item).unwrap();
+        assert_eq!(snbt, r#"{Count:1b,id:"minecraft:stone"}"#);
+        assert_eq!(snbt::from_str::<Item>(&snbt).unwrap(), item);
+        assert_eq!(
+            snbt::to_string_pretty(&item).unwrap(),
+            "{\n    Count: 1b,\n    id: \"minecraft:stone\"\n}"
+        );
+    }
+}
@@ -0,0 +1,997 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{
+    de::{
+        value::{MapDeserializer, SeqDeserializer, StringDeserializer},
+        IntoDeserializer,
+    },
+    forward_to_deserialize_any,
+    ser::{SerializeMap, SerializeSeq},
+};
+
+use crate::{
+    array,
+    error::{Error, Result},
+    TagType,
+};
+
+/// Owned NBT tag, for data which has no fixed layout
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Value>),
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+pub type Compound = BTreeMap<String, Value>;
+
+/// Deserializes a typed value from the NBT value
+pub fn from_value<T>(value: Value) -> Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    T::deserialize(value)
+}
+
+/// Serializes a typed value into an NBT value
+pub fn to_value<T>(value: &T) -> Result<Value>
+where
+    T: serde::ser::Serialize,
+{
+    value
+        .serialize(Serializer)?
+        .ok_or_else(|| Error::Custom("value is empty".to_string()))
+}
+
+impl Value {
+    /// Returns the value at the key or index, if the value is a compound or
+    /// list respectively
+    pub fn get<I: ValueIndex>(&self, index: I) -> Option<&Value> {
+        index.index_into(self)
+    }
+
+    pub fn get_mut<I: ValueIndex>(&mut self, index: I) -> Option<&mut Value> {
+        index.index_into_mut(self)
+    }
+
+    /// Returns any integer value, widened to i64
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Byte(v) => Some(v as i64),
+            Value::Short(v) => Some(v as i64),
+            Value::Int(v) => Some(v as i64),
+            Value::Long(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns any numeric value, converted to f64
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Float(v) => Some(v as f64),
+            Value::Double(v) => Some(v),
+            _ => self.as_i64().map(|v| v as f64),
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Byte(v) => Some(v != 0),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::List(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Value::List(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&Compound> {
+        match self {
+            Value::Compound(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound_mut(&mut self) -> Option<&mut Compound> {
+        match self {
+            Value::Compound(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($($type_:ty => $variant:ident),*) => {
+        $(
+            impl From<$type_> for Value {
+                fn from(value: $type_) -> Self {
+                    Value::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from! {
+    bool => Byte, i8 => Byte, i16 => Short, i32 => Int, i64 => Long, f32 => Float, f64 => Double,
+    String => String, &str => String, Vec<Value> => List, Compound => Compound
+}
+
+/// Key or index into a compound or list
+pub trait ValueIndex {
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value>;
+
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value>;
+}
+
+impl ValueIndex for usize {
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        value.as_list()?.get(*self)
+    }
+
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        value.as_list_mut()?.get_mut(*self)
+    }
+}
+
+impl ValueIndex for str {
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        value.as_compound()?.get(self)
+    }
+
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        value.as_compound_mut()?.get_mut(self)
+    }
+}
+
+impl ValueIndex for String {
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        self.as_str().index_into(value)
+    }
+
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        self.as_str().index_into_mut(value)
+    }
+}
+
+impl<T: ValueIndex + ?Sized> ValueIndex for &T {
+    fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        (**self).index_into(value)
+    }
+
+    fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        (**self).index_into_mut(value)
+    }
+}
+
+impl<I: ValueIndex> std::ops::Index<I> for Value {
+    type Output = Value;
+
+    /// Panics if the key or index does not exist
+    fn index(&self, index: I) -> &Self::Output {
+        index.index_into(self).expect("key or index does not exist")
+    }
+}
+
+impl<I: ValueIndex> std::ops::IndexMut<I> for Value {
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        index
+            .index_into_mut(self)
+            .expect("key or index does not exist")
+    }
+}
+
+impl serde::ser::Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        match self {
+            Value::Byte(v) => serializer.serialize_i8(*v),
+            Value::Short(v) => serializer.serialize_i16(*v),
+            Value::Int(v) => serializer.serialize_i32(*v),
+            Value::Long(v) => serializer.serialize_i64(*v),
+            Value::Float(v) => serializer.serialize_f32(*v),
+            Value::Double(v) => serializer.serialize_f64(*v),
+            Value::ByteArray(v) => {
+                serializer.serialize_bytes(&v.iter().map(|&v| v as u8).collect::<Vec<_>>())
+            }
+            Value::String(v) => serializer.serialize_str(v),
+            Value::List(v) => {
+                let mut seq = serializer.serialize_seq(Some(v.len()))?;
+                for element in v {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            }
+            Value::Compound(v) => {
+                let mut map = serializer.serialize_map(Some(v.len()))?;
+                for (key, value) in v {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            Value::IntArray(v) => serializer.serialize_newtype_struct(array::INT_ARRAY, v),
+            Value::LongArray(v) => serializer.serialize_newtype_struct(array::LONG_ARRAY, v),
+        }
+    }
+}
+
+impl<'de> serde::de::Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        struct ValueVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("NBT value")
+            }
+
+            fn visit_bool<E>(self, v: bool) -> std::result::Result<Self::Value, E> {
+                Ok(Value::Byte(v as i8))
+            }
+
+            fn visit_i8<E>(self, v: i8) -> std::result::Result<Self::Value, E> {
+                Ok(Value::Byte(v))
+            }
+
+            fn visit_i16<E>(self, v: i16) -> std::result::Result<Self::Value, E> {
+                Ok(Value::Short(v))
+            }
+
+            fn visit_i32<E>(self, v: i32) -> std::result::Result<Self::Value, E> {
+                Ok(Value::Int(v))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> std::result::Result<Self::Value, E> {
+                Ok(Value::Long(v))
+            }
+
+            fn visit_u8<E>(self, v: u8) -> std::result::Result<Self::Value, E> {
+                Ok(Value::Byte(v as i8))
+            }
+
+            fn visit_u16<E>(self, v: u16) -> std::result::Result<Self::Value, E> {
+                Ok(Value::Short(v as i16))
+            }
+
+            fn visit_u32<E>(self, v: u32) -> std::result::Result<Self::Value, E> {
+                Ok(Value::Int(v as i32))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> std::result::Result<Self::Value, E> {
+                Ok(Value::Long(v as i64))
+            }
+
+            fn visit_f32<E>(self, v: f32) -> std::result::Result<Self::Value, E> {
+                Ok(Value::Float(v))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> std::result::Result<Self::Value, E> {
+                Ok(Value::Double(v))
+            }
+
+            fn visit_str<E>(self, v: &str) -> std::result::Result<Self::Value, E> {
+                Ok(Value::String(v.to_string()))
+            }
+
+            fn visit_string<E>(self, v: String) -> std::result::Result<Self::Value, E> {
+                Ok(Value::String(v))
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> std::result::Result<Self::Value, E> {
+                Ok(Value::ByteArray(v.iter().map(|&v| v as i8).collect()))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+                while let Some(element) = seq.next_element()? {
+                    list.push(element);
+                }
+                Ok(Value::List(list))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut compound = Compound::new();
+                while let Some((key, value)) = map.next_entry()? {
+                    compound.insert(key, value);
+                }
+                Ok(Value::Compound(compound))
+            }
+
+            fn visit_newtype_struct<D>(
+                self,
+                deserializer: D,
+            ) -> std::result::Result<Self::Value, D::Error>
+            where
+                D: serde::de::Deserializer<'de>,
+            {
+                deserializer.deserialize_any(self)
+            }
+
+            fn visit_enum<A>(self, data: A) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: serde::de::EnumAccess<'de>,
+            {
+                // arrays are visited as newtype variants, see Deserializer::deserialize_newtype_struct
+                use serde::de::VariantAccess;
+
+                let (type_, array): (String, _) = data.variant()?;
+                match type_.as_str() {
+                    "ByteArray" => array.newtype_variant().map(Value::ByteArray),
+                    "IntArray" => array.newtype_variant().map(Value::IntArray),
+                    "LongArray" => array.newtype_variant().map(Value::LongArray),
+                    _ => Err(serde::de::Error::unknown_variant(
+                        &type_,
+                        &["ByteArray", "IntArray", "LongArray"],
+                    )),
+                }
+            }
+        }
+
+        // formats other than NBT treat this as plain newtype struct
+        deserializer.deserialize_newtype_struct(array::VALUE, ValueVisitor)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de> serde::de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 f32 f64 char str string bytes byte_buf unit seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self {
+            Value::Byte(v) => visitor.visit_i8(v),
+            Value::Short(v) => visitor.visit_i16(v),
+            Value::Int(v) => visitor.visit_i32(v),
+            Value::Long(v) => visitor.visit_i64(v),
+            Value::Float(v) => visitor.visit_f32(v),
+            Value::Double(v) => visitor.visit_f64(v),
+            Value::ByteArray(v) => visit_seq(v.into_iter().map(Value::Byte), visitor),
+            Value::String(v) => visitor.visit_string(v),
+            Value::List(v) => visit_seq(v.into_iter(), visitor),
+            Value::Compound(v) => {
+                let mut map = MapDeserializer::new(v.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Value::IntArray(v) => visit_seq(v.into_iter().map(Value::Int), visitor),
+            Value::LongArray(v) => visit_seq(v.into_iter().map(Value::Long), visitor),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self {
+            Value::Byte(0) => visitor.visit_bool(false),
+            Value::Byte(1) => visitor.visit_bool(true),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    // unsigned values are reinterpreted, like when reading binary NBT
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self {
+            Value::Byte(v) => visitor.visit_u8(v as u8),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self {
+            Value::Short(v) => visitor.visit_u16(v as u16),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self {
+            Value::Int(v) => visitor.visit_u32(v as u32),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self {
+            Value::Long(v) => visitor.visit_u64(v as u64),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // this is only needed for support reading optional fields
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self {
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Value::Compound(compound) if compound.len() == 1 => {
+                visitor.visit_enum(EnumDeserializer(compound.into_iter().next().unwrap()))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self {
+            Value::Compound(compound) if compound.is_empty() => visitor.visit_unit(),
+            _ => Err(serde::de::Error::invalid_type(
+                serde::de::Unexpected::Other("non-empty tag"),
+                &name,
+            )),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        if name != array::VALUE {
+            return visitor.visit_newtype_struct(self);
+        }
+
+        // same as for binary NBT, arrays are visited as newtype variants
+        match self {
+            Value::ByteArray(_) => {
+                visitor.visit_enum(EnumDeserializer(("ByteArray".to_string(), self)))
+            }
+            Value::IntArray(_) => {
+                visitor.visit_enum(EnumDeserializer(("IntArray".to_string(), self)))
+            }
+            Value::LongArray(_) => {
+                visitor.visit_enum(EnumDeserializer(("LongArray".to_string(), self)))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+pub(crate) fn visit_seq<'de, V>(values: impl Iterator<Item = Value>, visitor: V) -> Result<V::Value>
+where
+    V: serde::de::Visitor<'de>,
+{
+    let mut seq = SeqDeserializer::new(values);
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+/// Single-key compound, key is the variant
+pub(crate) struct EnumDeserializer(pub(crate) (String, Value));
+
+impl<'de> serde::de::EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = Value;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let (variant, value) = self.0;
+        let variant: StringDeserializer<Error> = variant.into_deserializer();
+        Ok((seed.deserialize(variant)?, value))
+    }
+}
+
+impl<'de> serde::de::VariantAccess<'de> for Value {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Err(serde::de::Error::invalid_type(
+            serde::de::Unexpected::NewtypeVariant,
+            &"unit variant",
+        ))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        serde::de::Deserializer::deserialize_any(self, visitor)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        serde::de::Deserializer::deserialize_any(self, visitor)
+    }
+}
+
+/// Serializes into values, empty values (None, unit) are omitted in compounds
+struct Serializer;
+
+impl serde::ser::Serializer for Serializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeCompound;
+    type SerializeStruct = SerializeCompound;
+    type SerializeStructVariant = SerializeCompound;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        Ok(Some(Value::Byte(v as i8)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        Ok(Some(Value::Byte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        Ok(Some(Value::Short(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        Ok(Some(Value::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        Ok(Some(Value::Long(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        Ok(Some(Value::Byte(v as i8)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        Ok(Some(Value::Short(v as i16)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        Ok(Some(Value::Int(v as i32)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        Ok(Some(Value::Long(v as i64)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        Ok(Some(Value::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        Ok(Some(Value::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        Ok(Some(Value::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        Ok(Some(Value::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        Ok(Some(Value::ByteArray(v.iter().map(|&v| v as i8).collect())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Self::Ok>
+    where
+        T: serde::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        Ok(Some(Value::Compound(Compound::new())))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized>(self, name: &'static str, value: &T) -> Result<Self::Ok>
+    where
+        T: serde::Serialize,
+    {
+        let value = value.serialize(self)?;
+        let Some((type_, _)) = array::array_type(name) else {
+            return Ok(value);
+        };
+
+        let Some(Value::List(list)) = value else {
+            return Ok(value);
+        };
+        let array_error =
+            || Error::Custom("array elements must be of the array's type".to_string());
+        Ok(Some(match type_ {
+            TagType::ByteArray => Value::ByteArray(
+                list.into_iter()
+                    .map(|value| match value {
+                        Value::Byte(value) => Ok(value),
+                        _ => Err(array_error()),
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            TagType::IntArray => Value::IntArray(
+                list.into_iter()
+                    .map(|value| match value {
+                        Value::Int(value) => Ok(value),
+                        _ => Err(array_error()),
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            _ => Value::LongArray(
+                list.into_iter()
+                    .map(|value| match value {
+                        Value::Long(value) => Ok(value),
+                        _ => Err(array_error()),
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+        }))
+    }
+
+    fn serialize_newtype_variant<T: ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok>
+    where
+        T: serde::Serialize,
+    {
+        // variants with data are written as single-key compounds
+        let mut compound = Compound::new();
+        if let Some(value) = value.serialize(self)? {
+            compound.insert(variant.to_string(), value);
+        }
+        Ok(Some(Value::Compound(compound)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SerializeList {
+            variant: None,
+            list: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(SerializeList {
+            variant: Some(variant),
+            list: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(SerializeCompound {
+            variant: None,
+            compound: Compound::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(SerializeCompound {
+            variant: Some(variant),
+            compound: Compound::new(),
+            key: None,
+        })
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct SerializeList {
+    variant: Option<&'static str>,
+    list: Vec<Value>,
+}
+
+impl SerializeList {
+    fn push<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        self.list.push(
+            value
+                .serialize(Serializer)?
+                .ok_or_else(|| Error::Custom("list element is empty".to_string()))?,
+        );
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Value>> {
+        Ok(Some(wrap_variant(self.variant, Value::List(self.list))))
+    }
+}
+
+impl serde::ser::SerializeSeq for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl serde::ser::SerializeTuple for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl serde::ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl serde::ser::SerializeTupleVariant for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+struct SerializeCompound {
+    variant: Option<&'static str>,
+    compound: Compound,
+    key: Option<String>,
+}
+
+impl SerializeCompound {
+    fn insert<T: ?Sized>(&mut self, key: String, value: &T) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        // omit empty values, like the binary serializer does
+        if let Some(value) = value.serialize(Serializer)? {
+            self.compound.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Value>> {
+        Ok(Some(wrap_variant(
+            self.variant,
+            Value::Compound(self.compound),
+        )))
+    }
+}
+
+impl serde::ser::SerializeMap for SerializeCompound {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        match key.serialize(Serializer)? {
+            Some(Value::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(Error::Custom("key is not a string".to_string())),
+        }
+    }
+
+    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::Custom("value without key".to_string()))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl serde::ser::SerializeStruct for SerializeCompound {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl serde::ser::SerializeStructVariant for SerializeCompound {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+fn wrap_variant(variant: Option<&'static str>, value: Value) -> Value {
+    match variant {
+        Some(variant) => Value::Compound(Compound::from([(variant.to_string(), value)])),
+        None => value,
+    }
+}
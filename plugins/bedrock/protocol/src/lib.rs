@@ -1 +1,30 @@
+pub mod types {
+    use serde::{de::DeserializeOwned, Serialize};
+    use tesseract_nbt::{error::Result, reader::Limits, Encoding};
 
+    /// NBT as it is sent over the network, little-endian with VarInts
+    #[derive(Clone, Debug)]
+    #[repr(transparent)]
+    pub struct Nbt<T>(pub T);
+
+    impl<T: Serialize> Nbt<T> {
+        pub fn encode(&self, output: &mut Vec<u8>) -> Result<()> {
+            output.extend(tesseract_nbt::ser::to_vec_with(
+                &self.0,
+                Encoding::BEDROCK_NETWORK,
+            )?);
+            Ok(())
+        }
+    }
+
+    impl<T: DeserializeOwned> Nbt<T> {
+        pub fn decode(input: &mut &[u8]) -> Result<Self> {
+            // limited, as NBT sent by clients is untrusted
+            Ok(Nbt(tesseract_nbt::de::from_reader_with(
+                input,
+                Encoding::BEDROCK_NETWORK,
+                Limits::default(),
+            )?))
+        }
+    }
+}
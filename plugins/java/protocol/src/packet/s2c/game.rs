@@ -1,6 +1,7 @@
 use std::{collections::HashMap, io::Write};
 
 use glam::{DVec3, IVec3};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use mojang_session_api::models::User;
@@ -75,7 +76,7 @@ pub enum GamePacket<'a> {
         pos: IVec3,
         #[using(VarI32)]
         type_: i32,
-        tag: Nbt<serde_value::Value>,
+        tag: Nbt<tesseract_nbt::value::Compound>,
     },
     BlockEvent {
         pos: IVec3,
@@ -898,18 +899,36 @@ pub enum GameEventPacketEvent {
 
 #[derive(Encode, Decode, Clone, Debug)]
 pub struct LevelChunkPacketData {
-    pub heightmaps: Nbt<serde_value::Value>,
+    pub heightmaps: Nbt<LevelChunkPacketDataHeightmaps>,
     pub buffer: Vec<u8>,
     pub block_entities_data: Vec<LevelChunkPacketDataBlockEntity>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LevelChunkPacketDataHeightmaps {
+    #[serde(
+        rename = "MOTION_BLOCKING",
+        with = "tesseract_nbt::long_array",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub motion_blocking: Option<Vec<u64>>,
+    #[serde(
+        rename = "WORLD_SURFACE",
+        with = "tesseract_nbt::long_array",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub world_surface: Option<Vec<u64>>,
+}
+
 #[derive(Encode, Decode, Clone, Debug)]
 pub struct LevelChunkPacketDataBlockEntity {
     pub xz: i8,
     pub y: i16,
     #[using(VarI32)]
     pub type_: i32,
-    pub data: Nbt<serde_value::Value>,
+    pub data: Nbt<tesseract_nbt::value::Compound>,
 }
 
 #[derive(Encode, Decode, Clone, Debug)]
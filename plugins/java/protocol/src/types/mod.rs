@@ -876,6 +876,17 @@ impl Direction {
             Direction::West => IVec3::X,
         }
     }
+
+    pub fn opposite(&self) -> Self {
+        match self {
+            Direction::Down => Direction::Up,
+            Direction::Up => Direction::Down,
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, IntoPrimitive, TryFromPrimitive)]
@@ -1049,14 +1060,22 @@ pub struct Nbt<T>(pub T);
 
 impl<T: Serialize> Encode for Nbt<T> {
     fn encode(&self, output: &mut impl Write) -> Result<()> {
-        output.write_all(&tesseract_nbt::ser::to_vec(&self.0)?)?;
+        output.write_all(&tesseract_nbt::ser::to_vec_with(
+            &self.0,
+            tesseract_nbt::Encoding::JAVA,
+        )?)?;
         Ok(())
     }
 }
 
 impl<T: DeserializeOwned> Decode<'_> for Nbt<T> {
     fn decode(input: &mut &'_ [u8]) -> Result<Self> {
-        Ok(Nbt(tesseract_nbt::de::from_slice(input)?))
+        // limited, as NBT sent by clients is untrusted
+        Ok(Nbt(tesseract_nbt::de::from_reader_with(
+            input,
+            tesseract_nbt::Encoding::JAVA,
+            tesseract_nbt::reader::Limits::default(),
+        )?))
     }
 }
 
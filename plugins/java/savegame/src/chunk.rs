@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
+use tesseract_nbt::value::{Compound, Value};
 
-use crate::{BlockState, PalettedContainer};
+use crate::{BlockState, Error, PalettedContainer};
 
 #[derive(Serialize, Deserialize)]
 pub struct Chunk {
@@ -12,7 +13,35 @@ pub struct Chunk {
     pub y: i32,
     #[serde(rename = "zPos")]
     pub z: i32,
+    #[serde(rename = "Status", skip_serializing_if = "Option::is_none", default)]
+    pub status: Option<String>,
     pub sections: Vec<ChunkSection>,
+    #[serde(rename = "Heightmaps", default)]
+    pub heightmaps: Heightmaps,
+    #[serde(default)]
+    pub block_entities: Vec<BlockEntity>,
+    #[serde(default)]
+    pub block_ticks: Vec<ScheduledTick>,
+    #[serde(default)]
+    pub fluid_ticks: Vec<ScheduledTick>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Heightmaps {
+    #[serde(
+        rename = "MOTION_BLOCKING",
+        with = "tesseract_nbt::long_array",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub motion_blocking: Option<Vec<u64>>,
+    #[serde(
+        rename = "WORLD_SURFACE",
+        with = "tesseract_nbt::long_array",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub world_surface: Option<Vec<u64>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -22,3 +51,58 @@ pub struct ChunkSection {
     pub block_states: PalettedContainer<BlockState>,
     pub biomes: PalettedContainer<String>,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct ScheduledTick {
+    /// Name of the block or fluid
+    #[serde(rename = "i")]
+    pub id: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    /// Ticks until the tick is due
+    #[serde(rename = "t")]
+    pub delay: i32,
+    #[serde(rename = "p")]
+    pub priority: i32,
+}
+
+/// Block entity, the data is kept as is, as its layout depends on the type
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(try_from = "Compound", into = "Compound")]
+pub struct BlockEntity {
+    pub id: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub data: Compound,
+}
+
+impl TryFrom<Compound> for BlockEntity {
+    type Error = Error;
+
+    fn try_from(mut data: Compound) -> Result<Self, Self::Error> {
+        let Some(Value::String(id)) = data.remove("id") else {
+            return Err(Error::InvalidData("block entity without id"));
+        };
+        let mut coordinate = |key| match data.remove(key) {
+            Some(Value::Int(value)) => Ok(value),
+            _ => Err(Error::InvalidData("block entity without position")),
+        };
+        let (x, y, z) = (coordinate("x")?, coordinate("y")?, coordinate("z")?);
+        // only used by proto chunks
+        data.remove("keepPacked");
+        Ok(Self { id, x, y, z, data })
+    }
+}
+
+impl From<BlockEntity> for Compound {
+    fn from(block_entity: BlockEntity) -> Self {
+        let mut data = block_entity.data;
+        data.insert("id".to_string(), Value::String(block_entity.id));
+        data.insert("x".to_string(), Value::Int(block_entity.x));
+        data.insert("y".to_string(), Value::Int(block_entity.y));
+        data.insert("z".to_string(), Value::Int(block_entity.z));
+        data
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use tesseract_nbt::value::Compound;
 
 #[derive(Serialize, Deserialize)]
 pub struct Entity {
@@ -10,9 +11,98 @@ pub struct Entity {
 
 #[derive(Serialize, Deserialize)]
 pub struct Player {
+    #[serde(rename = "DataVersion")]
+    pub version: i32,
     #[serde(flatten)]
     pub entity: Entity,
 
     #[serde(rename = "Dimension")]
     pub level: String,
+    #[serde(rename = "Inventory", default)]
+    pub inventory: Vec<Item>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Item {
+    #[serde(rename = "Slot", skip_serializing_if = "Option::is_none", default)]
+    pub slot: Option<i8>,
+    pub id: String,
+    #[serde(rename = "Count")]
+    pub count: i8,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub tag: Option<Compound>,
+}
+
+#[cfg(test)]
+mod tests {
+    use tesseract_nbt::value::Value;
+
+    use crate::{entity::*, DATA_VERSION};
+
+    #[test]
+    fn player_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("tesseract_player_test_{}.dat", std::process::id()));
+
+        crate::write_compressed(
+            &path,
+            &Player {
+                version: DATA_VERSION,
+                entity: Entity {
+                    position: [0.5, 64.0, -0.5],
+                    rotation: [90.0, -45.0],
+                },
+                level: "minecraft:the_nether".to_string(),
+                inventory: vec![],
+            },
+        )
+        .unwrap();
+        let player = crate::read_compressed::<Player, _>(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(player.version, DATA_VERSION);
+        assert_eq!(player.entity.position, [0.5, 64.0, -0.5]);
+        assert_eq!(player.entity.rotation, [90.0, -45.0]);
+        assert_eq!(player.level, "minecraft:the_nether");
+        assert!(player.inventory.is_empty());
+    }
+
+    #[test]
+    fn player_inventory_round_trip() {
+        let tag = Compound::from([("Damage".to_string(), Value::Int(3))]);
+        let value = tesseract_nbt::ser::to_vec(&Player {
+            version: DATA_VERSION,
+            entity: Entity {
+                position: [0.0; 3],
+                rotation: [0.0; 2],
+            },
+            level: "minecraft:overworld".to_string(),
+            inventory: vec![
+                Item {
+                    slot: Some(0),
+                    id: "minecraft:stone".to_string(),
+                    count: 64,
+                    tag: None,
+                },
+                Item {
+                    slot: Some(-106),
+                    id: "minecraft:iron_pickaxe".to_string(),
+                    count: 1,
+                    tag: Some(tag.clone()),
+                },
+            ],
+        })
+        .unwrap();
+        let player = crate::upgrade::from_slice::<Player>(&value).unwrap();
+
+        assert_eq!(player.inventory.len(), 2);
+        assert_eq!(player.inventory[0].slot, Some(0));
+        assert_eq!(player.inventory[0].id, "minecraft:stone");
+        assert_eq!(player.inventory[0].count, 64);
+        assert_eq!(player.inventory[0].tag, None);
+        assert_eq!(player.inventory[1].slot, Some(-106));
+        assert_eq!(player.inventory[1].id, "minecraft:iron_pickaxe");
+        assert_eq!(player.inventory[1].count, 1);
+        assert_eq!(player.inventory[1].tag, Some(tag));
+    }
 }
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -12,4 +14,7 @@ pub struct LevelData {
     pub time: i64,
     #[serde(rename = "DayTime")]
     pub day_time: i64,
+    /// Game rules by name, all values are strings
+    #[serde(rename = "GameRules", default)]
+    pub game_rules: BTreeMap<String, String>,
 }
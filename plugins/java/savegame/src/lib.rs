@@ -1,19 +1,88 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, Write},
+    path::Path,
+};
 
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 pub mod chunk;
 pub mod entity;
 pub mod level;
 pub mod region;
+pub mod upgrade;
+
+/// Data version of Minecraft: Java Edition 1.19.4
+pub const DATA_VERSION: i32 = 3337;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("IO error")]
+    Io(#[from] std::io::Error),
+    #[error("Nbt error")]
+    Nbt(#[from] tesseract_nbt::error::Error),
+
+    #[error("Invalid chunk location: {0}")]
+    InvalidChunkLocation(u32),
+    #[error("Invalid chunk length: {0}")]
+    InvalidChunkLength(usize),
+    #[error("Unknown chunk compression: {0}")]
+    UnknownChunkCompression(u8),
+    #[error("Chunk too large: {0} sectors")]
+    ChunkTooLarge(usize),
+    #[error("Invalid paletted container")]
+    InvalidPalettedContainer,
+    #[error("Unsupported data version: {0}")]
+    UnsupportedVersion(i32),
+    #[error("Invalid data: {0}")]
+    InvalidData(&'static str),
+    #[error("Unknown block: {0}")]
+    UnknownBlock(u32),
+    #[error("Unknown block state: {0}")]
+    UnknownBlockState(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Reads gzip-compressed NBT, like level.dat and player data, and upgrades it
+/// if it is from an older version
+pub fn read_compressed<T: upgrade::Upgradable, P: AsRef<Path>>(path: P) -> Result<T> {
+    // read as value, as the data version is only known after reading
+    let value = tesseract_nbt::de::from_reader_with(
+        BufReader::new(GzDecoder::new(File::open(path)?)),
+        tesseract_nbt::Encoding::JAVA,
+        tesseract_nbt::reader::Limits::default(),
+    )?;
+    upgrade::from_value(value)
+}
+
+/// Writes gzip-compressed NBT, like level.dat and player data
+pub fn write_compressed<T: Serialize, P: AsRef<Path>>(path: P, value: &T) -> Result<()> {
+    if let Some(parent_path) = path.as_ref().parent() {
+        std::fs::create_dir_all(parent_path)?;
+    }
+
+    let mut encoder = GzEncoder::new(File::create(path)?, Compression::default());
+    encoder.write_all(&tesseract_nbt::ser::to_vec(value)?)?;
+    encoder.finish()?;
+    Ok(())
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct PalettedContainer<T> {
     pub palette: Vec<T>,
+    #[serde(
+        with = "tesseract_nbt::long_array",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
     pub data: Option<Vec<u64>>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct BlockState {
     #[serde(rename = "Name")]
     pub name: String,
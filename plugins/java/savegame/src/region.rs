@@ -1,100 +1,390 @@
 use std::{
-    fs::File,
-    io::{Read, Seek, SeekFrom},
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use byteorder::{BigEndian, ReadBytesExt};
-use flate2::read::{GzDecoder, ZlibDecoder};
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::ZlibEncoder,
+    Compression,
+};
 use glam::IVec2;
 
+use crate::{Error, Result};
+
+const SECTOR_SIZE: usize = 4096;
+
 pub struct RegionStorage {
     path: PathBuf,
+
+    regions: HashMap<IVec2, (Region, u64)>,
+    /// Positions of the open regions by their last access, to find the least
+    /// recently used one
+    region_positions_by_access: BTreeMap<u64, IVec2>,
+    region_capacity: usize,
+    region_access: u64,
 }
 
 impl RegionStorage {
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self::with_capacity(path, 256)
+    }
+
+    /// Creates a region storage, which keeps at most region_capacity region
+    /// files open, the least recently used ones are closed first
+    pub fn with_capacity<P: AsRef<Path>>(path: P, region_capacity: usize) -> Self {
+        assert!(region_capacity > 0);
         RegionStorage {
             path: {
                 let mut path_ = PathBuf::new();
                 path_.push(path);
                 path_
             },
+
+            regions: HashMap::new(),
+            region_positions_by_access: BTreeMap::new(),
+            region_capacity,
+            region_access: 0,
         }
     }
 
-    pub fn read(&mut self, position: IVec2) -> Option<Vec<u8>> {
-        let path = self
-            .path
-            .join(format!("r.{}.{}.mca", position.x >> 5, position.y >> 5));
-        if !path.exists() {
-            None
+    pub fn read(&mut self, position: IVec2) -> Result<Option<Vec<u8>>> {
+        if let Some(region) = self.region(position, false)? {
+            region.read(Self::chunk_index(position))
         } else {
-            Region::new(path)
-                .read(((position.x & 0x1F) as u32 | ((position.y & 0x1F) as u32) << 5) as usize)
+            Ok(None)
         }
     }
+
+    pub fn write(&mut self, position: IVec2, data: &[u8]) -> Result<()> {
+        self.region(position, true)?
+            .unwrap()
+            .write(Self::chunk_index(position), data)
+    }
+
+    fn region(&mut self, position: IVec2, create: bool) -> Result<Option<&mut Region>> {
+        let region_position = IVec2::new(position.x >> 5, position.y >> 5);
+        if let Some((_, region_access)) = self.regions.get_mut(&region_position) {
+            self.region_positions_by_access.remove(region_access);
+        } else {
+            let path = self
+                .path
+                .join(format!("r.{}.{}.mca", region_position.x, region_position.y));
+            if !path.exists() {
+                if !create {
+                    return Ok(None);
+                }
+
+                std::fs::create_dir_all(&self.path)?;
+            }
+            let region = Region::new(path)?;
+
+            // close least recently used region
+            if self.regions.len() >= self.region_capacity {
+                let (_, least_recently_used_region_position) =
+                    self.region_positions_by_access.pop_first().unwrap();
+                self.regions.remove(&least_recently_used_region_position);
+            }
+
+            self.regions.insert(region_position, (region, 0));
+        }
+
+        self.region_access += 1;
+        self.region_positions_by_access
+            .insert(self.region_access, region_position);
+        let (region, region_access) = self.regions.get_mut(&region_position).unwrap();
+        *region_access = self.region_access;
+        Ok(Some(region))
+    }
+
+    fn chunk_index(position: IVec2) -> usize {
+        ((position.x & 0x1F) as u32 | ((position.y & 0x1F) as u32) << 5) as usize
+    }
 }
 
 struct Region {
     file: File,
     file_header: Vec<u8>,
+
+    #[cfg(feature = "mmap")]
+    file_map: Option<memmap2::Mmap>,
 }
 
 impl Region {
-    #[allow(clippy::unused_io_amount)]
-    pub fn new<P: AsRef<Path>>(path: P) -> Self {
-        let mut file = File::open(path).unwrap();
-        let mut file_header = vec![0; 4096 * 2];
-        file.read(&mut file_header).unwrap();
-        Region { file, file_header }
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut file_header = vec![0; SECTOR_SIZE * 2];
+        if file.metadata()?.len() == 0 {
+            // new region, initialize header with no chunks
+            file.write_all(&file_header)?;
+        } else {
+            file.read_exact(&mut file_header)?;
+        }
+        Ok(Region {
+            file,
+            file_header,
+
+            #[cfg(feature = "mmap")]
+            file_map: None,
+        })
     }
 
-    pub fn read(&mut self, index: usize) -> Option<Vec<u8>> {
-        let location = (&self.file_header[index * 4..])
-            .read_u32::<BigEndian>()
-            .unwrap();
-        let _timestamp = (&self.file_header[index * 4 + 4096..])
-            .read_u32::<BigEndian>()
-            .unwrap();
+    pub fn read(&mut self, index: usize) -> Result<Option<Vec<u8>>> {
+        let location = BigEndian::read_u32(&self.file_header[index * 4..]);
+        let _timestamp = BigEndian::read_u32(&self.file_header[index * 4 + SECTOR_SIZE..]);
         if location == 0 {
-            return None;
+            return Ok(None);
         }
 
-        let sector_offset = ((location >> 8) * 4096) as usize;
-        let sector_size = ((location & 0xFF) * 4096) as usize;
-        if sector_offset < 2 {
-            return None;
+        // sector 0 and 1 are used by the header
+        if location >> 8 < 2 {
+            return Err(Error::InvalidChunkLocation(location));
         }
-        self.file
-            .seek(SeekFrom::Start(sector_offset as u64))
-            .unwrap();
+        let sector_offset = ((location >> 8) as usize) * SECTOR_SIZE;
+        let sector_size = ((location & 0xFF) as usize) * SECTOR_SIZE;
 
-        let size = self.file.read_u32::<BigEndian>().unwrap() as usize;
-        if size > sector_size {
-            return None;
+        let sectors = self.read_sectors(sector_offset, sector_size)?;
+        let mut sectors = sectors.as_ref();
+        let size = sectors.read_u32::<BigEndian>()? as usize;
+        if size == 0 || size > sectors.len() {
+            return Err(Error::InvalidChunkLength(size));
         }
 
-        let mut data = vec![0; size];
-        self.file.read_exact(&mut data).unwrap();
-        let mut data = &data[..];
-        match data.read_u8().unwrap() {
+        let mut data = &sectors[..size];
+        match data.read_u8()? {
             1 => {
                 let mut decompressed_data = Vec::new();
-                GzDecoder::new(&mut data)
-                    .read_to_end(&mut decompressed_data)
-                    .unwrap();
-                Some(decompressed_data)
+                GzDecoder::new(&mut data).read_to_end(&mut decompressed_data)?;
+                Ok(Some(decompressed_data))
             }
             2 => {
                 let mut decompressed_data = Vec::new();
-                ZlibDecoder::new(&mut data)
-                    .read_to_end(&mut decompressed_data)
-                    .unwrap();
-                Some(decompressed_data)
+                ZlibDecoder::new(&mut data).read_to_end(&mut decompressed_data)?;
+                Ok(Some(decompressed_data))
             }
-            3 => Some(data.to_vec()),
-            _ => None,
+            3 => Ok(Some(data.to_vec())),
+            compression => Err(Error::UnknownChunkCompression(compression)),
+        }
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    fn read_sectors(&mut self, sector_offset: usize, sector_size: usize) -> Result<Cow<'_, [u8]>> {
+        self.file.seek(SeekFrom::Start(sector_offset as u64))?;
+
+        let mut sectors = Vec::with_capacity(sector_size);
+        (&mut self.file)
+            .take(sector_size as u64)
+            .read_to_end(&mut sectors)?;
+        Ok(Cow::Owned(sectors))
+    }
+
+    #[cfg(feature = "mmap")]
+    fn read_sectors(&mut self, sector_offset: usize, sector_size: usize) -> Result<Cow<'_, [u8]>> {
+        // the map is created lazily, as writing can change the file size
+        if self.file_map.is_none() {
+            self.file_map = Some(unsafe { memmap2::Mmap::map(&self.file) }?);
+        }
+        let file_map = self.file_map.as_ref().unwrap();
+        let sector_begin = sector_offset.min(file_map.len());
+        let sector_end = (sector_offset + sector_size).min(file_map.len());
+        Ok(Cow::Borrowed(&file_map[sector_begin..sector_end]))
+    }
+
+    pub fn write(&mut self, index: usize, data: &[u8]) -> Result<()> {
+        let mut compressed_data = vec![];
+        {
+            let mut encoder = ZlibEncoder::new(&mut compressed_data, Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()?;
+        }
+
+        // length (4 bytes) + compression type (1 byte) + compressed data
+        let size = compressed_data.len() + 1;
+        let sector_count = (4 + size).div_ceil(SECTOR_SIZE);
+        if sector_count > 0xFF {
+            return Err(Error::ChunkTooLarge(sector_count));
         }
+
+        let sector_offset = self.allocate(sector_count);
+
+        let mut sectors = Vec::with_capacity(sector_count * SECTOR_SIZE);
+        sectors.write_u32::<BigEndian>(size as u32)?;
+        sectors.write_u8(2)?;
+        sectors.write_all(&compressed_data)?;
+        sectors.resize(sector_count * SECTOR_SIZE, 0);
+        self.file
+            .seek(SeekFrom::Start((sector_offset * SECTOR_SIZE) as u64))?;
+        self.file.write_all(&sectors)?;
+        #[cfg(feature = "mmap")]
+        {
+            self.file_map = None;
+        }
+
+        // update location and timestamp in the header, and write both back
+        let location = (sector_offset as u32) << 8 | sector_count as u32;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs() as u32);
+        BigEndian::write_u32(&mut self.file_header[index * 4..], location);
+        BigEndian::write_u32(&mut self.file_header[index * 4 + SECTOR_SIZE..], timestamp);
+        self.file.seek(SeekFrom::Start((index * 4) as u64))?;
+        self.file.write_u32::<BigEndian>(location)?;
+        self.file
+            .seek(SeekFrom::Start((index * 4 + SECTOR_SIZE) as u64))?;
+        self.file.write_u32::<BigEndian>(timestamp)?;
+        Ok(())
+    }
+
+    /// Finds a run of free sectors, the current sectors of the chunk are not
+    /// reused, as they have to stay intact until the header points to the new
+    /// sectors, otherwise a failed write would corrupt the chunk
+    fn allocate(&self, sector_count: usize) -> usize {
+        // sector 0 and 1 are used by the header
+        let mut used_sectors = vec![true, true];
+        for index in 0..1024 {
+            let location = BigEndian::read_u32(&self.file_header[index * 4..]);
+            let used_sector_offset = (location >> 8) as usize;
+            let used_sector_end = used_sector_offset + (location & 0xFF) as usize;
+            if used_sector_offset < 2 || used_sector_end == used_sector_offset {
+                continue;
+            }
+
+            if used_sectors.len() < used_sector_end {
+                used_sectors.resize(used_sector_end, false);
+            }
+            used_sectors[used_sector_offset..used_sector_end].fill(true);
+        }
+
+        let mut free_sector_offset = 0;
+        let mut free_sector_count = 0;
+        for (sector_offset, &used) in used_sectors.iter().enumerate() {
+            if used {
+                free_sector_count = 0;
+                continue;
+            }
+
+            if free_sector_count == 0 {
+                free_sector_offset = sector_offset;
+            }
+            free_sector_count += 1;
+            if free_sector_count == sector_count {
+                return free_sector_offset;
+            }
+        }
+
+        // append at the end
+        used_sectors.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_does_not_overwrite_current_sectors() {
+        let path =
+            std::env::temp_dir().join(format!("tesseract_region_test_{}.mca", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut region = Region::new(&path).unwrap();
+        region.write(0, b"first").unwrap();
+        let first_location = BigEndian::read_u32(&region.file_header[0..]);
+        region.write(0, b"second").unwrap();
+        let second_location = BigEndian::read_u32(&region.file_header[0..]);
+        assert_ne!(first_location >> 8, second_location >> 8);
+        assert_eq!(region.read(0).unwrap().unwrap(), b"second");
+
+        // the sectors which are free again are reused
+        region.write(0, b"third").unwrap();
+        assert_eq!(
+            BigEndian::read_u32(&region.file_header[0..]) >> 8,
+            first_location >> 8
+        );
+        assert_eq!(region.read(0).unwrap().unwrap(), b"third");
+
+        drop(region);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn write_does_not_overwrite_sectors_of_other_chunks() {
+        let path = std::env::temp_dir().join(format!(
+            "tesseract_region_test_other_{}.mca",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let mut region = Region::new(&path).unwrap();
+        region.write(0, b"first").unwrap();
+        region.write(1, b"other").unwrap();
+        let first_location = BigEndian::read_u32(&region.file_header[0..]);
+        let other_location = BigEndian::read_u32(&region.file_header[4..]);
+        assert_ne!(first_location >> 8, other_location >> 8);
+
+        // neither the current sectors of the chunk, nor the sectors of the other
+        // chunk are used, as both are still referenced by the header
+        region.write(0, b"second").unwrap();
+        let second_location = BigEndian::read_u32(&region.file_header[0..]);
+        assert_ne!(second_location >> 8, first_location >> 8);
+        assert_ne!(second_location >> 8, other_location >> 8);
+        assert_eq!(region.read(0).unwrap().unwrap(), b"second");
+        assert_eq!(region.read(1).unwrap().unwrap(), b"other");
+
+        drop(region);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn evicted_regions_are_reopened() {
+        let path = std::env::temp_dir().join(format!(
+            "tesseract_region_storage_test_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+
+        let mut region_storage = RegionStorage::with_capacity(&path, 2);
+        region_storage.write(IVec2::new(0, 0), b"0 0").unwrap();
+        region_storage.write(IVec2::new(32, 0), b"32 0").unwrap();
+        // the first region is more recently used than the second one
+        assert_eq!(region_storage.read(IVec2::new(1, 1)).unwrap(), None);
+        region_storage.write(IVec2::new(-1, -1), b"-1 -1").unwrap();
+        assert_eq!(region_storage.regions.len(), 2);
+        assert!(region_storage.regions.contains_key(&IVec2::new(0, 0)));
+        assert!(region_storage.regions.contains_key(&IVec2::new(-1, -1)));
+        assert_eq!(region_storage.region_positions_by_access.len(), 2);
+
+        // unopened regions are not created when reading
+        assert_eq!(region_storage.read(IVec2::new(64, 0)).unwrap(), None);
+        assert!(!path.join("r.2.0.mca").exists());
+
+        assert_eq!(
+            region_storage.read(IVec2::new(32, 0)).unwrap().unwrap(),
+            b"32 0"
+        );
+        assert!(!region_storage.regions.contains_key(&IVec2::new(0, 0)));
+        assert_eq!(
+            region_storage.read(IVec2::new(0, 0)).unwrap().unwrap(),
+            b"0 0"
+        );
+        assert_eq!(
+            region_storage.read(IVec2::new(-1, -1)).unwrap().unwrap(),
+            b"-1 -1"
+        );
+        assert_eq!(region_storage.regions.len(), 2);
+        assert_eq!(region_storage.region_positions_by_access.len(), 2);
+
+        drop(region_storage);
+        std::fs::remove_dir_all(&path).unwrap();
     }
 }
@@ -0,0 +1,543 @@
+use serde::de::DeserializeOwned;
+use tesseract_nbt::value::{Compound, Value};
+
+use crate::{chunk::Chunk, entity::Player, level::Level, Error, Result};
+
+/// Data version of Minecraft: Java Edition 17w47a (The Flattening), older data
+/// uses numeric ids and is not supported
+pub const MIN_DATA_VERSION: i32 = 1451;
+
+/// Data which can be upgraded from older data versions
+pub trait Upgradable: DeserializeOwned {
+    /// Keys leading to the data version, data without one is from before 1.9
+    const VERSION_KEYS: &'static [&'static str];
+
+    /// Upgrade steps, each one is applied to data older than its version
+    fn upgrades() -> &'static [Upgrade];
+}
+
+pub struct Upgrade {
+    pub version: i32,
+    pub upgrade: fn(&mut Compound) -> Result<()>,
+}
+
+/// Deserializes the data, and upgrades it first if it is older than the current
+/// data version
+pub fn from_slice<T: Upgradable>(data: &[u8]) -> Result<T> {
+    // read as value, as the data version is only known after reading
+    from_value(tesseract_nbt::de::from_slice(&mut &data[..])?)
+}
+
+/// Upgrades the value if it is older than the current data version, and
+/// deserializes it
+pub fn from_value<T: Upgradable>(value: Value) -> Result<T> {
+    let Value::Compound(mut compound) = value else {
+        return Err(Error::InvalidData("root is not a compound"));
+    };
+
+    let mut version_value = compound.get(T::VERSION_KEYS[0]);
+    for key in &T::VERSION_KEYS[1..] {
+        version_value = version_value.and_then(|value| value.get(*key));
+    }
+    let version = version_value.and_then(Value::as_i64).unwrap_or_default() as i32;
+    if version < MIN_DATA_VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+
+    for upgrade in T::upgrades()
+        .iter()
+        .filter(|upgrade| version < upgrade.version)
+    {
+        (upgrade.upgrade)(&mut compound)?;
+    }
+    Ok(tesseract_nbt::value::from_value(Value::Compound(compound))?)
+}
+
+impl Upgradable for Chunk {
+    const VERSION_KEYS: &'static [&'static str] = &["DataVersion"];
+
+    fn upgrades() -> &'static [Upgrade] {
+        &[
+            Upgrade {
+                version: 2529,
+                upgrade: upgrade_chunk_block_states_padding,
+            },
+            Upgrade {
+                version: 2844,
+                upgrade: upgrade_chunk_layout,
+            },
+        ]
+    }
+}
+
+impl Upgradable for Level {
+    const VERSION_KEYS: &'static [&'static str] = &["Data", "DataVersion"];
+
+    fn upgrades() -> &'static [Upgrade] {
+        // Time and DayTime haven't changed since
+        &[]
+    }
+}
+
+impl Upgradable for Player {
+    const VERSION_KEYS: &'static [&'static str] = &["DataVersion"];
+
+    fn upgrades() -> &'static [Upgrade] {
+        &[Upgrade {
+            version: 2566,
+            upgrade: upgrade_player_dimension,
+        }]
+    }
+}
+
+/// Block states were packed tightly across longs, since 20w17a values don't
+/// span multiple longs anymore
+fn upgrade_chunk_block_states_padding(chunk: &mut Compound) -> Result<()> {
+    let Some(Value::Compound(level)) = chunk.get_mut("Level") else {
+        return Err(Error::InvalidData("missing Level"));
+    };
+    let Some(Value::List(sections)) = level.get_mut("Sections") else {
+        return Ok(());
+    };
+
+    for section in sections {
+        let Value::Compound(section) = section else {
+            return Err(Error::InvalidData("section is not a compound"));
+        };
+        let (Some(Value::List(palette)), Some(block_states)) =
+            (section.get("Palette"), section.get("BlockStates"))
+        else {
+            continue;
+        };
+
+        let bits = bits(palette.len()).max(4);
+        let block_states = long_array(block_states)?;
+        let values = (0..16 * 16 * 16)
+            .map(|index| {
+                let bit_index = index * bits as usize;
+                let cell_index = bit_index / 64;
+                let bit_offset = bit_index % 64;
+                let mut value = *block_states
+                    .get(cell_index)
+                    .ok_or(Error::InvalidPalettedContainer)? as u64
+                    >> bit_offset;
+                if bit_offset + bits as usize > 64 {
+                    let next_value = *block_states
+                        .get(cell_index + 1)
+                        .ok_or(Error::InvalidPalettedContainer)?
+                        as u64;
+                    value |= next_value << (64 - bit_offset);
+                }
+                Ok((value & ((1u64 << bits) - 1)) as u32)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        section.insert(
+            "BlockStates".to_string(),
+            Value::LongArray(pack(&values, bits)),
+        );
+    }
+    Ok(())
+}
+
+/// Chunks were moved out of Level, and extended from 0..256 to -64..320 with
+/// biomes per section in 1.18
+fn upgrade_chunk_layout(chunk: &mut Compound) -> Result<()> {
+    let Some(Value::Compound(mut level)) = chunk.remove("Level") else {
+        return Err(Error::InvalidData("missing Level"));
+    };
+
+    let biomes = match level.remove("Biomes") {
+        Some(Value::IntArray(biomes)) => biomes,
+        Some(Value::List(biomes)) => biomes
+            .into_iter()
+            .map(|biome| match biome {
+                Value::Int(biome) => Ok(biome),
+                _ => Err(Error::InvalidData("biome is not an int")),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        _ => vec![],
+    };
+    // 4x4x4 cells since 19w36a, before that there was one biome per column
+    let biome_at = |x: usize, y: usize, z: usize| {
+        let biome = match biomes.len() {
+            1024 => biomes[(y.min(63) * 4 + z) * 4 + x],
+            256 => biomes[(z * 4) * 16 + x * 4],
+            _ => 1,
+        };
+        legacy_biome_name(biome).to_string()
+    };
+
+    let mut old_sections = match level.remove("Sections") {
+        Some(Value::List(sections)) => sections,
+        _ => vec![],
+    };
+    let mut sections = vec![];
+    for section_y in -4i8..20 {
+        let mut section = old_sections
+            .iter()
+            .position(|section| match section {
+                Value::Compound(section) => section.get("Y") == Some(&Value::Byte(section_y)),
+                _ => false,
+            })
+            .and_then(|index| match old_sections.swap_remove(index) {
+                Value::Compound(section) => Some(section),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let mut block_states = Compound::new();
+        match (section.remove("Palette"), section.remove("BlockStates")) {
+            (Some(Value::List(palette)), Some(data)) if palette.len() > 1 => {
+                block_states.insert("palette".to_string(), Value::List(palette));
+                block_states.insert("data".to_string(), Value::LongArray(long_array(&data)?));
+            }
+            (Some(Value::List(palette)), _) if !palette.is_empty() => {
+                block_states.insert("palette".to_string(), Value::List(palette));
+            }
+            _ => {
+                block_states.insert(
+                    "palette".to_string(),
+                    Value::List(vec![Value::Compound(Compound::from([(
+                        "Name".to_string(),
+                        Value::String("minecraft:air".to_string()),
+                    )]))]),
+                );
+            }
+        }
+
+        // sections below and above use the biomes of the nearest cells
+        let mut biome_palette = vec![];
+        let biome_indices = (0..4 * 4 * 4)
+            .map(|index| {
+                let cell_y = (section_y.max(0) as usize * 4 + index / 16).min(63);
+                let biome = biome_at(index % 4, cell_y, index / 4 % 4);
+                biome_palette
+                    .iter()
+                    .position(|other_biome| *other_biome == biome)
+                    .unwrap_or_else(|| {
+                        biome_palette.push(biome);
+                        biome_palette.len() - 1
+                    }) as u32
+            })
+            .collect::<Vec<_>>();
+        let mut biomes = Compound::new();
+        if biome_palette.len() > 1 {
+            biomes.insert(
+                "data".to_string(),
+                Value::LongArray(pack(&biome_indices, bits(biome_palette.len()))),
+            );
+        }
+        biomes.insert(
+            "palette".to_string(),
+            Value::List(biome_palette.into_iter().map(Value::String).collect()),
+        );
+
+        section.insert("Y".to_string(), Value::Byte(section_y));
+        section.insert("block_states".to_string(), Value::Compound(block_states));
+        section.insert("biomes".to_string(), Value::Compound(biomes));
+        sections.push(Value::Compound(section));
+    }
+    chunk.insert("sections".to_string(), Value::List(sections));
+    chunk.insert("yPos".to_string(), Value::Int(-4));
+
+    // heightmaps are relative to the old minimum height
+    level.remove("Heightmaps");
+
+    for (old_key, key) in [
+        ("TileEntities", "block_entities"),
+        ("TileTicks", "block_ticks"),
+        ("LiquidTicks", "fluid_ticks"),
+        ("Structures", "structures"),
+    ] {
+        if let Some(value) = level.remove(old_key) {
+            level.insert(key.to_string(), value);
+        }
+    }
+    chunk.extend(level);
+    Ok(())
+}
+
+/// Dimensions were identified by numeric ids until 1.16
+fn upgrade_player_dimension(player: &mut Compound) -> Result<()> {
+    if let Some(Value::Int(dimension)) = player.get("Dimension") {
+        let dimension = match dimension {
+            -1 => "minecraft:the_nether",
+            1 => "minecraft:the_end",
+            _ => "minecraft:overworld",
+        };
+        player.insert(
+            "Dimension".to_string(),
+            Value::String(dimension.to_string()),
+        );
+    }
+    Ok(())
+}
+
+/// Long arrays written by other tools might be lists
+fn long_array(value: &Value) -> Result<Vec<i64>> {
+    match value {
+        Value::LongArray(values) => Ok(values.clone()),
+        Value::List(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::Long(value) => Ok(*value),
+                _ => Err(Error::InvalidData("long array contains non-longs")),
+            })
+            .collect(),
+        _ => Err(Error::InvalidData("not a long array")),
+    }
+}
+
+/// Least amount of bits needed for the palette
+fn bits(palette_len: usize) -> u32 {
+    u32::BITS - (palette_len.max(1) as u32 - 1).leading_zeros()
+}
+
+/// Packs the values into longs, without spanning multiple longs
+fn pack(values: &[u32], bits: u32) -> Vec<i64> {
+    let values_per_long = (u64::BITS / bits) as usize;
+    values
+        .chunks(values_per_long)
+        .map(|values| {
+            values
+                .iter()
+                .enumerate()
+                .fold(0u64, |data, (index, &value)| {
+                    data | (value as u64) << (index as u32 * bits)
+                }) as i64
+        })
+        .collect()
+}
+
+/// Names of the numeric biome ids used before 1.18, including the biomes which
+/// have been merged or renamed
+fn legacy_biome_name(biome: i32) -> &'static str {
+    match biome {
+        0 => "minecraft:ocean",
+        2 | 17 | 130 => "minecraft:desert",
+        3 | 20 | 162 => "minecraft:windswept_hills",
+        4 | 18 => "minecraft:forest",
+        5 | 19 | 133 => "minecraft:taiga",
+        6 | 134 => "minecraft:swamp",
+        7 => "minecraft:river",
+        8 => "minecraft:nether_wastes",
+        9 => "minecraft:the_end",
+        10 => "minecraft:frozen_ocean",
+        11 => "minecraft:frozen_river",
+        12 | 13 => "minecraft:snowy_plains",
+        14 | 15 => "minecraft:mushroom_fields",
+        16 => "minecraft:beach",
+        21 | 22 | 149 => "minecraft:jungle",
+        23 | 151 => "minecraft:sparse_jungle",
+        24 => "minecraft:deep_ocean",
+        25 => "minecraft:stony_shore",
+        26 => "minecraft:snowy_beach",
+        27 | 28 => "minecraft:birch_forest",
+        29 | 157 => "minecraft:dark_forest",
+        30 | 31 | 158 => "minecraft:snowy_taiga",
+        32 | 33 => "minecraft:old_growth_pine_taiga",
+        34 => "minecraft:windswept_forest",
+        35 => "minecraft:savanna",
+        36 | 164 => "minecraft:savanna_plateau",
+        37 | 39 | 167 => "minecraft:badlands",
+        38 | 166 => "minecraft:wooded_badlands",
+        40 => "minecraft:small_end_islands",
+        41 => "minecraft:end_midlands",
+        42 => "minecraft:end_highlands",
+        43 => "minecraft:end_barrens",
+        44 | 47 => "minecraft:warm_ocean",
+        45 => "minecraft:lukewarm_ocean",
+        46 => "minecraft:cold_ocean",
+        48 => "minecraft:deep_lukewarm_ocean",
+        49 => "minecraft:deep_cold_ocean",
+        50 => "minecraft:deep_frozen_ocean",
+        127 => "minecraft:the_void",
+        129 => "minecraft:sunflower_plains",
+        131 => "minecraft:windswept_gravelly_hills",
+        132 => "minecraft:flower_forest",
+        140 => "minecraft:ice_spikes",
+        155 | 156 => "minecraft:old_growth_birch_forest",
+        160 | 161 => "minecraft:old_growth_spruce_taiga",
+        163 => "minecraft:windswept_savanna",
+        165 => "minecraft:eroded_badlands",
+        168 | 169 => "minecraft:bamboo_jungle",
+        170 => "minecraft:soul_sand_valley",
+        171 => "minecraft:crimson_forest",
+        172 => "minecraft:warped_forest",
+        173 => "minecraft:basalt_deltas",
+        174 => "minecraft:dripstone_caves",
+        175 => "minecraft:lush_caves",
+        _ => "minecraft:plains",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_state(name: &str) -> Value {
+        Value::Compound(Compound::from([(
+            "Name".to_string(),
+            Value::String(name.to_string()),
+        )]))
+    }
+
+    #[test]
+    fn upgrade_block_states_padding() {
+        let palette = (0..17)
+            .map(|index| block_state(&format!("minecraft:block_{index}")))
+            .collect::<Vec<_>>();
+        let values = (0..16 * 16 * 16)
+            .map(|index| index % 17)
+            .collect::<Vec<u32>>();
+
+        // 5 bits per value, packed tightly
+        let mut block_states = vec![0i64; 16 * 16 * 16 * 5 / 64];
+        for (index, &value) in values.iter().enumerate() {
+            let bit_index = index * 5;
+            block_states[bit_index / 64] |= ((value as u64) << (bit_index % 64)) as i64;
+            if bit_index % 64 + 5 > 64 {
+                block_states[bit_index / 64 + 1] |=
+                    ((value as u64) >> (64 - bit_index % 64)) as i64;
+            }
+        }
+
+        let mut chunk = Compound::from([(
+            "Level".to_string(),
+            Value::Compound(Compound::from([(
+                "Sections".to_string(),
+                Value::List(vec![Value::Compound(Compound::from([
+                    ("Y".to_string(), Value::Byte(0)),
+                    ("Palette".to_string(), Value::List(palette)),
+                    ("BlockStates".to_string(), Value::LongArray(block_states)),
+                ]))]),
+            )])),
+        )]);
+        upgrade_chunk_block_states_padding(&mut chunk).unwrap();
+
+        let Value::LongArray(block_states) = &chunk["Level"]["Sections"][0]["BlockStates"] else {
+            panic!("block states are not a long array");
+        };
+        // 12 values per long, the remaining 4 bits are padding
+        assert_eq!(block_states.len(), (16 * 16 * 16usize).div_ceil(12));
+        for (index, &value) in values.iter().enumerate() {
+            let data = block_states[index / 12] as u64 >> (index % 12 * 5);
+            assert_eq!((data & 0x1F) as u32, value);
+        }
+    }
+
+    #[test]
+    fn upgrade_chunk_layout_to_1_18() {
+        let chunk = Value::Compound(Compound::from([
+            ("DataVersion".to_string(), Value::Int(2730)),
+            (
+                "Level".to_string(),
+                Value::Compound(Compound::from([
+                    ("xPos".to_string(), Value::Int(1)),
+                    ("zPos".to_string(), Value::Int(-2)),
+                    (
+                        "Sections".to_string(),
+                        Value::List(vec![Value::Compound(Compound::from([
+                            ("Y".to_string(), Value::Byte(0)),
+                            (
+                                "Palette".to_string(),
+                                Value::List(vec![block_state("minecraft:stone")]),
+                            ),
+                        ]))]),
+                    ),
+                    (
+                        "Biomes".to_string(),
+                        Value::IntArray(
+                            (0..1024)
+                                .map(|index| if index < 16 { 2 } else { 1 })
+                                .collect(),
+                        ),
+                    ),
+                    (
+                        "TileTicks".to_string(),
+                        Value::List(vec![Value::Compound(Compound::from([
+                            (
+                                "i".to_string(),
+                                Value::String("minecraft:stone".to_string()),
+                            ),
+                            ("x".to_string(), Value::Int(16)),
+                            ("y".to_string(), Value::Int(0)),
+                            ("z".to_string(), Value::Int(-32)),
+                            ("t".to_string(), Value::Int(1)),
+                            ("p".to_string(), Value::Int(0)),
+                        ]))]),
+                    ),
+                ])),
+            ),
+        ]));
+        let chunk = from_value::<Chunk>(chunk).unwrap();
+
+        assert_eq!((chunk.x, chunk.y, chunk.z), (1, -4, -2));
+        assert_eq!(chunk.sections.len(), 24);
+        for (section, section_y) in chunk.sections.iter().zip(-4i8..20) {
+            assert_eq!(section.y, section_y);
+            assert_eq!(section.block_states.data, None);
+            assert_eq!(section.block_states.palette.len(), 1);
+            assert_eq!(
+                section.block_states.palette[0].name,
+                if section_y == 0 {
+                    "minecraft:stone"
+                } else {
+                    "minecraft:air"
+                }
+            );
+        }
+
+        // the lowest cells are desert, sections below use the nearest cells
+        for section in &chunk.sections[..5] {
+            assert_eq!(
+                section.biomes.palette,
+                ["minecraft:desert", "minecraft:plains"]
+            );
+            assert!(section.biomes.data.is_some());
+        }
+        for section in &chunk.sections[5..] {
+            assert_eq!(section.biomes.palette, ["minecraft:plains"]);
+            assert_eq!(section.biomes.data, None);
+        }
+
+        assert_eq!(chunk.block_ticks.len(), 1);
+        assert_eq!(chunk.block_ticks[0].id, "minecraft:stone");
+    }
+
+    #[test]
+    fn upgrade_player_dimension_to_name() {
+        let player = Value::Compound(Compound::from([
+            ("DataVersion".to_string(), Value::Int(2230)),
+            (
+                "Pos".to_string(),
+                Value::List(vec![
+                    Value::Double(0.5),
+                    Value::Double(64.0),
+                    Value::Double(0.5),
+                ]),
+            ),
+            (
+                "Rotation".to_string(),
+                Value::List(vec![Value::Float(0.0), Value::Float(0.0)]),
+            ),
+            ("Dimension".to_string(), Value::Int(-1)),
+        ]));
+        let player = from_slice::<Player>(&tesseract_nbt::ser::to_vec(&player).unwrap()).unwrap();
+
+        assert_eq!(player.level, "minecraft:the_nether");
+        assert_eq!(player.entity.position, [0.5, 64.0, 0.5]);
+    }
+
+    #[test]
+    fn reject_data_before_the_flattening() {
+        let player = Value::Compound(Compound::from([(
+            "DataVersion".to_string(),
+            Value::Int(1343),
+        )]));
+        assert!(matches!(
+            from_value::<Player>(player),
+            Err(Error::UnsupportedVersion(1343))
+        ));
+    }
+}
@@ -0,0 +1,255 @@
+use bevy::prelude::*;
+
+use tesseract_base::{
+    actor, block,
+    hierarchy::{EntityCommandsExt, IndexedChildren, ParentWithIndex},
+    item, level,
+};
+
+/// Physical block breaking and placing, players are always in creative mode,
+/// therefore blocks are broken instantly and items are not consumed
+#[derive(Default)]
+pub struct GameplayPlugin;
+
+impl Plugin for GameplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Cancellable<BreakBlock>>()
+            .init_resource::<Cancellable<PlaceBlock>>()
+            .add_systems(Update, interact.in_set(GameplaySet::Interact))
+            .add_systems(
+                Update,
+                (break_blocks, place_blocks)
+                    .in_set(GameplaySet::Apply)
+                    .after(GameplaySet::Interact),
+            );
+    }
+}
+
+/// Interactions are turned into events in [`GameplaySet::Interact`], which are
+/// applied in [`GameplaySet::Apply`], systems in between can cancel them
+#[derive(SystemSet, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GameplaySet {
+    Interact,
+    Apply,
+}
+
+/// Events of the current tick, which can be cancelled until they are applied
+#[derive(Resource)]
+pub struct Cancellable<T>(Vec<(T, bool)>);
+
+impl<T> Default for Cancellable<T> {
+    fn default() -> Self {
+        Self(vec![])
+    }
+}
+
+impl<T> Cancellable<T> {
+    /// Returns all events which have not been cancelled
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.0
+            .iter()
+            .filter(|(_, cancelled)| !cancelled)
+            .map(|(event, _)| event)
+    }
+
+    /// Cancels all events for which the predicate returns true
+    pub fn cancel(&mut self, mut predicate: impl FnMut(&T) -> bool) {
+        for (event, cancelled) in self.0.iter_mut() {
+            if !*cancelled && predicate(event) {
+                *cancelled = true;
+            }
+        }
+    }
+
+    fn send(&mut self, event: T) {
+        self.0.push((event, false));
+    }
+
+    fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.0
+            .drain(..)
+            .filter(|(_, cancelled)| !cancelled)
+            .map(|(event, _)| event)
+    }
+}
+
+/// Block which is about to be broken by a player
+pub struct BreakBlock {
+    pub player: Entity,
+    pub level: Entity,
+    pub position: IVec3,
+    pub block: Entity,
+}
+
+/// Block which is about to be placed by a player, using the item instance
+pub struct PlaceBlock {
+    pub player: Entity,
+    pub level: Entity,
+    pub position: IVec3,
+    pub block: Entity,
+    pub item_instance: Entity,
+}
+
+/// Turns the interactions of players into events
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn interact(
+    mut break_blocks: ResMut<Cancellable<BreakBlock>>,
+    mut place_blocks: ResMut<Cancellable<PlaceBlock>>,
+
+    level_blocks: level::LevelBlocksRef,
+    chunk_access: Query<&ParentWithIndex<IVec2>>,
+    block_access: Query<(Option<&block::Air>, Option<&block::Replaceable>), With<block::Base>>,
+    item_instance_access: Query<&item::Instance>,
+
+    mut for_players: Query<
+        (
+            Entity,
+            &Parent,
+            Option<&IndexedChildren<item::Slot>>,
+            &mut actor::player::Interaction,
+        ),
+        Changed<actor::player::Interaction>,
+    >,
+) {
+    for (player, chunk, inventory, mut interaction) in for_players.iter_mut() {
+        let Ok(indexed_chunk) = chunk_access.get(chunk.get()) else {
+            continue;
+        };
+        let level = indexed_chunk.parent;
+
+        let block_at = |position| level_blocks.get(level, position).ok().map(Entity::from_raw);
+        let is_replaceable = |block| {
+            block_access
+                .get(block)
+                .map_or(false, |(_, replaceable)| replaceable.is_some())
+        };
+
+        match *interaction {
+            actor::player::Interaction::None | actor::player::Interaction::UseItem => continue,
+            actor::player::Interaction::BreakBlock(position) => {
+                let Some(block) = block_at(position) else {
+                    continue;
+                };
+                if block_access
+                    .get(block)
+                    .map_or(true, |(air, _)| air.is_none())
+                {
+                    break_blocks.send(BreakBlock {
+                        player,
+                        level,
+                        position,
+                        block,
+                    });
+                }
+            }
+            actor::player::Interaction::UseItemOn(position, direction, ref slot) => {
+                let Some(item_instance) =
+                    inventory.and_then(|inventory| inventory.0.get(slot).copied())
+                else {
+                    continue;
+                };
+                // only items which are also blocks can be placed
+                let Ok(item_instance_data) = item_instance_access.get(item_instance) else {
+                    continue;
+                };
+                if !block_access.contains(item_instance_data.item) {
+                    continue;
+                }
+
+                // the clicked block is replaced if possible, otherwise the block next to
+                // the clicked face
+                let Some(clicked_block) = block_at(position) else {
+                    continue;
+                };
+                let position = if is_replaceable(clicked_block) {
+                    position
+                } else {
+                    position + direction.vector()
+                };
+                if block_at(position).map_or(false, is_replaceable) {
+                    place_blocks.send(PlaceBlock {
+                        player,
+                        level,
+                        position,
+                        block: item_instance_data.item,
+                        item_instance,
+                    });
+                }
+            }
+        }
+
+        *interaction = actor::player::Interaction::None;
+    }
+}
+
+/// Block which replaces broken blocks, the air block with the lowest id
+struct AirCache {
+    air: Option<u32>,
+}
+
+impl FromWorld for AirCache {
+    fn from_world(world: &mut World) -> Self {
+        let mut for_blocks =
+            world.query_filtered::<Entity, (With<block::Base>, With<block::Air>)>();
+
+        let air = for_blocks.iter(world).map(|block| block.index()).min();
+        if air.is_none() {
+            warn!("No air block exists, blocks can't be broken");
+        }
+        Self { air }
+    }
+}
+
+/// Replaces broken blocks with air, and removes their block entities
+fn break_blocks(
+    mut commands: Commands,
+    air_cache: Local<AirCache>,
+    mut break_blocks: ResMut<Cancellable<BreakBlock>>,
+
+    mut level_blocks: level::LevelBlocks,
+) {
+    let Some(air) = air_cache.air else {
+        break_blocks.0.clear();
+        return;
+    };
+
+    for break_block in break_blocks.drain() {
+        let Ok(chunk) = level_blocks.chunk(break_block.level, break_block.position) else {
+            continue;
+        };
+        if level_blocks
+            .set(break_block.level, break_block.position, air)
+            .is_ok()
+        {
+            commands
+                .entity(chunk)
+                .set_indexed_child(break_block.position, None);
+        }
+    }
+}
+
+/// Places blocks, and removes the block entities of the replaced blocks
+fn place_blocks(
+    mut commands: Commands,
+    mut place_blocks: ResMut<Cancellable<PlaceBlock>>,
+
+    mut level_blocks: level::LevelBlocks,
+) {
+    for place_block in place_blocks.drain() {
+        let Ok(chunk) = level_blocks.chunk(place_block.level, place_block.position) else {
+            continue;
+        };
+        if level_blocks
+            .set(
+                place_block.level,
+                place_block.position,
+                place_block.block.index(),
+            )
+            .is_ok()
+        {
+            commands
+                .entity(chunk)
+                .set_indexed_child(place_block.position, None);
+        }
+    }
+}
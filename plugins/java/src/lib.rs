@@ -4,13 +4,17 @@ use std::borrow::Cow;
 
 use bevy::prelude::*;
 
+pub use gameplay::GameplayPlugin;
 pub use persistence::PersistencePlugin;
 pub use registry::RegistryPlugin;
 pub use replication::ReplicationPlugin;
+pub use worldgen::WorldGenPlugin;
 
+pub mod gameplay;
 pub mod persistence;
 pub mod registry;
 pub mod replication;
+pub mod worldgen;
 
 /// Tags which can be used to group entities together
 #[derive(Component)]
@@ -58,4 +62,21 @@ pub mod block {
     pub enum Auto {
         Snowy,
     }
+
+    /// Type and data of block entities, the data is kept as is, and is also
+    /// sent to clients (part of Block instance)
+    #[derive(Component)]
+    pub struct EntityData {
+        pub type_: String,
+        pub data: tesseract_nbt::value::Compound,
+    }
+}
+
+pub mod item {
+    use bevy::prelude::*;
+
+    /// Tag of item instances, like enchantments or custom names, the tag is
+    /// kept as is (part of Item instance)
+    #[derive(Component)]
+    pub struct Data(pub tesseract_nbt::value::Compound);
 }
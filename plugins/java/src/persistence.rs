@@ -1,101 +1,234 @@
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::Read,
-    path::{Path, PathBuf},
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
-use bevy::{math::DVec3, prelude::*};
-use flate2::read::GzDecoder;
+use bevy::{
+    math::DVec3,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use futures::FutureExt;
+use uuid::Uuid;
 
 pub use tesseract_base::persistence::*;
-use tesseract_base::{actor, hierarchy::ParentWithIndex, level};
+use tesseract_base::{
+    actor,
+    hierarchy::{EntityCommandsExt, IndexedChildren, ParentWithIndex},
+    item, level,
+};
 use tesseract_java_protocol::types::{Biome, BitStorage, PalettedContainer};
 
-use crate::{registry, replication};
+use crate::{block, registry, replication};
 
 /// Support for Minecraft: Java Edition persistence
-pub struct PersistencePlugin(pub HashMap<String, PathBuf>);
+pub struct PersistencePlugin {
+    pub levels: HashMap<String, PathBuf>,
+    pub player_data_path: PathBuf,
+
+    pub autosave_interval: Duration,
+    pub chunk_load_budget: usize,
+
+    /// Block which is used for unknown block states, chunks with unknown block
+    /// states are quarantined if there is none
+    pub fallback_block: Option<String>,
+}
+
+impl Default for PersistencePlugin {
+    fn default() -> Self {
+        Self {
+            levels: HashMap::new(),
+            player_data_path: PathBuf::from("levels/overworld/playerdata"),
+
+            autosave_interval: Duration::from_secs(5 * 60),
+            chunk_load_budget: 64,
+
+            fallback_block: Some("minecraft:air".to_string()),
+        }
+    }
+}
 
 impl Plugin for PersistencePlugin {
     fn build(&self, app: &mut App) {
-        let levels = self.0.clone();
-        let spawn_levels = move |mut commands: Commands| {
-            for (level_name, level_path) in levels.iter() {
-                let savegame_level = {
-                    let mut data = vec![];
-                    GzDecoder::new(File::open(level_path.join("level.dat")).unwrap())
-                        .read_to_end(&mut data)
-                        .unwrap();
-                    tesseract_nbt::de::from_slice::<tesseract_java_savegame::level::Level>(
-                        &mut data.as_slice(),
-                    )
-                    .unwrap()
-                }
-                .data;
-
-                commands.spawn((
-                    level::LevelBundle {
-                        base: level::Base::new(level_name.clone(), level_name.clone()),
-                        age_and_time: level::AgeAndTime {
-                            age: savegame_level.time as u64,
-                            time: savegame_level.day_time as u64,
+        let levels = self.levels.clone();
+        let spawn_levels =
+            move |mut commands: Commands, mut errors: EventWriter<PersistenceError>| {
+                for (level_name, level_path) in levels.iter() {
+                    // levels without (valid) level.dat start at the beginning of time
+                    let (age_and_time, random_tick_speed) =
+                        match tesseract_java_savegame::read_compressed(level_path.join("level.dat"))
+                        {
+                            Ok(tesseract_java_savegame::level::Level {
+                                data: savegame_level,
+                            }) => (
+                                level::AgeAndTime {
+                                    age: savegame_level.time as u64,
+                                    time: savegame_level.day_time as u64,
+                                },
+                                savegame_level
+                                    .game_rules
+                                    .get("randomTickSpeed")
+                                    .and_then(|random_tick_speed| random_tick_speed.parse().ok())
+                                    .map(level::RandomTickSpeed),
+                            ),
+                            Err(error) => {
+                                error!("Failed to load level {:?}: {}", level_name, error);
+                                errors.send(PersistenceError::Level {
+                                    level_name: level_name.clone(),
+                                    error,
+                                });
+                                Default::default()
+                            }
+                        };
+
+                    let mut level_commands = commands.spawn((
+                        level::LevelBundle {
+                            base: level::Base::new(level_name.clone(), level_name.clone()),
+                            age_and_time,
                         },
-                    },
-                    Persistence {
-                        region_storage: tesseract_java_savegame::region::RegionStorage::new(
-                            level_path.join("region"),
-                        ),
-                    },
-                ));
-            }
-        };
+                        Persistence {
+                            region_storage: Arc::new(Mutex::new(
+                                tesseract_java_savegame::region::RegionStorage::new(
+                                    level_path.join("region"),
+                                ),
+                            )),
+                        },
+                    ));
+                    if let Some(random_tick_speed) = random_tick_speed {
+                        level_commands.insert(random_tick_speed);
+                    }
+                }
+            };
 
-        app.add_systems(PreStartup, spawn_levels)
-            .add_systems(First, (load_players, load_chunks).before(UpdateFlush))
-            .add_systems(
-                First,
-                apply_system_buffers
-                    .in_set(UpdateFlush)
-                    .after(replication::UpdateFlush),
-            );
+        app.insert_resource(PlayerStorage {
+            path: self.player_data_path.clone(),
+        })
+        .insert_resource(Autosave(Timer::new(
+            self.autosave_interval,
+            TimerMode::Repeating,
+        )))
+        .insert_resource(ChunkLoadBudget(self.chunk_load_budget))
+        .insert_resource(FallbackBlock(self.fallback_block.clone()))
+        .add_event::<PersistenceError>()
+        .add_systems(PreStartup, spawn_levels)
+        .add_systems(First, update_autosave)
+        .add_systems(
+            First,
+            (load_players, load_chunks, finish_loading_chunks).before(UpdateFlush),
+        )
+        .add_systems(
+            First,
+            apply_system_buffers
+                .in_set(UpdateFlush)
+                .after(replication::UpdateFlush),
+        )
+        .add_systems(PostUpdate, mark_unsaved_chunks)
+        .add_systems(Last, (save_players, save_and_unload_chunks));
     }
 }
 
+/// Failure while loading or saving, the affected data is either skipped or
+/// left untouched
+#[derive(Debug)]
+pub enum PersistenceError {
+    Level {
+        level_name: String,
+        error: tesseract_java_savegame::Error,
+    },
+    Player {
+        player: Entity,
+        error: tesseract_java_savegame::Error,
+    },
+    Chunk {
+        level: Entity,
+        position: IVec2,
+        error: tesseract_java_savegame::Error,
+    },
+}
+
 #[derive(Component)]
 struct Persistence {
-    region_storage: tesseract_java_savegame::region::RegionStorage,
+    region_storage: Arc<Mutex<tesseract_java_savegame::region::RegionStorage>>,
+}
+
+#[derive(Resource)]
+struct PlayerStorage {
+    path: PathBuf,
+}
+
+impl PlayerStorage {
+    fn path(&self, id: Uuid) -> PathBuf {
+        self.path.join(format!("{id}.dat"))
+    }
+}
+
+#[derive(Resource)]
+struct Autosave(Timer);
+
+fn update_autosave(time: Res<Time>, mut autosave: ResMut<Autosave>) {
+    autosave.0.tick(time.delta());
+}
+
+struct ItemCache {
+    items: HashMap<String, Entity>,
+}
+
+impl FromWorld for ItemCache {
+    fn from_world(world: &mut World) -> Self {
+        let mut for_items = world.query_filtered::<(Entity, &block::Name), With<item::Base>>();
+
+        let mut items = HashMap::new();
+        for (item, item_name) in for_items.iter(world) {
+            items.insert(item_name.name.clone(), item);
+        }
+        Self { items }
+    }
 }
 
 /// Loads savegame data for newly connected players
 fn load_players(
     mut commands: Commands,
+    item_cache: Local<ItemCache>,
+    player_storage: Res<PlayerStorage>,
+    mut persistence_errors: EventWriter<PersistenceError>,
 
     levels_access: Query<(Entity, &level::Base)>,
     for_players: Query<(Entity, &replication::Connection), Added<replication::Connection>>,
 ) {
     for (player, connection) in for_players.iter() {
-        let savegame_player_path =
-            format!("levels/overworld/playerdata/{}.dat", connection.user().id);
-        let savegame_player_path = Path::new(&savegame_player_path);
+        let savegame_player_path = player_storage.path(connection.user().id);
         if savegame_player_path.exists() {
-            let savegame_player = {
-                let mut data = vec![];
-                GzDecoder::new(File::open(savegame_player_path).unwrap())
-                    .read_to_end(&mut data)
-                    .unwrap();
-                tesseract_nbt::de::from_slice::<tesseract_java_savegame::entity::Player>(
-                    &mut data.as_slice(),
-                )
-                .unwrap()
-            };
+            // players with unreadable data are treated like new players, the data is
+            // moved aside first, as it would be overwritten when they are saved
+            let savegame_player: tesseract_java_savegame::entity::Player =
+                match tesseract_java_savegame::read_compressed(&savegame_player_path) {
+                    Ok(savegame_player) => savegame_player,
+                    Err(error) => {
+                        error!("Failed to load {:?}: {}", player, error);
+                        persistence_errors.send(PersistenceError::Player { player, error });
+
+                        let corrupt_savegame_player_path =
+                            savegame_player_path.with_extension("dat.corrupt");
+                        if let Err(error) =
+                            std::fs::rename(&savegame_player_path, &corrupt_savegame_player_path)
+                        {
+                            error!(
+                                "Failed to move {:?} to {:?}: {}",
+                                savegame_player_path, corrupt_savegame_player_path, error
+                            );
+                        }
+                        continue;
+                    }
+                };
 
             if let Some((level, _)) = levels_access
                 .iter()
                 .find(|(_, level_base)| level_base.name() == savegame_player.level)
             {
-                commands
-                    .entity(player)
+                let mut player_commands = commands.entity(player);
+                player_commands
                     .insert(actor::player::PlayerBundle {
                         base: actor::Base {
                             id: connection.user().id,
@@ -110,6 +243,35 @@ fn load_players(
                         interaction: Default::default(),
                     })
                     .set_parent(level);
+
+                for savegame_item in savegame_player.inventory {
+                    // empty stacks are treated like empty slots
+                    if savegame_item.count <= 0 {
+                        continue;
+                    }
+
+                    let (Some(slot), Some(&item)) = (
+                        savegame_item.slot.and_then(slot_from_savegame),
+                        item_cache.items.get(&savegame_item.id),
+                    ) else {
+                        warn!(
+                            "Item ({:?}) in slot {:?} for {:?} does not exist",
+                            savegame_item.id, savegame_item.slot, player
+                        );
+                        continue;
+                    };
+
+                    let mut item_instance_commands =
+                        player_commands.commands().spawn(item::Instance {
+                            item,
+                            count: savegame_item.count as u8,
+                        });
+                    if let Some(tag) = savegame_item.tag {
+                        item_instance_commands.insert(crate::item::Data(tag));
+                    }
+                    let item_instance = item_instance_commands.id();
+                    player_commands.set_indexed_child(slot, Some(item_instance));
+                }
             } else {
                 warn!(
                     "Level ({:?}) for {:?} does not exist",
@@ -120,87 +282,1046 @@ fn load_players(
     }
 }
 
+/// Saves players when they disconnect, or periodically while connected
+#[allow(clippy::type_complexity)]
+fn save_players(
+    mut disconnected_players: RemovedComponents<replication::Connection>,
+    player_storage: Res<PlayerStorage>,
+    autosave: Res<Autosave>,
+    mut persistence_errors: EventWriter<PersistenceError>,
+
+    levels_access: Query<&level::Base>,
+    chunks_access: Query<&ParentWithIndex<IVec2>>,
+    items_access: Query<&block::Name, With<item::Base>>,
+    item_instances_access: Query<(&item::Instance, Option<&crate::item::Data>)>,
+    players_access: Query<(
+        &actor::Base,
+        &actor::Position,
+        &actor::Rotation,
+        &Parent,
+        Option<&IndexedChildren<item::Slot>>,
+    )>,
+    for_players: Query<Entity, With<replication::Connection>>,
+) {
+    let players = disconnected_players.iter().collect::<Vec<_>>();
+    let players = if autosave.0.just_finished() {
+        players
+            .into_iter()
+            .chain(for_players.iter())
+            .collect::<Vec<_>>()
+    } else {
+        players
+    };
+
+    for (player, (actor_base, actor_position, actor_rotation, level_or_chunk, inventory)) in players
+        .into_iter()
+        .filter_map(|player| Some((player, players_access.get(player).ok()?)))
+    {
+        let level = chunks_access
+            .get(level_or_chunk.get())
+            .map_or(level_or_chunk.get(), |indexed_chunk| indexed_chunk.parent);
+        let Ok(level_base) = levels_access.get(level) else {
+            continue;
+        };
+
+        let savegame_player = tesseract_java_savegame::entity::Player {
+            version: tesseract_java_savegame::DATA_VERSION,
+            entity: tesseract_java_savegame::entity::Entity {
+                position: actor_position.0.to_array(),
+                rotation: [actor_rotation.yaw, actor_rotation.pitch],
+            },
+            level: level_base.name().to_string(),
+            inventory: inventory.map_or_else(Vec::new, |inventory| {
+                inventory
+                    .0
+                    .iter()
+                    .filter_map(|(slot, &item_instance)| {
+                        let (item_instance, item_data) =
+                            item_instances_access.get(item_instance).ok()?;
+                        if item_instance.count == 0 {
+                            return None;
+                        }
+
+                        Some(tesseract_java_savegame::entity::Item {
+                            slot: Some(slot_to_savegame(slot)?),
+                            id: items_access.get(item_instance.item).ok()?.name.clone(),
+                            count: item_instance.count as i8,
+                            tag: item_data.map(|item_data| item_data.0.clone()),
+                        })
+                    })
+                    .collect()
+            }),
+        };
+
+        if let Err(error) = tesseract_java_savegame::write_compressed(
+            player_storage.path(actor_base.id),
+            &savegame_player,
+        ) {
+            error!("Failed to save {:?}: {}", player, error);
+            persistence_errors.send(PersistenceError::Player { player, error });
+        }
+    }
+}
+
+fn slot_from_savegame(slot: i8) -> Option<item::Slot> {
+    Some(match slot {
+        0..=8 => item::Slot::Hotbar(slot as u8),
+        9..=35 => item::Slot::Inventory((slot - 9) as u8),
+        100 => item::Slot::Feet,
+        101 => item::Slot::Legs,
+        102 => item::Slot::Torso,
+        103 => item::Slot::Head,
+        -106 => item::Slot::Offhand,
+        _ => return None,
+    })
+}
+
+fn slot_to_savegame(slot: &item::Slot) -> Option<i8> {
+    Some(match slot {
+        item::Slot::Cursor => return None,
+        item::Slot::Hotbar(index) => *index as i8,
+        item::Slot::Inventory(index) => 9 + *index as i8,
+        item::Slot::Feet => 100,
+        item::Slot::Legs => 101,
+        item::Slot::Torso => 102,
+        item::Slot::Head => 103,
+        item::Slot::Offhand => -106,
+    })
+}
+
+#[derive(Resource)]
+struct FallbackBlock(Option<String>);
+
 struct RenderCache {
-    blocks: HashMap<String, u32>,
+    blocks: HashMap<String, Vec<(BTreeMap<String, String>, u32)>>,
+    /// Blocks for the default block state by name
+    default_blocks: HashMap<String, u32>,
+    fallback_block: Option<u32>,
+    /// Block states which have already been warned about, as they are usually
+    /// in the palette of many chunks
+    unknown_block_states: Mutex<HashSet<String>>,
 }
 
 impl FromWorld for RenderCache {
-    fn from_world(_world: &mut World) -> Self {
-        todo!()
+    fn from_world(world: &mut World) -> Self {
+        let fallback_block_name = world
+            .resource::<FallbackBlock>()
+            .0
+            .as_ref()
+            .map(|fallback_block| block::Name::new(fallback_block.clone()));
+        let mut for_blocks =
+            world.query_filtered::<(Entity, &block::Name), With<tesseract_base::block::Base>>();
+
+        let mut blocks = HashMap::<_, Vec<_>>::new();
+        let mut fallback_block = None;
+        for (block_id, block_name) in for_blocks.iter(world) {
+            blocks
+                .entry(block_name.name.clone())
+                .or_default()
+                .push((block_name.properties.clone(), block_id.index()));
+            if fallback_block_name.as_ref() == Some(block_name) {
+                fallback_block = Some(block_id.index());
+            }
+        }
+
+        // most specific blocks first
+        for block_states in blocks.values_mut() {
+            block_states.sort_by_key(|(properties, _)| std::cmp::Reverse(properties.len()));
+        }
+
+        let blocks_report = world.resource::<registry::BlocksReport>();
+        let default_blocks = blocks
+            .iter()
+            .filter_map(|(name, block_states)| {
+                let default_properties = &blocks_report
+                    .0
+                    .get(name)?
+                    .states
+                    .iter()
+                    .find(|block_state_report| block_state_report.default)?
+                    .properties;
+                block_states
+                    .iter()
+                    .find(|(properties, _)| {
+                        properties.iter().all(|(property_key, property_value)| {
+                            default_properties.get(property_key) == Some(property_value)
+                        })
+                    })
+                    .map(|&(_, block)| (name.clone(), block))
+            })
+            .collect();
+
+        if let (Some(fallback_block_name), None) = (&fallback_block_name, fallback_block) {
+            warn!(
+                "Fallback block ({:?}) does not exist",
+                fallback_block_name.name
+            );
+        }
+        Self {
+            blocks,
+            default_blocks,
+            fallback_block,
+            unknown_block_states: Default::default(),
+        }
+    }
+}
+
+impl RenderCache {
+    /// Finds the most specific block for the block state, blocks don't have to
+    /// specify properties which are automatically derived or irrelevant
+    fn block(
+        &self,
+        block_state: &tesseract_java_savegame::BlockState,
+    ) -> tesseract_java_savegame::Result<u32> {
+        if let Some(&(_, block)) = self.blocks.get(&block_state.name).and_then(|block_states| {
+            block_states.iter().find(|(properties, _)| {
+                properties.iter().all(|(property_key, property_value)| {
+                    block_state.properties.get(property_key) == Some(property_value)
+                })
+            })
+        }) {
+            return Ok(block);
+        }
+
+        let block_state_name = block_state.name();
+        if self
+            .unknown_block_states
+            .lock()
+            .unwrap()
+            .insert(block_state_name.clone())
+        {
+            warn!("Block state ({}) does not exist", block_state_name);
+        }
+        self.fallback_block
+            .ok_or(tesseract_java_savegame::Error::UnknownBlockState(
+                block_state_name,
+            ))
     }
 }
 
-/// Loads savegame chunks for newly spawned chunks
+/// Everything needed to decode savegame chunks, can be shared with tasks
+#[derive(Clone)]
+struct ChunkDecoder {
+    render_cache: Arc<RenderCache>,
+    biomes: Arc<HashMap<String, u32>>,
+}
+
+impl FromWorld for ChunkDecoder {
+    fn from_world(world: &mut World) -> Self {
+        let biomes = world
+            .resource::<registry::DataRegistry<Biome>>()
+            .registry()
+            .value
+            .iter()
+            .map(|biome| (biome.name.clone(), biome.id))
+            .collect();
+        Self {
+            render_cache: Arc::new(RenderCache::from_world(world)),
+            biomes: Arc::new(biomes),
+        }
+    }
+}
+
+/// Chunk data, the heightmaps if they have been saved, the block entities by
+/// their position in the level, and the scheduled ticks, which are due relative
+/// to the age of the level
+struct DecodedChunk {
+    data: level::chunk::Data,
+    heightmaps: Option<level::chunk::Heightmaps>,
+    block_entities: Vec<(IVec3, block::EntityData)>,
+    scheduled_ticks: Vec<level::chunk::ScheduledTick>,
+}
+
+impl ChunkDecoder {
+    fn decode(&self, region_chunk_data: &[u8]) -> tesseract_java_savegame::Result<DecodedChunk> {
+        let savegame_chunk = tesseract_java_savegame::upgrade::from_slice::<
+            tesseract_java_savegame::chunk::Chunk,
+        >(region_chunk_data)?;
+        let sections = savegame_chunk
+            .sections
+            .into_iter()
+            .map(|savegame_chunk_section| {
+                Ok(level::chunk::DataSection {
+                    block_states: load_paletted_container(
+                        savegame_chunk_section.block_states,
+                        |entry| self.render_cache.block(entry),
+                    )?,
+                    biomes: load_paletted_container(savegame_chunk_section.biomes, |entry| {
+                        Ok(self.biome_id(entry))
+                    })?,
+                    block_state_changes: Default::default(),
+                })
+            })
+            .collect::<tesseract_java_savegame::Result<Vec<_>>>()?;
+
+        // heightmaps are computed again if missing or invalid
+        let height = sections.len() as u32 * 16;
+        let heightmap = |data: Option<Vec<u64>>| level::chunk::Heightmap::from_data(height, data?);
+        let heightmaps = heightmap(savegame_chunk.heightmaps.motion_blocking)
+            .zip(heightmap(savegame_chunk.heightmaps.world_surface))
+            .map(
+                |(motion_blocking, world_surface)| level::chunk::Heightmaps {
+                    motion_blocking,
+                    world_surface,
+                },
+            );
+
+        let block_entities = savegame_chunk
+            .block_entities
+            .into_iter()
+            .map(|savegame_block_entity| {
+                (
+                    IVec3::new(
+                        savegame_block_entity.x,
+                        savegame_block_entity.y,
+                        savegame_block_entity.z,
+                    ),
+                    block::EntityData {
+                        type_: savegame_block_entity.id,
+                        data: savegame_block_entity.data,
+                    },
+                )
+            })
+            .collect();
+
+        let data = level::chunk::Data {
+            sections,
+            y_offset: 4,
+        };
+        let scheduled_ticks = self
+            .decode_scheduled_ticks(
+                savegame_chunk.block_ticks,
+                level::chunk::TickKind::Block,
+                &data,
+            )
+            .chain(self.decode_scheduled_ticks(
+                savegame_chunk.fluid_ticks,
+                level::chunk::TickKind::Fluid,
+                &data,
+            ))
+            .collect();
+
+        Ok(DecodedChunk {
+            data,
+            heightmaps,
+            block_entities,
+            scheduled_ticks,
+        })
+    }
+
+    /// Resolves ticks to the block at their position if the name matches,
+    /// otherwise to the default block state, fluids are identified by any of
+    /// their blocks, which also includes flowing fluids, ticks of unknown
+    /// blocks or fluids are skipped
+    fn decode_scheduled_ticks<'a>(
+        &'a self,
+        savegame_scheduled_ticks: Vec<tesseract_java_savegame::chunk::ScheduledTick>,
+        kind: level::chunk::TickKind,
+        data: &'a level::chunk::Data,
+    ) -> impl Iterator<Item = level::chunk::ScheduledTick> + 'a {
+        savegame_scheduled_ticks
+            .into_iter()
+            .filter_map(move |savegame_scheduled_tick| {
+                let name = match kind {
+                    level::chunk::TickKind::Block => savegame_scheduled_tick.id.clone(),
+                    level::chunk::TickKind::Fluid => {
+                        savegame_scheduled_tick.id.replace(":flowing_", ":")
+                    }
+                };
+                let y = savegame_scheduled_tick.y + data.y_offset as i32 * 16;
+                if y < 0 || y >= data.sections.len() as i32 * 16 {
+                    return None;
+                }
+                let position = level::chunk::BlockPosition::new(
+                    savegame_scheduled_tick.x as u8,
+                    y as u16,
+                    savegame_scheduled_tick.z as u8,
+                );
+
+                let block_at_position = data.get(position.x(), position.y(), position.z());
+                let block = if self
+                    .render_cache
+                    .blocks
+                    .get(&name)
+                    .map_or(false, |block_states| {
+                        block_states
+                            .iter()
+                            .any(|&(_, block)| block == block_at_position)
+                    }) {
+                    block_at_position
+                } else if let Some(&block) = self.render_cache.default_blocks.get(&name) {
+                    block
+                } else {
+                    warn!(
+                        "Scheduled tick for unknown block or fluid ({})",
+                        savegame_scheduled_tick.id
+                    );
+                    return None;
+                };
+
+                Some(level::chunk::ScheduledTick {
+                    position,
+                    kind,
+                    block,
+                    due: savegame_scheduled_tick.delay.max(0) as u64,
+                    priority: savegame_scheduled_tick.priority.clamp(-3, 3) as i8,
+                })
+            })
+    }
+
+    fn biome_id(&self, name: &str) -> u32 {
+        *self.biomes.get(name).unwrap_or(&0)
+    }
+}
+
+/// Unpacks the palette and storage, and checks that the storage has the
+/// expected length and only refers to existing palette entries
+fn load_paletted_container<
+    T,
+    const STORAGE_SIZE: u32,
+    const LINEAR_MIN_BITS: u32,
+    const LINEAR_MAX_BITS: u32,
+    const GLOBAL_BITS: u32,
+>(
+    savegame_paletted_container: tesseract_java_savegame::PalettedContainer<T>,
+    palette_entry_to_value: impl Fn(&T) -> tesseract_java_savegame::Result<u32>,
+) -> tesseract_java_savegame::Result<
+    PalettedContainer<STORAGE_SIZE, LINEAR_MIN_BITS, LINEAR_MAX_BITS, GLOBAL_BITS>,
+> {
+    let palette = savegame_paletted_container
+        .palette
+        .iter()
+        .map(palette_entry_to_value)
+        .collect::<tesseract_java_savegame::Result<Vec<_>>>()?;
+    let Some(data) = savegame_paletted_container.data else {
+        return palette
+            .first()
+            .map(|&value| PalettedContainer::Single(value))
+            .ok_or(tesseract_java_savegame::Error::InvalidPalettedContainer);
+    };
+
+    // same as BitStorage::from_data, which would panic on unexpected lengths
+    let values_per_long = match data.len() {
+        0 => 0,
+        1 if STORAGE_SIZE <= 64 => 64,
+        1 => 0,
+        data_len => (STORAGE_SIZE - 1) / (data_len as u32 - 1),
+    };
+    if !(1..=64).contains(&values_per_long)
+        || STORAGE_SIZE.div_ceil(values_per_long) as usize != data.len()
+    {
+        return Err(tesseract_java_savegame::Error::InvalidPalettedContainer);
+    }
+
+    let storage = BitStorage::from_data(STORAGE_SIZE, data);
+    if (0..STORAGE_SIZE).any(|index| storage.get(index) as usize >= palette.len()) {
+        return Err(tesseract_java_savegame::Error::InvalidPalettedContainer);
+    }
+
+    Ok(PalettedContainer::Indirect { palette, storage }.fix())
+}
+
+/// Chunk which is being loaded (part of Chunk)
+#[derive(Component)]
+pub(crate) struct Loading(Task<tesseract_java_savegame::Result<Option<DecodedChunk>>>);
+
+#[derive(Resource)]
+struct ChunkLoadBudget(usize);
+
+/// Starts loading savegame chunks for newly spawned chunks in the background
 fn load_chunks(
     mut commands: Commands,
-    render_cache: Local<RenderCache>,
-    biome_registry: Res<registry::DataRegistry<Biome>>,
+    chunk_decoder: Local<ChunkDecoder>,
 
-    mut levels_access: Query<&mut Persistence>,
+    levels_access: Query<&Persistence>,
     for_chunks: Query<(Entity, &ParentWithIndex<IVec2>), Added<level::chunk::Base>>,
 ) {
+    let task_pool = AsyncComputeTaskPool::get();
     for (chunk, indexed_chunk) in for_chunks.iter() {
-        let region_storage = &mut levels_access
-            .get_mut(indexed_chunk.parent)
-            .unwrap()
-            .region_storage;
-        if let Some(region_chunk_data) = region_storage.read(indexed_chunk.index) {
-            let savegame_chunk = tesseract_nbt::de::from_slice::<
-                tesseract_java_savegame::chunk::Chunk,
-            >(&mut region_chunk_data.as_slice())
-            .unwrap();
-            let sections = savegame_chunk
-                .sections
-                .into_iter()
-                .map(|savegame_chunk_section| level::chunk::DataSection {
-                    block_states: if let Some(data) = savegame_chunk_section.block_states.data {
-                        PalettedContainer::Indirect {
-                            palette: savegame_chunk_section
-                                .block_states
-                                .palette
-                                .iter()
-                                .map(|entry| render_cache.blocks[&entry.name])
-                                .collect(),
-                            storage: BitStorage::from_data(16 * 16 * 16, data),
-                        }
-                    } else {
-                        PalettedContainer::Single(
-                            render_cache.blocks[&savegame_chunk_section
-                                .block_states
-                                .palette
-                                .first()
-                                .unwrap()
-                                .name],
-                        )
-                    },
-                    biomes: if let Some(data) = savegame_chunk_section.biomes.data {
-                        PalettedContainer::Indirect {
-                            palette: savegame_chunk_section
-                                .biomes
-                                .palette
-                                .iter()
-                                .map(|entry| biome_registry.id(entry))
-                                .collect(),
-                            storage: BitStorage::from_data(4 * 4 * 4, data),
+        let Ok(persistence) = levels_access.get(indexed_chunk.parent) else {
+            continue;
+        };
+
+        let region_storage = persistence.region_storage.clone();
+        let chunk_decoder = chunk_decoder.clone();
+        let chunk_position = indexed_chunk.index;
+        commands
+            .entity(chunk)
+            .insert(Loading(task_pool.spawn(async move {
+                let Some(region_chunk_data) =
+                    region_storage.lock().unwrap().read(chunk_position)?
+                else {
+                    return Ok(None);
+                };
+                chunk_decoder.decode(&region_chunk_data).map(Some)
+            })));
+    }
+}
+
+/// Inserts the data of loaded chunks, but not more than the budget allows per
+/// tick
+fn finish_loading_chunks(
+    mut commands: Commands,
+    chunk_load_budget: Res<ChunkLoadBudget>,
+    mut persistence_errors: EventWriter<PersistenceError>,
+
+    levels_access: Query<&level::AgeAndTime>,
+    mut for_chunks: Query<(Entity, &ParentWithIndex<IVec2>, &mut Loading)>,
+) {
+    let mut chunk_load_budget = chunk_load_budget.0;
+    for (chunk, indexed_chunk, mut loading) in for_chunks.iter_mut() {
+        if chunk_load_budget == 0 {
+            break;
+        }
+
+        if let Some(chunk_data) = (&mut loading.0).now_or_never() {
+            chunk_load_budget -= 1;
+
+            let mut chunk_commands = commands.entity(chunk);
+            chunk_commands.remove::<Loading>();
+            match chunk_data {
+                Ok(Some(decoded_chunk)) => {
+                    let chunk_data = decoded_chunk.data;
+                    let chunk_origin = IVec3::new(
+                        indexed_chunk.index.x * 16,
+                        -(chunk_data.y_offset as i32) * 16,
+                        indexed_chunk.index.y * 16,
+                    );
+                    let chunk_size = IVec3::new(16, chunk_data.sections.len() as i32 * 16, 16);
+                    for (position, block_entity_data) in decoded_chunk.block_entities {
+                        let local_position = position - chunk_origin;
+                        if local_position.cmplt(IVec3::ZERO).any()
+                            || local_position.cmpge(chunk_size).any()
+                        {
+                            warn!(
+                                "Block entity ({:?}) at {} is outside of chunk {} of {:?}",
+                                block_entity_data.type_,
+                                position,
+                                indexed_chunk.index,
+                                indexed_chunk.parent
+                            );
+                            continue;
                         }
-                        .fix()
-                    } else {
-                        PalettedContainer::Single(
-                            biome_registry.id(savegame_chunk_section
-                                .biomes
-                                .palette
-                                .first()
-                                .unwrap()),
-                        )
+
+                        let block = chunk_data.get(
+                            local_position.x as u8,
+                            local_position.y as u16,
+                            local_position.z as u8,
+                        );
+                        let block_entity = chunk_commands
+                            .commands()
+                            .spawn((
+                                tesseract_base::block::Instance {
+                                    block: Entity::from_raw(block),
+                                },
+                                block_entity_data,
+                            ))
+                            .id();
+                        chunk_commands.set_indexed_child(position, Some(block_entity));
+                    }
+
+                    let age = levels_access
+                        .get(indexed_chunk.parent)
+                        .map_or(0, |level_age_and_time| level_age_and_time.age);
+                    let mut scheduled_ticks = level::chunk::ScheduledTicks::default();
+                    for mut scheduled_tick in decoded_chunk.scheduled_ticks {
+                        scheduled_tick.due += age;
+                        scheduled_ticks.schedule(scheduled_tick);
+                    }
+
+                    chunk_commands.insert((chunk_data, scheduled_ticks, JustLoaded));
+                    if let Some(chunk_heightmaps) = decoded_chunk.heightmaps {
+                        chunk_commands.insert(chunk_heightmaps);
+                    }
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    error!(
+                        "Failed to load chunk {} of {:?}, quarantining it: {}",
+                        indexed_chunk.index, indexed_chunk.parent, error
+                    );
+                    chunk_commands.insert(Quarantined);
+                    persistence_errors.send(PersistenceError::Chunk {
+                        level: indexed_chunk.parent,
+                        position: indexed_chunk.index,
+                        error,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Marks chunks whose savegame data is corrupt, they are generated like new
+/// chunks but never saved, so the savegame data is kept for recovery (part of
+/// Chunk)
+#[derive(Component)]
+pub struct Quarantined;
+
+/// Marks chunks which have block changes as unsaved (part of Chunk)
+#[derive(Component)]
+pub(crate) struct Unsaved;
+
+/// Marks chunks which have been loaded in this tick, their block entities are
+/// not changed but added (part of Chunk)
+#[derive(Component)]
+struct JustLoaded;
+
+/// Marks chunks with block or block entity changes since they have been loaded
+/// as unsaved
+#[allow(clippy::type_complexity)]
+fn mark_unsaved_chunks(
+    mut commands: Commands,
+
+    chunk_access: Query<Option<&JustLoaded>, Without<Unsaved>>,
+    for_chunks: Query<
+        (Entity, &level::chunk::Data),
+        (Changed<level::chunk::Data>, Without<Unsaved>),
+    >,
+    for_block_entities: Query<
+        (&ParentWithIndex<IVec3>, Ref<block::EntityData>),
+        Changed<block::EntityData>,
+    >,
+    for_just_loaded_chunks: Query<Entity, With<JustLoaded>>,
+) {
+    // loaded chunks have no block state changes, therefore only changes made after
+    // loading are counted
+    for (chunk, chunk_data) in for_chunks.iter() {
+        if chunk_data
+            .sections
+            .iter()
+            .any(|section| !section.block_state_changes.is_empty())
+        {
+            commands.entity(chunk).insert(Unsaved);
+        }
+    }
+
+    for (indexed_block_entity, block_entity_data) in for_block_entities.iter() {
+        // skip block entities which have just been loaded with their chunk
+        if let Ok(just_loaded) = chunk_access.get(indexed_block_entity.parent) {
+            if !(just_loaded.is_some() && block_entity_data.is_added()) {
+                commands.entity(indexed_block_entity.parent).insert(Unsaved);
+            }
+        }
+    }
+
+    for chunk in for_just_loaded_chunks.iter() {
+        commands.entity(chunk).remove::<JustLoaded>();
+    }
+}
+
+struct SaveCache {
+    blocks: HashMap<u32, tesseract_java_savegame::BlockState>,
+}
+
+impl FromWorld for SaveCache {
+    fn from_world(world: &mut World) -> Self {
+        let mut for_blocks =
+            world.query_filtered::<(Entity, &block::Name), With<tesseract_base::block::Base>>();
+
+        let mut blocks = HashMap::new();
+        for (block_id, block_name) in for_blocks.iter(world) {
+            blocks.insert(
+                block_id.index(),
+                tesseract_java_savegame::BlockState {
+                    name: block_name.name.clone(),
+                    properties: block_name.properties.clone(),
+                },
+            );
+        }
+        Self { blocks }
+    }
+}
+
+/// Saves unsaved chunks periodically, and releases chunks which are neither
+/// subscribed nor contain actors after saving them
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn save_and_unload_chunks(
+    mut commands: Commands,
+    save_cache: Local<SaveCache>,
+    biome_registry: Res<registry::DataRegistry<Biome>>,
+    autosave: Res<Autosave>,
+    mut persistence_errors: EventWriter<PersistenceError>,
+
+    levels_access: Query<(&Persistence, &level::AgeAndTime)>,
+    block_entities_access: Query<&block::EntityData>,
+    for_chunks: Query<(
+        Entity,
+        &ParentWithIndex<IVec2>,
+        &replication::Replication,
+        Option<&Children>,
+        Option<&level::chunk::Data>,
+        Option<&level::chunk::Heightmaps>,
+        Option<&IndexedChildren<IVec3>>,
+        Option<&level::chunk::ScheduledTicks>,
+        Option<&Unsaved>,
+        Option<&Quarantined>,
+    )>,
+) {
+    for (
+        chunk,
+        indexed_chunk,
+        replication,
+        actors,
+        chunk_data,
+        chunk_heightmaps,
+        block_entities,
+        scheduled_ticks,
+        unsaved,
+        quarantined,
+    ) in for_chunks.iter()
+    {
+        // only levels with persistence are saved and unloaded
+        let Ok((persistence, level_age_and_time)) = levels_access.get(indexed_chunk.parent) else {
+            continue;
+        };
+
+        let unload =
+            replication.subscriber.is_empty() && actors.map_or(true, |actors| actors.is_empty());
+        if unsaved.is_some() && (unload || autosave.0.just_finished()) {
+            if let (Some(chunk_data), None) = (chunk_data, quarantined) {
+                let savegame_chunk = save_chunk(
+                    &save_cache,
+                    &biome_registry,
+                    indexed_chunk.index,
+                    chunk_data,
+                    chunk_heightmaps,
+                    block_entities.map_or_else(Vec::new, |block_entities| {
+                        block_entities
+                            .0
+                            .iter()
+                            .filter_map(|(position, &block_entity)| {
+                                let block_entity_data =
+                                    block_entities_access.get(block_entity).ok()?;
+                                Some(tesseract_java_savegame::chunk::BlockEntity {
+                                    id: block_entity_data.type_.clone(),
+                                    x: position.x,
+                                    y: position.y,
+                                    z: position.z,
+                                    data: block_entity_data.data.clone(),
+                                })
+                            })
+                            .collect()
+                    }),
+                    scheduled_ticks,
+                    level_age_and_time.age,
+                );
+                if let Err(error) = savegame_chunk
+                    .and_then(|savegame_chunk| Ok(tesseract_nbt::ser::to_vec(&savegame_chunk)?))
+                    .and_then(|region_chunk_data| {
+                        persistence
+                            .region_storage
+                            .lock()
+                            .unwrap()
+                            .write(indexed_chunk.index, &region_chunk_data)
+                    })
+                {
+                    error!(
+                        "Failed to save chunk {} of {:?}: {}",
+                        indexed_chunk.index, indexed_chunk.parent, error
+                    );
+                    persistence_errors.send(PersistenceError::Chunk {
+                        level: indexed_chunk.parent,
+                        position: indexed_chunk.index,
+                        error,
+                    });
+                }
+            }
+
+            commands.entity(chunk).remove::<Unsaved>();
+        }
+
+        if unload {
+            // block entities are not despawned together with the chunk
+            if let Some(block_entities) = block_entities {
+                for &block_entity in block_entities.0.values() {
+                    commands.entity(block_entity).despawn();
+                }
+            }
+            commands
+                .entity(indexed_chunk.parent)
+                .set_indexed_child(indexed_chunk.index, None);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_chunk(
+    save_cache: &SaveCache,
+    biome_registry: &registry::DataRegistry<Biome>,
+    position: IVec2,
+    chunk_data: &level::chunk::Data,
+    chunk_heightmaps: Option<&level::chunk::Heightmaps>,
+    block_entities: Vec<tesseract_java_savegame::chunk::BlockEntity>,
+    scheduled_ticks: Option<&level::chunk::ScheduledTicks>,
+    age: u64,
+) -> tesseract_java_savegame::Result<tesseract_java_savegame::chunk::Chunk> {
+    let height = chunk_data.sections.len() as u32 * 16;
+    let (block_ticks, fluid_ticks) = save_scheduled_ticks(
+        save_cache,
+        position,
+        chunk_data.y_offset,
+        scheduled_ticks,
+        age,
+    );
+
+    let sections = chunk_data
+        .sections
+        .iter()
+        .enumerate()
+        .map(|(section_y, section)| {
+            Ok(tesseract_java_savegame::chunk::ChunkSection {
+                y: (section_y as i32 - chunk_data.y_offset as i32) as i8,
+                // blocks without a name can't be saved
+                block_states: save_paletted_container(
+                    (0..16 * 16 * 16).map(|index| section.block_states.get(index)),
+                    4,
+                    |block| {
+                        save_cache
+                            .blocks
+                            .get(&block)
+                            .cloned()
+                            .ok_or(tesseract_java_savegame::Error::UnknownBlock(block))
                     },
-                    block_state_changes: Default::default(),
-                })
-                .collect::<Vec<_>>();
+                )?,
+                biomes: save_paletted_container(
+                    (0..4 * 4 * 4).map(|index| section.biomes.get(index)),
+                    1,
+                    |biome| Ok(biome_registry.name(biome).to_string()),
+                )?,
+            })
+        })
+        .collect::<tesseract_java_savegame::Result<Vec<_>>>()?;
 
-            commands.entity(chunk).insert(level::chunk::Data {
-                sections,
-                y_offset: 4,
-            });
+    Ok(tesseract_java_savegame::chunk::Chunk {
+        version: tesseract_java_savegame::DATA_VERSION,
+        x: position.x,
+        y: -(chunk_data.y_offset as i32),
+        z: position.y,
+        status: Some("minecraft:full".to_string()),
+        sections,
+        heightmaps: tesseract_java_savegame::chunk::Heightmaps {
+            motion_blocking: chunk_heightmaps
+                .map(|chunk_heightmaps| chunk_heightmaps.motion_blocking.to_data(height)),
+            world_surface: chunk_heightmaps
+                .map(|chunk_heightmaps| chunk_heightmaps.world_surface.to_data(height)),
+        },
+        block_entities,
+        block_ticks,
+        fluid_ticks,
+    })
+}
+
+/// Splits the scheduled ticks into block and fluid ticks, the delay is relative
+/// to the age of the level
+fn save_scheduled_ticks(
+    save_cache: &SaveCache,
+    position: IVec2,
+    y_offset: u8,
+    scheduled_ticks: Option<&level::chunk::ScheduledTicks>,
+    age: u64,
+) -> (
+    Vec<tesseract_java_savegame::chunk::ScheduledTick>,
+    Vec<tesseract_java_savegame::chunk::ScheduledTick>,
+) {
+    let mut block_ticks = vec![];
+    let mut fluid_ticks = vec![];
+    for scheduled_tick in scheduled_ticks
+        .into_iter()
+        .flat_map(|scheduled_ticks| scheduled_ticks.iter())
+    {
+        // the block which has scheduled the tick might not exist anymore
+        let Some(block_state) = save_cache.blocks.get(&scheduled_tick.block) else {
+            warn!(
+                "Scheduled tick of unknown block ({}) is not saved",
+                scheduled_tick.block
+            );
+            continue;
+        };
+        let savegame_scheduled_tick = tesseract_java_savegame::chunk::ScheduledTick {
+            id: block_state.name.clone(),
+            x: position.x * 16 + scheduled_tick.position.x() as i32,
+            y: scheduled_tick.position.y() as i32 - y_offset as i32 * 16,
+            z: position.y * 16 + scheduled_tick.position.z() as i32,
+            delay: scheduled_tick.due.saturating_sub(age) as i32,
+            priority: scheduled_tick.priority as i32,
+        };
+        match scheduled_tick.kind {
+            level::chunk::TickKind::Block => block_ticks.push(savegame_scheduled_tick),
+            level::chunk::TickKind::Fluid => fluid_ticks.push(savegame_scheduled_tick),
+        }
+    }
+    (block_ticks, fluid_ticks)
+}
+
+/// Packs the values into a palette and a storage, which uses the least amount
+/// of bits (but at least min_bits), like Minecraft does
+fn save_paletted_container<T>(
+    values: impl Iterator<Item = u32>,
+    min_bits: u32,
+    value_to_palette_entry: impl Fn(u32) -> tesseract_java_savegame::Result<T>,
+) -> tesseract_java_savegame::Result<tesseract_java_savegame::PalettedContainer<T>> {
+    let mut palette = vec![];
+    let mut palette_index_by_value = HashMap::new();
+    let palette_indices = values
+        .map(|value| {
+            *palette_index_by_value.entry(value).or_insert_with(|| {
+                palette.push(value);
+                palette.len() as u32 - 1
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let data = if palette.len() > 1 {
+        let bits = (u32::BITS - (palette.len() as u32 - 1).leading_zeros()).max(min_bits);
+        let mut storage = BitStorage::new(palette_indices.len() as u32, bits);
+        for (index, palette_index) in palette_indices.into_iter().enumerate() {
+            storage.set(index as u32, palette_index);
+        }
+        Some(storage.data().to_vec())
+    } else {
+        None
+    };
+
+    Ok(tesseract_java_savegame::PalettedContainer {
+        palette: palette
+            .into_iter()
+            .map(value_to_palette_entry)
+            .collect::<tesseract_java_savegame::Result<_>>()?,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AIR: u32 = 0;
+    const STONE: u32 = 1;
+    const WATER: u32 = 2;
+    const WATER_FLOWING: u32 = 3;
+
+    fn block_state(name: &str, properties: &[(&str, &str)]) -> tesseract_java_savegame::BlockState {
+        tesseract_java_savegame::BlockState {
+            name: name.to_owned(),
+            properties: properties
+                .iter()
+                .map(|&(key, value)| (key.to_owned(), value.to_owned()))
+                .collect(),
+        }
+    }
+
+    fn caches() -> (SaveCache, ChunkDecoder) {
+        let block_states = [
+            (AIR, block_state("minecraft:air", &[])),
+            (STONE, block_state("minecraft:stone", &[])),
+            (WATER, block_state("minecraft:water", &[("level", "0")])),
+            (
+                WATER_FLOWING,
+                block_state("minecraft:water", &[("level", "1")]),
+            ),
+        ];
+
+        let mut blocks = HashMap::<_, Vec<_>>::new();
+        let mut default_blocks = HashMap::new();
+        for (block, block_state) in block_states.iter() {
+            blocks
+                .entry(block_state.name.clone())
+                .or_default()
+                .push((block_state.properties.clone(), *block));
+            default_blocks
+                .entry(block_state.name.clone())
+                .or_insert(*block);
+        }
+
+        (
+            SaveCache {
+                blocks: block_states.into_iter().collect(),
+            },
+            ChunkDecoder {
+                render_cache: Arc::new(RenderCache {
+                    blocks,
+                    default_blocks,
+                    fallback_block: None,
+                    unknown_block_states: Default::default(),
+                }),
+                biomes: Arc::new(HashMap::new()),
+            },
+        )
+    }
+
+    #[test]
+    fn scheduled_ticks_round_trip() {
+        let (save_cache, chunk_decoder) = caches();
+        let mut data = level::chunk::Data::new(24, 4, AIR, 0);
+        data.set(1, 70, 2, WATER_FLOWING);
+        data.set(3, 0, 4, STONE);
+
+        let age = 100;
+        let mut scheduled_ticks = level::chunk::ScheduledTicks::default();
+        for (position, kind, block, due, priority) in [
+            // the flowing water is still there
+            ((1, 70, 2), level::chunk::TickKind::Fluid, WATER, 105, 0),
+            // the stone has been replaced, and is resolved to the default block
+            ((1, 70, 2), level::chunk::TickKind::Block, STONE, 102, -1),
+            // overdue ticks are saved as due in this tick
+            ((3, 0, 4), level::chunk::TickKind::Block, STONE, 90, 2),
+            // unknown blocks are not saved
+            ((5, 5, 5), level::chunk::TickKind::Block, 99, 101, 0),
+        ] {
+            assert!(scheduled_ticks.schedule(level::chunk::ScheduledTick {
+                position: level::chunk::BlockPosition::new(position.0, position.1, position.2),
+                kind,
+                block,
+                due,
+                priority,
+            }));
         }
+
+        let (block_ticks, fluid_ticks) = save_scheduled_ticks(
+            &save_cache,
+            IVec2::new(1, -2),
+            data.y_offset,
+            Some(&scheduled_ticks),
+            age,
+        );
+        assert_eq!(block_ticks.len(), 2);
+        assert_eq!(fluid_ticks.len(), 1);
+        assert_eq!(fluid_ticks[0].id, "minecraft:water");
+        assert_eq!(
+            (fluid_ticks[0].x, fluid_ticks[0].y, fluid_ticks[0].z),
+            (17, 6, -30)
+        );
+        assert_eq!(fluid_ticks[0].delay, 5);
+
+        let block_ticks = chunk_decoder
+            .decode_scheduled_ticks(block_ticks, level::chunk::TickKind::Block, &data)
+            .collect::<Vec<_>>();
+        let fluid_ticks = chunk_decoder
+            .decode_scheduled_ticks(fluid_ticks, level::chunk::TickKind::Fluid, &data)
+            .collect::<Vec<_>>();
+
+        // ticks are saved in the order they are going to be run
+        assert_eq!(
+            block_ticks[0].position,
+            level::chunk::BlockPosition::new(3, 0, 4)
+        );
+        assert_eq!(block_ticks[0].block, STONE);
+        assert_eq!((block_ticks[0].due, block_ticks[0].priority), (0, 2));
+        assert_eq!(
+            block_ticks[1].position,
+            level::chunk::BlockPosition::new(1, 70, 2)
+        );
+        assert_eq!(block_ticks[1].block, STONE);
+        assert_eq!((block_ticks[1].due, block_ticks[1].priority), (2, -1));
+
+        assert_eq!(fluid_ticks.len(), 1);
+        assert_eq!(fluid_ticks[0].kind, level::chunk::TickKind::Fluid);
+        assert_eq!(
+            fluid_ticks[0].position,
+            level::chunk::BlockPosition::new(1, 70, 2)
+        );
+        assert_eq!(fluid_ticks[0].block, WATER_FLOWING);
+        assert_eq!((fluid_ticks[0].due, fluid_ticks[0].priority), (5, 0));
     }
 }
@@ -4,11 +4,14 @@ use std::{
     path::Path,
 };
 
-use bevy::prelude::*;
+use bevy::{math::DVec3, prelude::*};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
+use tesseract_base::shape::{Aabb, VoxelShape};
 use tesseract_java_protocol::types::{Biome, DamageType, DimensionType, Registry, RegistryEntry};
 
+use crate::block;
+
 /// Needed for Minecraft: Java Edition persistence & replication
 #[derive(Default)]
 pub struct RegistryPlugin;
@@ -16,6 +19,9 @@ pub struct RegistryPlugin;
 impl Plugin for RegistryPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(BlocksReport::new("generated/reports/blocks.json"))
+            .insert_resource(BlockCollisionShapesReport::new(
+                "generated/reports/block_collision_shapes.json",
+            ))
             .insert_resource(RegistriesReport::new("generated/reports/registries.json"))
             .insert_resource(DataRegistry::<DimensionType>::new(
                 "generated/data/dimension_type",
@@ -28,7 +34,8 @@ impl Plugin for RegistryPlugin {
             .insert_resource(DataRegistry::<DamageType>::new(
                 "generated/data/damage_type",
                 "minecraft:damage_type",
-            ));
+            ))
+            .add_systems(Startup, insert_collision_shapes);
     }
 }
 
@@ -93,6 +100,145 @@ pub(crate) struct BlockStateReport {
     pub(crate) default: bool,
 }
 
+/// Collision shapes of block states in the format of minecraft-data
+/// (blockCollisionShapes.json), which is optional, as the shapes of common
+/// blocks are also derived from their name and properties
+#[derive(Resource, Serialize, Deserialize, Default)]
+pub(crate) struct BlockCollisionShapesReport {
+    blocks: HashMap<String, BlockCollisionShapeIdsReport>,
+    shapes: HashMap<String, Vec<[f64; 6]>>,
+}
+
+impl BlockCollisionShapesReport {
+    fn new<P: AsRef<Path>>(path: P) -> Self {
+        let Ok(file) = File::open(path) else {
+            warn!("No block collision shapes report exists, only derived shapes are used");
+            return Self::default();
+        };
+        serde_json::from_reader(file).unwrap()
+    }
+
+    /// Returns the shape of the block state, by its index in the states of the
+    /// block
+    fn shape(&self, name: &str, state_index: usize) -> Option<VoxelShape> {
+        let shape_id = match self
+            .blocks
+            .get(name.strip_prefix("minecraft:").unwrap_or(name))?
+        {
+            BlockCollisionShapeIdsReport::Block(shape_id) => *shape_id,
+            BlockCollisionShapeIdsReport::States(shape_ids) => *shape_ids.get(state_index)?,
+        };
+        Some(VoxelShape(
+            self.shapes
+                .get(&shape_id.to_string())?
+                .iter()
+                .map(|&[min_x, min_y, min_z, max_x, max_y, max_z]| {
+                    Aabb::new(
+                        DVec3::new(min_x, min_y, min_z),
+                        DVec3::new(max_x, max_y, max_z),
+                    )
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Shape for all states of the block, or a shape for each state
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum BlockCollisionShapeIdsReport {
+    Block(u32),
+    States(Vec<u32>),
+}
+
+/// Inserts the collision shapes of the report into all blocks which don't
+/// define their own
+#[allow(clippy::type_complexity)]
+fn insert_collision_shapes(
+    mut commands: Commands,
+    blocks_report: Res<BlocksReport>,
+    block_collision_shapes_report: Res<BlockCollisionShapesReport>,
+
+    for_blocks: Query<
+        (Entity, &block::Name),
+        (
+            With<tesseract_base::block::Base>,
+            Without<tesseract_base::block::Collision>,
+        ),
+    >,
+) {
+    for (block, block_name) in for_blocks.iter() {
+        let Some(block_report) = blocks_report.0.get(&block_name.name) else {
+            continue;
+        };
+        let Some(block_state_report) = (if block_name.properties.is_empty() {
+            block_report
+                .states
+                .iter()
+                .find(|block_state_report| block_state_report.default)
+        } else {
+            block_report
+                .states
+                .iter()
+                .find(|block_state_report| block_state_report.properties == block_name.properties)
+        }) else {
+            continue;
+        };
+
+        // states are numbered consecutively per block
+        let first_block_state_id = block_report
+            .states
+            .iter()
+            .map(|block_state_report| block_state_report.id)
+            .min()
+            .unwrap_or(block_state_report.id);
+        if let Some(shape) = block_collision_shapes_report
+            .shape(
+                &block_name.name,
+                (block_state_report.id - first_block_state_id) as usize,
+            )
+            .or_else(|| derive_collision_shape(&block_name.name, &block_state_report.properties))
+        {
+            commands
+                .entity(block)
+                .insert(tesseract_base::block::Collision(shape));
+        }
+    }
+}
+
+/// Derives the collision shape of blocks, which are not a full block, from
+/// their name and properties, returns none for all other blocks
+fn derive_collision_shape(name: &str, properties: &BTreeMap<String, String>) -> Option<VoxelShape> {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    let height = |min_y: f64, max_y: f64| {
+        VoxelShape(vec![Aabb::new(
+            DVec3::new(0.0, min_y, 0.0),
+            DVec3::new(1.0, max_y, 1.0),
+        )])
+    };
+    Some(match name {
+        _ if name.ends_with("_slab") => match properties.get("type")?.as_str() {
+            "bottom" => height(0.0, 0.5),
+            "top" => height(0.5, 1.0),
+            _ => VoxelShape::full(),
+        },
+        _ if name.ends_with("_carpet") => height(0.0, 1.0 / 16.0),
+        "snow" => {
+            let layers = properties.get("layers")?.parse::<u8>().ok()?;
+            if layers <= 1 {
+                VoxelShape::empty()
+            } else {
+                height(0.0, (layers - 1) as f64 * 2.0 / 16.0)
+            }
+        }
+        "farmland" | "dirt_path" => height(0.0, 15.0 / 16.0),
+        "soul_sand" | "mud" => height(0.0, 14.0 / 16.0),
+        "enchanting_table" => height(0.0, 12.0 / 16.0),
+        "daylight_detector" => height(0.0, 6.0 / 16.0),
+        _ => return None,
+    })
+}
+
 #[derive(Resource)]
 pub(crate) struct DataRegistry<T> {
     registry: Registry<T>,
@@ -140,4 +286,70 @@ impl<T: DeserializeOwned> DataRegistry<T> {
     pub(crate) fn id(&self, name: &str) -> u32 {
         *self.id_by_name.get(name).unwrap_or(&0)
     }
+
+    pub(crate) fn name(&self, id: u32) -> &str {
+        &self.registry.value[id as usize].name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(properties: &[(&str, &str)]) -> BTreeMap<String, String> {
+        properties
+            .iter()
+            .map(|&(key, value)| (key.to_owned(), value.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn derive_slab_collision_shape() {
+        let half = |min_y, max_y| {
+            VoxelShape(vec![Aabb::new(
+                DVec3::new(0.0, min_y, 0.0),
+                DVec3::new(1.0, max_y, 1.0),
+            )])
+        };
+        assert_eq!(
+            derive_collision_shape(
+                "minecraft:oak_slab",
+                &properties(&[("type", "bottom"), ("waterlogged", "false")])
+            ),
+            Some(half(0.0, 0.5))
+        );
+        assert_eq!(
+            derive_collision_shape(
+                "minecraft:oak_slab",
+                &properties(&[("type", "top"), ("waterlogged", "false")])
+            ),
+            Some(half(0.5, 1.0))
+        );
+        assert_eq!(
+            derive_collision_shape(
+                "minecraft:oak_slab",
+                &properties(&[("type", "double"), ("waterlogged", "false")])
+            ),
+            Some(VoxelShape::full())
+        );
+    }
+
+    #[test]
+    fn derive_snow_collision_shape() {
+        assert_eq!(
+            derive_collision_shape("minecraft:snow", &properties(&[("layers", "1")])),
+            Some(VoxelShape::empty())
+        );
+        assert_eq!(
+            derive_collision_shape("minecraft:snow", &properties(&[("layers", "8")])),
+            Some(VoxelShape(vec![Aabb::new(
+                DVec3::ZERO,
+                DVec3::new(1.0, 14.0 / 16.0, 1.0)
+            )]))
+        );
+        assert_eq!(
+            derive_collision_shape("minecraft:stone", &BTreeMap::new()),
+            None
+        );
+    }
 }
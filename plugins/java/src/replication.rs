@@ -31,8 +31,9 @@ use tesseract_java_protocol::{
     codec::{Codec, Compression},
     packet::{c2s, c2s::game::PlayerActionPacketAction, s2c},
     types::{
-        Biome, Component as ChatComponent, DamageType, DimensionType, GameType, Intention, Json,
-        Nbt, PalettedContainer, Registries, Registry, Status, StatusPlayers, StatusVersion, VarI32,
+        Biome, Component as ChatComponent, DamageType, DimensionType, GameType, Hand, Intention,
+        Json, Nbt, PalettedContainer, Registries, Registry, Status, StatusPlayers, StatusVersion,
+        VarI32,
     },
     Decode, Encode,
 };
@@ -109,6 +110,16 @@ impl Plugin for ReplicationPlugin {
                     cleanup_chunks,
                     replicate_chunks_late,
                     replicate_chunks_delta,
+                    replicate_light
+                        .after(subscribe_and_replicate_chunks)
+                        .after(replicate_chunks_late)
+                        .after(replicate_chunks_delta),
+                    replicate_block_entities
+                        .after(replicate_chunks_late)
+                        .after(replicate_chunks_delta),
+                    replicate_block_changed_acks
+                        .after(replicate_chunks_delta)
+                        .after(replicate_block_entities),
                     replicate_actors,
                     replicate_actors_delta,
                 ),
@@ -127,6 +138,11 @@ pub struct Connection {
     keep_alive: Instant,
     keep_alive_id: Option<i64>,
     latency: u32,
+
+    selected_slot: u8,
+    /// Highest sequence of block changes, which is acknowledged after the
+    /// resulting block updates have been sent
+    block_changed_ack: Option<i32>,
 }
 
 impl Connection {
@@ -144,6 +160,15 @@ impl Connection {
     pub fn latency(&self) -> u32 {
         self.latency
     }
+
+    fn acknowledge_block_changes(&mut self, sequence: i32) {
+        self.block_changed_ack = Some(
+            self.block_changed_ack
+                .map_or(sequence, |block_changed_ack| {
+                    block_changed_ack.max(sequence)
+                }),
+        );
+    }
 }
 
 async fn handle_new_connection(
@@ -271,6 +296,8 @@ async fn handle_new_connection(
                         keep_alive: Instant::now(),
                         keep_alive_id: None,
                         latency: 0,
+                        selected_slot: 0,
+                        block_changed_ack: None,
                     });
 
                     tokio::spawn(async move {
@@ -324,8 +351,30 @@ fn spawn_player(mut commands: Commands, mut new_connection_rx: ResMut<NewConnect
     }
 }
 
+/// Items by their protocol id
+struct ItemCache {
+    items: HashMap<u32, Entity>,
+}
+
+impl FromWorld for ItemCache {
+    fn from_world(world: &mut World) -> Self {
+        let mut for_items = world.query_filtered::<(Entity, &block::Name), With<item::Base>>();
+        let registries_report = world.resource::<registry::RegistriesReport>();
+
+        let mut items = HashMap::new();
+        for (item, item_name) in for_items.iter(world) {
+            items.insert(
+                registries_report.id("minecraft:item", &item_name.name),
+                item,
+            );
+        }
+        Self { items }
+    }
+}
+
 fn update_players(
     mut commands: Commands,
+    item_cache: Local<ItemCache>,
 
     mut for_players: Query<(
         Entity,
@@ -422,10 +471,11 @@ fn update_players(
                         sequence,
                         ..
                     } => {
-                        connection.send(&s2c::GamePacket::BlockChangedAck { sequence });
+                        connection.acknowledge_block_changes(sequence);
 
                         match action {
-                            PlayerActionPacketAction::StartDestroyBlock => {
+                            PlayerActionPacketAction::StartDestroyBlock
+                            | PlayerActionPacketAction::StopDestroyBlock => {
                                 *interaction = actor::player::Interaction::BreakBlock(pos);
                             }
                             PlayerActionPacketAction::AbortDestroyBlock => {
@@ -448,30 +498,51 @@ fn update_players(
                             _ => todo!(),
                         };
 
-                        let item_instance = item_stack.map(|item_stack| {
-                            commands
-                                .spawn(item::Instance {
-                                    item: Entity::from_raw(0),
-                                    count: item_stack.count as u8,
-                                })
-                                .id()
+                        let item_instance = item_stack.and_then(|item_stack| {
+                            let Some(&item) = item_cache.items.get(&(item_stack.item as u32))
+                            else {
+                                warn!("Item ({}) does not exist", item_stack.item);
+                                return None;
+                            };
+
+                            Some(
+                                commands
+                                    .spawn(item::Instance {
+                                        item,
+                                        count: item_stack.count as u8,
+                                    })
+                                    .id(),
+                            )
                         });
                         commands
                             .entity(player)
                             .set_indexed_child(slot, item_instance);
                     }
+                    c2s::GamePacket::SetCarriedItem { slot } => {
+                        if (0..9).contains(&slot) {
+                            connection.selected_slot = slot as u8;
+                        }
+                    }
                     c2s::GamePacket::UseItemOn {
+                        hand,
                         block_pos,
                         direction,
                         sequence,
                         ..
                     } => {
-                        connection.send(&s2c::GamePacket::BlockChangedAck { sequence });
-
-                        *interaction = actor::player::Interaction::UseItemOn(block_pos, direction);
+                        connection.acknowledge_block_changes(sequence);
+
+                        *interaction = actor::player::Interaction::UseItemOn(
+                            block_pos,
+                            direction,
+                            match hand {
+                                Hand::MainHand => item::Slot::Hotbar(connection.selected_slot),
+                                Hand::OffHand => item::Slot::Offhand,
+                            },
+                        );
                     }
                     c2s::GamePacket::UseItem { sequence, .. } => {
-                        connection.send(&s2c::GamePacket::BlockChangedAck { sequence });
+                        connection.acknowledge_block_changes(sequence);
                     }
                     _ => {}
                 }
@@ -733,10 +804,18 @@ fn cleanup_chunks(
 #[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn subscribe_and_replicate_chunks(
     mut commands: Commands,
+    registries_report: Res<registry::RegistriesReport>,
 
     level_access: Query<&IndexedChildren<IVec2>>,
     chunk_access: Query<&ParentWithIndex<IVec2>>,
-    mut subscription_chunk_access: Query<(Option<&RenderedChunk>, &mut Replication)>,
+    mut subscription_chunk_access: Query<(
+        Option<&RenderedChunk>,
+        Option<&level::light::Data>,
+        Option<&level::chunk::Heightmaps>,
+        Option<&IndexedChildren<IVec3>>,
+        &mut Replication,
+    )>,
+    block_entity_access: Query<&block::EntityData>,
     actor_access: Query<(Entity, &actor::Base, &actor::Position, &actor::Rotation)>,
 
     mut for_players: Query<
@@ -770,7 +849,8 @@ fn subscribe_and_replicate_chunks(
                 if let Some(&chunk) = indexed_chunks.0.get(&chunk_position) {
                     trace!("Release chunk: {:?}", chunk_position);
 
-                    let (_, mut replication) = subscription_chunk_access.get_mut(chunk).unwrap();
+                    let (_, _, _, _, mut replication) =
+                        subscription_chunk_access.get_mut(chunk).unwrap();
                     replication.subscriber.remove(&player);
 
                     // connection: remove chunk and actors, cause: unsubscribe
@@ -800,8 +880,13 @@ fn subscribe_and_replicate_chunks(
                 })
             {
                 if let Some(&chunk) = indexed_chunks.0.get(&chunk_position) {
-                    if let Ok((rendered_chunk, mut replication)) =
-                        subscription_chunk_access.get_mut(chunk)
+                    if let Ok((
+                        rendered_chunk,
+                        light_data,
+                        heightmaps,
+                        block_entities,
+                        mut replication,
+                    )) = subscription_chunk_access.get_mut(chunk)
                     {
                         trace!("Acquire chunk: {:?}", chunk_position);
 
@@ -809,7 +894,17 @@ fn subscribe_and_replicate_chunks(
 
                         if let Some(rendered_chunk) = rendered_chunk {
                             // connection: add chunk and actors, cause: subscribe
-                            connection.send(&add_chunk_packet(chunk_position, rendered_chunk));
+                            connection.send(&add_chunk_packet(
+                                chunk_position,
+                                rendered_chunk,
+                                light_data,
+                                heightmaps,
+                                block_entities_data(
+                                    &registries_report,
+                                    &block_entity_access,
+                                    block_entities,
+                                ),
+                            ));
                             for (actor, actor_base, actor_position, actor_rotation) in
                                 actor_access.iter_many(&replication.replicated)
                             {
@@ -836,6 +931,9 @@ fn subscribe_and_replicate_chunks(
                         .spawn(level::chunk::ChunkBundle {
                             base: level::chunk::Base,
                             update_queue: Default::default(),
+                            neighbor_updates: Default::default(),
+                            scheduled_ticks: Default::default(),
+                            random_ticks: Default::default(),
                             replication: Replication {
                                 subscriber: HashSet::from([player]),
                                 replicated: vec![],
@@ -854,16 +952,34 @@ fn subscribe_and_replicate_chunks(
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn replicate_chunks_late(
+    registries_report: Res<registry::RegistriesReport>,
     player_access: Query<&Connection>,
+    block_entity_access: Query<&block::EntityData>,
 
     for_chunks: Query<
-        (&ParentWithIndex<IVec2>, &RenderedChunk, &Replication),
+        (
+            &ParentWithIndex<IVec2>,
+            &RenderedChunk,
+            Option<&level::light::Data>,
+            Option<&level::chunk::Heightmaps>,
+            Option<&IndexedChildren<IVec3>>,
+            &Replication,
+        ),
         Added<RenderedChunk>,
     >,
 ) {
-    for (indexed_chunk, rendered_chunk, replication) in for_chunks.iter() {
-        let add_chunk_packet = add_chunk_packet(indexed_chunk.index, rendered_chunk);
+    for (indexed_chunk, rendered_chunk, light_data, heightmaps, block_entities, replication) in
+        for_chunks.iter()
+    {
+        let add_chunk_packet = add_chunk_packet(
+            indexed_chunk.index,
+            rendered_chunk,
+            light_data,
+            heightmaps,
+            block_entities_data(&registries_report, &block_entity_access, block_entities),
+        );
         for &player in &replication.subscriber {
             // connection: add chunk, cause: subscribe (late)
             if let Ok(connection) = player_access.get(player) {
@@ -919,7 +1035,7 @@ fn replicate_chunks_delta(
                                 section_y as i32 - y_offset,
                                 chunk_position.y,
                             ),
-                            suppress_light_updates: true,
+                            suppress_light_updates: false,
                             position_and_states: section
                                 .block_state_changes
                                 .iter()
@@ -956,6 +1072,94 @@ fn replicate_chunks_delta(
     }
 }
 
+fn replicate_light(
+    player_access: Query<&Connection>,
+
+    mut for_chunks: Query<
+        (
+            &ParentWithIndex<IVec2>,
+            Option<Ref<RenderedChunk>>,
+            &Replication,
+            &mut level::light::Data,
+        ),
+        Changed<level::light::Data>,
+    >,
+) {
+    for (indexed_chunk, rendered_chunk, replication, mut light_data) in for_chunks.iter_mut() {
+        if light_data.section_changes.is_empty() {
+            continue;
+        }
+
+        // not replicated yet, or already part of the chunk packet
+        if rendered_chunk.map_or(true, |rendered_chunk| rendered_chunk.is_added()) {
+            light_data.section_changes.clear();
+            continue;
+        }
+
+        let light_update_packet = s2c::GamePacket::LightUpdate {
+            x: indexed_chunk.index.x,
+            z: indexed_chunk.index.y,
+            light_data: light_update_packet_data(
+                &light_data,
+                light_data.section_changes.iter().copied(),
+            ),
+        };
+        light_data.section_changes.clear();
+
+        for &player in &replication.subscriber {
+            // connection: update light, cause: block changes or neighboring chunks
+            if let Ok(connection) = player_access.get(player) {
+                connection.send(&light_update_packet);
+            }
+        }
+    }
+}
+
+fn replicate_block_entities(
+    registries_report: Res<registry::RegistriesReport>,
+    player_access: Query<&Connection>,
+    chunk_access: Query<(Ref<RenderedChunk>, &Replication)>,
+
+    for_block_entities: Query<
+        (&ParentWithIndex<IVec3>, &block::EntityData),
+        Changed<block::EntityData>,
+    >,
+) {
+    for (indexed_block_entity, block_entity_data) in for_block_entities.iter() {
+        let Ok((rendered_chunk, replication)) = chunk_access.get(indexed_block_entity.parent)
+        else {
+            continue;
+        };
+        // already part of the chunk packet
+        if rendered_chunk.is_added() {
+            continue;
+        }
+
+        let block_entity_data_packet = s2c::GamePacket::BlockEntityData {
+            pos: indexed_block_entity.index,
+            type_: registries_report.id("minecraft:block_entity_type", &block_entity_data.type_)
+                as i32,
+            tag: Nbt(block_entity_data.data.clone()),
+        };
+        for &player in &replication.subscriber {
+            // connection: update block entity, cause: block entity changes
+            if let Ok(connection) = player_access.get(player) {
+                connection.send(&block_entity_data_packet);
+            }
+        }
+    }
+}
+
+/// Acknowledges block changes after the resulting block updates have been
+/// sent, as clients revert their predicted blocks when acknowledged
+fn replicate_block_changed_acks(mut for_players: Query<&mut Connection>) {
+    for mut connection in for_players.iter_mut() {
+        if let Some(sequence) = connection.block_changed_ack.take() {
+            connection.send(&s2c::GamePacket::BlockChangedAck { sequence });
+        }
+    }
+}
+
 //=========================================================================== ACTOR REPLICATION ====
 
 fn replicate_actors(
@@ -1047,12 +1251,27 @@ fn replicate_actors_delta(
     player_access: Query<&Connection>,
 
     for_actors: Query<
-        (Entity, &Parent, Ref<actor::Position>, Ref<actor::Rotation>),
-        Or<(Changed<actor::Position>, Changed<actor::Rotation>)>,
+        (
+            Entity,
+            &Parent,
+            Ref<actor::Position>,
+            Ref<actor::Rotation>,
+            Option<Ref<actor::Velocity>>,
+            Option<&actor::OnGround>,
+        ),
+        Or<(
+            Changed<actor::Position>,
+            Changed<actor::Rotation>,
+            Changed<actor::Velocity>,
+        )>,
     >,
 ) {
-    for (actor, chunk, actor_position, actor_rotation) in for_actors.iter() {
+    for (actor, chunk, actor_position, actor_rotation, actor_velocity, actor_on_ground) in
+        for_actors.iter()
+    {
         if let Ok(replication) = chunks_access.get(chunk.get()) {
+            let on_ground = actor_on_ground.map_or(false, |on_ground| on_ground.0);
+
             let mut packets = vec![];
             if actor_position.is_changed() {
                 /*if actor_rotation.is_changed() {
@@ -1079,20 +1298,30 @@ fn replicate_actors_delta(
                     pos: actor_position.0,
                     pitch: actor_rotation.pitch,
                     yaw: actor_rotation.yaw,
-                    on_ground: false,
+                    on_ground,
                 });
             } else if actor_rotation.is_changed() {
                 packets.push(s2c::GamePacket::MoveEntityRot {
                     entity_id: actor.index() as i32,
                     yaw: actor_rotation.yaw,
                     pitch: actor_rotation.pitch,
-                    on_ground: false,
+                    on_ground,
                 });
                 packets.push(s2c::GamePacket::RotateHead {
                     entity_id: actor.index() as i32,
                     head_yaw: actor_rotation.yaw,
                 });
             }
+            if let Some(actor_velocity) = actor_velocity.filter(|velocity| velocity.is_changed()) {
+                // in 1/8000 blocks per tick, limited like Minecraft does
+                let motion = |velocity: f64| (velocity.clamp(-3.9, 3.9) * 8000.0) as i16;
+                packets.push(s2c::GamePacket::SetEntityMotion {
+                    id: actor.index() as i32,
+                    xa: motion(actor_velocity.0.x),
+                    ya: motion(actor_velocity.0.y),
+                    za: motion(actor_velocity.0.z),
+                });
+            }
 
             for &player in replication.subscriber.iter() {
                 // except owner
@@ -1205,37 +1434,120 @@ impl Iterator for ConcentricSquareIterator {
     }
 }
 
-fn add_chunk_packet<'a>(position: IVec2, rendered_chunk: &RenderedChunk) -> s2c::GamePacket<'a> {
+fn add_chunk_packet<'a>(
+    position: IVec2,
+    rendered_chunk: &RenderedChunk,
+    light_data: Option<&level::light::Data>,
+    heightmaps: Option<&level::chunk::Heightmaps>,
+    block_entities_data: Vec<s2c::game::LevelChunkPacketDataBlockEntity>,
+) -> s2c::GamePacket<'a> {
     let mut buffer = Vec::new();
-    let mut sky_y_mask = 0i64;
-    let mut sky_updates = Vec::new();
-    for (i, rendered_section) in rendered_chunk.sections.iter().enumerate() {
+    for rendered_section in &rendered_chunk.sections {
         4096i16.encode(&mut buffer).unwrap();
         rendered_section.block_states.encode(&mut buffer).unwrap();
         rendered_section.biomes.encode(&mut buffer).unwrap();
-
-        sky_y_mask |= 1 << (i + 1);
-        sky_updates.push(vec![0xFF; 2048])
     }
 
-    s2c::GamePacket::LevelChunkWithLight {
-        x: position.x,
-        z: position.y,
-        chunk_data: s2c::game::LevelChunkPacketData {
-            heightmaps: Nbt(serde_value::Value::Map(Default::default())),
-            buffer: buffer.clone(),
-            block_entities_data: vec![],
-        },
-        light_data: s2c::game::LightUpdatePacketData {
+    // chunks without light are fully lit by the sky
+    let section_count = rendered_chunk.sections.len();
+    let height = section_count as u32 * 16;
+    let mut light_update_packet_data = light_data.map_or_else(
+        || s2c::game::LightUpdatePacketData {
             trust_edges: true,
-            sky_y_mask: vec![sky_y_mask],
+            sky_y_mask: vec![((1 << section_count) - 1) << 1],
             block_y_mask: vec![0],
             empty_sky_y_mask: vec![0],
             empty_block_y_mask: vec![0],
-            sky_updates,
+            sky_updates: vec![vec![0xFF; 2048]; section_count],
             block_updates: vec![],
         },
+        |light_data| light_update_packet_data(light_data, 0..section_count),
+    );
+
+    // the section above the level is always fully lit by the sky
+    light_update_packet_data.sky_y_mask[0] |= 1 << (section_count + 1);
+    light_update_packet_data.sky_updates.push(vec![0xFF; 2048]);
+
+    s2c::GamePacket::LevelChunkWithLight {
+        x: position.x,
+        z: position.y,
+        chunk_data: s2c::game::LevelChunkPacketData {
+            heightmaps: Nbt(s2c::game::LevelChunkPacketDataHeightmaps {
+                motion_blocking: heightmaps
+                    .map(|heightmaps| heightmaps.motion_blocking.to_data(height)),
+                world_surface: heightmaps
+                    .map(|heightmaps| heightmaps.world_surface.to_data(height)),
+            }),
+            buffer: buffer.clone(),
+            block_entities_data,
+        },
+        light_data: light_update_packet_data,
+    }
+}
+
+/// Collects the block entities of the chunk, positions are relative to the chunk
+/// except for the height
+fn block_entities_data(
+    registries_report: &registry::RegistriesReport,
+    block_entity_access: &Query<&block::EntityData>,
+    block_entities: Option<&IndexedChildren<IVec3>>,
+) -> Vec<s2c::game::LevelChunkPacketDataBlockEntity> {
+    block_entities.map_or_else(Vec::new, |block_entities| {
+        block_entities
+            .0
+            .iter()
+            .filter_map(|(position, &block_entity)| {
+                let block_entity_data = block_entity_access.get(block_entity).ok()?;
+                Some(s2c::game::LevelChunkPacketDataBlockEntity {
+                    xz: ((position.x & 0xF) << 4 | (position.z & 0xF)) as i8,
+                    y: position.y as i16,
+                    type_: registries_report
+                        .id("minecraft:block_entity_type", &block_entity_data.type_)
+                        as i32,
+                    data: Nbt(block_entity_data.data.clone()),
+                })
+            })
+            .collect()
+    })
+}
+
+/// Packs the light of the sections, empty sections are only marked in the
+/// empty masks
+fn light_update_packet_data(
+    light_data: &level::light::Data,
+    sections: impl Iterator<Item = usize>,
+) -> s2c::game::LightUpdatePacketData {
+    let mut light_update_packet_data = s2c::game::LightUpdatePacketData {
+        trust_edges: true,
+        sky_y_mask: vec![0],
+        block_y_mask: vec![0],
+        empty_sky_y_mask: vec![0],
+        empty_block_y_mask: vec![0],
+        sky_updates: vec![],
+        block_updates: vec![],
+    };
+    for section_y in sections {
+        let section = &light_data.sections[section_y];
+        // first bit is the section below the level
+        let section_bit = 1 << (section_y + 1);
+        if section.sky.is_empty() {
+            light_update_packet_data.empty_sky_y_mask[0] |= section_bit;
+        } else {
+            light_update_packet_data.sky_y_mask[0] |= section_bit;
+            light_update_packet_data
+                .sky_updates
+                .push(section.sky.data().to_vec());
+        }
+        if section.block.is_empty() {
+            light_update_packet_data.empty_block_y_mask[0] |= section_bit;
+        } else {
+            light_update_packet_data.block_y_mask[0] |= section_bit;
+            light_update_packet_data
+                .block_updates
+                .push(section.block.data().to_vec());
+        }
     }
+    light_update_packet_data
 }
 
 fn add_actor_packet<'a>(
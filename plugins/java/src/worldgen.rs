@@ -0,0 +1,537 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+    sync::Arc,
+};
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use futures::FutureExt;
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+
+use tesseract_base::{hierarchy::ParentWithIndex, level};
+use tesseract_java_protocol::types::{Biome, DimensionType};
+
+use crate::{block, persistence, registry, replication};
+
+/// Generation of chunks which have no savegame data
+pub struct WorldGenPlugin {
+    /// Generators by level name, chunks of levels without generator are left
+    /// empty
+    pub generators: HashMap<String, Arc<dyn Generator>>,
+
+    pub chunk_generate_budget: usize,
+}
+
+impl Default for WorldGenPlugin {
+    fn default() -> Self {
+        Self {
+            generators: HashMap::new(),
+
+            chunk_generate_budget: 64,
+        }
+    }
+}
+
+impl Plugin for WorldGenPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Generators(self.generators.clone()))
+            .insert_resource(ChunkGenerateBudget(self.chunk_generate_budget))
+            .add_systems(
+                First,
+                (generate_chunks, finish_generating_chunks)
+                    .after(replication::UpdateFlush)
+                    .after(persistence::UpdateFlush),
+            );
+    }
+}
+
+/// Generates the data of a single chunk, is called from tasks and therefore
+/// should only depend on the position and its own settings
+pub trait Generator: Send + Sync {
+    fn generate(&self, context: &GeneratorContext, position: IVec2) -> level::chunk::Data;
+}
+
+/// Blocks and biomes by name, and the dimension of the level the chunk is
+/// generated for
+#[derive(Clone)]
+pub struct GeneratorContext {
+    blocks: Arc<HashMap<String, Vec<(BTreeMap<String, String>, u32)>>>,
+    biomes: Arc<HashMap<String, (u32, Biome)>>,
+
+    min_y: i32,
+    height: u32,
+}
+
+impl FromWorld for GeneratorContext {
+    fn from_world(world: &mut World) -> Self {
+        let mut for_blocks =
+            world.query_filtered::<(Entity, &block::Name), With<tesseract_base::block::Base>>();
+
+        let mut blocks = HashMap::<_, Vec<_>>::new();
+        for (block_id, block_name) in for_blocks.iter(world) {
+            blocks
+                .entry(block_name.name.clone())
+                .or_default()
+                .push((block_name.properties.clone(), block_id.index()));
+        }
+
+        // least specific blocks first, as generators mostly use plain names
+        for block_states in blocks.values_mut() {
+            block_states.sort_by_key(|(properties, _)| properties.len());
+        }
+
+        let biomes = world
+            .resource::<registry::DataRegistry<Biome>>()
+            .registry()
+            .value
+            .iter()
+            .map(|biome| (biome.name.clone(), (biome.id, biome.element.clone())))
+            .collect();
+        Self {
+            blocks: Arc::new(blocks),
+            biomes: Arc::new(biomes),
+
+            min_y: 0,
+            height: 0,
+        }
+    }
+}
+
+impl GeneratorContext {
+    /// Finds the first block which has all the given properties
+    pub fn block(&self, name: &str) -> Option<u32> {
+        let block_name = block::Name::new(name.to_string());
+        self.blocks
+            .get(&block_name.name)?
+            .iter()
+            .find(|(properties, _)| {
+                block_name
+                    .properties
+                    .iter()
+                    .all(|(property_key, property_value)| {
+                        properties.get(property_key) == Some(property_value)
+                    })
+            })
+            .map(|&(_, block)| block)
+    }
+
+    pub fn biome(&self, name: &str) -> Option<(u32, &Biome)> {
+        self.biomes.get(name).map(|(id, biome)| (*id, biome))
+    }
+
+    pub fn min_y(&self) -> i32 {
+        self.min_y
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Creates empty chunk data which spans the whole height of the level
+    pub fn data(&self, default_block_id: u32, default_biome_id: u32) -> level::chunk::Data {
+        level::chunk::Data::new(
+            (self.height / 16) as u8,
+            (-self.min_y / 16) as u8,
+            default_block_id,
+            default_biome_id,
+        )
+    }
+}
+
+#[derive(Resource)]
+struct Generators(HashMap<String, Arc<dyn Generator>>);
+
+/// Chunk which is being generated (part of Chunk)
+#[derive(Component)]
+struct Generating(Task<level::chunk::Data>);
+
+#[derive(Resource)]
+struct ChunkGenerateBudget(usize);
+
+/// Starts generating chunks in the background, which have neither data nor are
+/// being loaded, this includes quarantined chunks, which are generated but
+/// never saved
+#[allow(clippy::type_complexity)]
+fn generate_chunks(
+    mut commands: Commands,
+    generator_context: Local<GeneratorContext>,
+    generators: Res<Generators>,
+    dimension_type_registry: Res<registry::DataRegistry<DimensionType>>,
+
+    levels_access: Query<&level::Base>,
+    for_chunks: Query<
+        (Entity, &ParentWithIndex<IVec2>),
+        (
+            With<level::chunk::Base>,
+            Without<level::chunk::Data>,
+            Without<persistence::Loading>,
+            Without<Generating>,
+        ),
+    >,
+) {
+    let task_pool = AsyncComputeTaskPool::get();
+    for (chunk, indexed_chunk) in for_chunks.iter() {
+        let Ok(level_base) = levels_access.get(indexed_chunk.parent) else {
+            continue;
+        };
+        let Some(generator) = generators.0.get(level_base.name()) else {
+            continue;
+        };
+
+        let dimension_type = &dimension_type_registry.registry().value
+            [dimension_type_registry.id(level_base.dimension_type()) as usize]
+            .element;
+        let generator_context = GeneratorContext {
+            min_y: dimension_type.min_y,
+            height: dimension_type.height,
+            ..generator_context.clone()
+        };
+        let generator = generator.clone();
+        let chunk_position = indexed_chunk.index;
+        commands
+            .entity(chunk)
+            .insert(Generating(task_pool.spawn(async move {
+                let mut chunk_data = generator.generate(&generator_context, chunk_position);
+                for section in &mut chunk_data.sections {
+                    section.block_state_changes.clear();
+                }
+                chunk_data
+            })));
+    }
+}
+
+/// Inserts the data of generated chunks, but not more than the budget allows
+/// per tick, generated chunks are unsaved
+fn finish_generating_chunks(
+    mut commands: Commands,
+    chunk_generate_budget: Res<ChunkGenerateBudget>,
+
+    mut for_chunks: Query<(Entity, &mut Generating)>,
+) {
+    let mut chunk_generate_budget = chunk_generate_budget.0;
+    for (chunk, mut generating) in for_chunks.iter_mut() {
+        if chunk_generate_budget == 0 {
+            break;
+        }
+
+        if let Some(chunk_data) = (&mut generating.0).now_or_never() {
+            chunk_generate_budget -= 1;
+
+            commands
+                .entity(chunk)
+                .remove::<Generating>()
+                .insert((chunk_data, persistence::Unsaved));
+        }
+    }
+}
+
+//=================================================================================== SUPERFLAT ====
+
+/// Generates the same layers of blocks everywhere
+pub struct SuperflatGenerator {
+    /// Blocks and their thickness from the bottom up, layers with unknown
+    /// blocks are left empty
+    pub layers: Vec<(String, u16)>,
+    pub biome: String,
+}
+
+impl Default for SuperflatGenerator {
+    fn default() -> Self {
+        Self {
+            layers: vec![
+                ("minecraft:bedrock".to_string(), 1),
+                ("minecraft:dirt".to_string(), 3),
+                ("minecraft:grass_block".to_string(), 1),
+            ],
+            biome: "minecraft:plains".to_string(),
+        }
+    }
+}
+
+impl Generator for SuperflatGenerator {
+    fn generate(&self, context: &GeneratorContext, _position: IVec2) -> level::chunk::Data {
+        let air_id = context.block("minecraft:air").unwrap_or(0);
+        let biome_id = context
+            .biome(&self.biome)
+            .map_or(0, |(biome_id, _)| biome_id);
+        let mut chunk_data = context.data(air_id, biome_id);
+
+        let mut layer_y = 0;
+        for (block_name, thickness) in &self.layers {
+            if let Some(block_id) = context.block(block_name) {
+                for y in layer_y..layer_y + thickness {
+                    for x in 0..16 {
+                        for z in 0..16 {
+                            chunk_data.set(x, y, z, block_id);
+                        }
+                    }
+                }
+            }
+            layer_y += thickness;
+        }
+
+        chunk_data
+    }
+}
+
+//======================================================================================= NOISE ====
+
+/// Generates hilly terrain with oceans from fractal noise, the continentalness
+/// of the biome source decides where the land is
+pub struct NoiseGenerator {
+    pub seed: u32,
+    pub sea_level: i32,
+
+    pub biome_source: MultiNoiseBiomeSource,
+}
+
+impl Default for NoiseGenerator {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            sea_level: 63,
+
+            biome_source: Default::default(),
+        }
+    }
+}
+
+impl Generator for NoiseGenerator {
+    fn generate(&self, context: &GeneratorContext, position: IVec2) -> level::chunk::Data {
+        let height_noise = Fbm::<Perlin>::new(self.seed)
+            .set_octaves(6)
+            .set_frequency(1.0 / 128.0);
+        let climate_sampler = self.biome_source.sampler();
+
+        let air_id = context.block("minecraft:air").unwrap_or(0);
+        let bedrock_id = context.block("minecraft:bedrock").unwrap_or(air_id);
+        let stone_id = context.block("minecraft:stone").unwrap_or(air_id);
+        let dirt_id = context.block("minecraft:dirt").unwrap_or(air_id);
+        let grass_block_id = context.block("minecraft:grass_block").unwrap_or(dirt_id);
+        let sand_id = context.block("minecraft:sand").unwrap_or(dirt_id);
+        let water_id = context.block("minecraft:water[level=0]").unwrap_or(air_id);
+        let mut chunk_data = context.data(air_id, 0);
+
+        let block_x = position.x * 16;
+        let block_z = position.y * 16;
+        let max_y = context.height() as i32 - 1;
+        let sea_level = (self.sea_level - context.min_y()).clamp(0, max_y);
+        for x in 0..16u8 {
+            for z in 0..16u8 {
+                let column_x = block_x + x as i32;
+                let column_z = block_z + z as i32;
+                let continentalness = climate_sampler
+                    .sample(column_x, self.sea_level, column_z)
+                    .continentalness;
+                let surface = (sea_level
+                    + ((continentalness + 0.15) * 50.0
+                        + height_noise.get([column_x as f64, column_z as f64]) * 6.0)
+                        as i32)
+                    .clamp(1, max_y);
+
+                for y in 0..=surface.max(sea_level) {
+                    let block_id = if y == 0 {
+                        bedrock_id
+                    } else if y > surface {
+                        water_id
+                    } else if surface <= sea_level + 1 && y > surface - 4 {
+                        sand_id
+                    } else if y == surface {
+                        grass_block_id
+                    } else if y > surface - 4 {
+                        dirt_id
+                    } else {
+                        stone_id
+                    };
+                    chunk_data.set(x, y as u16, z, block_id);
+                }
+            }
+        }
+
+        self.biome_source
+            .populate(context, &climate_sampler, position, &mut chunk_data);
+        chunk_data
+    }
+}
+
+//================================================================================= MULTI NOISE ====
+
+/// Point in the climate space, every parameter is roughly between -1 and 1
+#[derive(Clone, Copy, Debug)]
+pub struct Climate {
+    pub temperature: f64,
+    pub humidity: f64,
+    pub continentalness: f64,
+}
+
+/// Part of the climate space in which a biome is placed
+#[derive(Clone, Debug)]
+pub struct ClimateParameters {
+    pub temperature: Range<f64>,
+    pub humidity: Range<f64>,
+    pub continentalness: Range<f64>,
+}
+
+impl ClimateParameters {
+    pub fn new(temperature: Range<f64>, humidity: Range<f64>, continentalness: Range<f64>) -> Self {
+        Self {
+            temperature,
+            humidity,
+            continentalness,
+        }
+    }
+
+    /// Squared distance of the climate to this part, zero if it is inside
+    fn distance(&self, climate: &Climate) -> f64 {
+        fn distance(parameter: &Range<f64>, value: f64) -> f64 {
+            if value < parameter.start {
+                parameter.start - value
+            } else if value > parameter.end {
+                value - parameter.end
+            } else {
+                0.0
+            }
+        }
+
+        distance(&self.temperature, climate.temperature).powi(2)
+            + distance(&self.humidity, climate.humidity).powi(2)
+            + distance(&self.continentalness, climate.continentalness).powi(2)
+    }
+}
+
+/// Samples the climate from one noise per parameter
+pub struct ClimateSampler {
+    temperature: Fbm<Perlin>,
+    humidity: Fbm<Perlin>,
+    continentalness: Fbm<Perlin>,
+}
+
+impl ClimateSampler {
+    pub fn sample(&self, x: i32, y: i32, z: i32) -> Climate {
+        let point = [x as f64, y as f64, z as f64];
+        Climate {
+            temperature: self.temperature.get(point).clamp(-1.0, 1.0),
+            humidity: self.humidity.get(point).clamp(-1.0, 1.0),
+            continentalness: self.continentalness.get(point).clamp(-1.0, 1.0),
+        }
+    }
+}
+
+/// Places biomes by the climate at each 4x4x4 cell, the biome which is
+/// closest to the climate is picked
+///
+/// The default biomes are an approximate placeholder, they are neither the
+/// parameters nor the noises of vanilla, and only cover the common surface
+/// biomes. Worlds generated with them won't match vanilla worlds of the same
+/// seed.
+pub struct MultiNoiseBiomeSource {
+    pub seed: u32,
+
+    /// Biomes and where they are placed, biomes which don't exist are skipped
+    pub biomes: Vec<(String, ClimateParameters)>,
+}
+
+impl Default for MultiNoiseBiomeSource {
+    fn default() -> Self {
+        // hand-picked ranges, loosely modeled after the vanilla overworld, which
+        // also uses erosion, weirdness and depth
+        const ANY: Range<f64> = -1.0..1.0;
+        const FROZEN: Range<f64> = -1.0..-0.45;
+        const COLD: Range<f64> = -0.45..-0.15;
+        const TEMPERATE: Range<f64> = -0.15..0.2;
+        const WARM: Range<f64> = 0.2..0.55;
+        const HOT: Range<f64> = 0.55..1.0;
+        const DRY: Range<f64> = -1.0..-0.1;
+        const NEUTRAL: Range<f64> = -0.1..0.3;
+        const WET: Range<f64> = 0.3..1.0;
+        const DEEP_OCEAN: Range<f64> = -1.0..-0.455;
+        const OCEAN: Range<f64> = -0.455..-0.19;
+        const COAST: Range<f64> = -0.19..-0.11;
+        const INLAND: Range<f64> = -0.11..1.0;
+
+        let biomes = [
+            ("frozen_ocean", FROZEN, ANY, -1.0..-0.19),
+            ("cold_ocean", COLD, ANY, -1.0..-0.19),
+            ("deep_ocean", TEMPERATE, ANY, DEEP_OCEAN),
+            ("ocean", TEMPERATE, ANY, OCEAN),
+            ("lukewarm_ocean", WARM, ANY, -1.0..-0.19),
+            ("warm_ocean", HOT, ANY, -1.0..-0.19),
+            ("snowy_beach", FROZEN, ANY, COAST),
+            ("beach", -0.45..0.55, ANY, COAST),
+            ("snowy_plains", FROZEN, -1.0..0.3, INLAND),
+            ("snowy_taiga", FROZEN, WET, INLAND),
+            ("plains", COLD, DRY, INLAND),
+            ("taiga", COLD, -0.1..1.0, INLAND),
+            ("plains", TEMPERATE, DRY, INLAND),
+            ("forest", TEMPERATE, NEUTRAL, INLAND),
+            ("dark_forest", TEMPERATE, WET, INLAND),
+            ("savanna", WARM, DRY, INLAND),
+            ("birch_forest", WARM, NEUTRAL, INLAND),
+            ("jungle", WARM, WET, INLAND),
+            ("desert", HOT, ANY, -0.19..1.0),
+        ];
+        Self {
+            seed: 0,
+
+            biomes: biomes
+                .into_iter()
+                .map(|(name, temperature, humidity, continentalness)| {
+                    (
+                        format!("minecraft:{name}"),
+                        ClimateParameters::new(temperature, humidity, continentalness),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl MultiNoiseBiomeSource {
+    pub fn sampler(&self) -> ClimateSampler {
+        let noise = |seed_offset: u32, frequency: f64| {
+            Fbm::<Perlin>::new(self.seed.wrapping_add(seed_offset))
+                .set_octaves(4)
+                .set_frequency(frequency)
+        };
+        ClimateSampler {
+            temperature: noise(1, 1.0 / 1024.0),
+            humidity: noise(2, 1.0 / 1024.0),
+            continentalness: noise(3, 1.0 / 2048.0),
+        }
+    }
+
+    /// Finds the biome which is closest to the climate
+    pub fn biome(&self, context: &GeneratorContext, climate: &Climate) -> Option<u32> {
+        self.biomes
+            .iter()
+            .filter_map(|(biome_name, parameters)| {
+                Some((context.biome(biome_name)?.0, parameters.distance(climate)))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(biome_id, _)| biome_id)
+    }
+
+    /// Assigns the biome of each 4x4x4 cell, by the climate at its center
+    pub fn populate(
+        &self,
+        context: &GeneratorContext,
+        climate_sampler: &ClimateSampler,
+        position: IVec2,
+        chunk_data: &mut level::chunk::Data,
+    ) {
+        let y_offset = chunk_data.y_offset as i32;
+        for (section_y, section) in chunk_data.sections.iter_mut().enumerate() {
+            for index in 0..4 * 4 * 4 {
+                let x = position.x * 16 + (index & 0x3) as i32 * 4 + 2;
+                let y = (section_y as i32 - y_offset) * 16 + (index >> 4) as i32 * 4 + 2;
+                let z = position.y * 16 + (index >> 2 & 0x3) as i32 * 4 + 2;
+                if let Some(biome_id) = self.biome(context, &climate_sampler.sample(x, y, z)) {
+                    section.biomes.get_and_set(index, biome_id);
+                }
+            }
+        }
+    }
+}
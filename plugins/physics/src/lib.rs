@@ -1,129 +1,740 @@
-#![feature(array_zip)]
+use std::collections::HashMap;
 
-use bevy::prelude::*;
+use bevy::{math::DVec3, prelude::*};
 use rand::prelude::*;
 
-use tesseract_base::{block, level};
+use tesseract_base::{
+    actor, block,
+    hierarchy::ParentWithIndex,
+    level,
+    shape::{Aabb, VoxelShape},
+};
 
+//======================================================================================= FLUID ====
+
+/// Kind of fluid, like water or lava, which is shared by all of its fluid
+/// blocks
+#[derive(Component)]
+pub struct FluidKind {
+    /// Volume of a full block, which also limits how far the fluid spreads
+    pub volume_max: u8,
+    /// Ticks between flows, lower is faster
+    pub flow_delay: u64,
+    /// Blocks next to at least two full blocks, above a full or solid block,
+    /// are filled
+    pub infinite_source: bool,
+}
+
+/// Fluid block of a kind (part of Block)
 #[derive(Component)]
 pub struct Fluid {
+    pub kind: Entity,
+    /// Volume minus one, as blocks without any volume are not fluid blocks
     pub volume: u8,
 }
 
-pub struct FluidCache([u32; 8]);
+/// Randomness of the fluid simulation, can be seeded to make it reproducible
+#[derive(Resource)]
+pub struct FluidRng(pub StdRng);
+
+impl Default for FluidRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+
+/// Content of a block, as seen by the fluid simulation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cell {
+    /// Blocks which are neither fluids nor replaceable, and unloaded blocks
+    Solid,
+    Replaceable,
+    /// Fluid of a kind, with a volume of at least 1
+    Fluid(Entity, u8),
+}
+
+/// Cells by block id, and fluid blocks by kind and volume
+pub struct FluidCache {
+    air: Option<u32>,
+    cells: Vec<Cell>,
+    fluids: HashMap<Entity, Vec<Option<u32>>>,
+}
 
 impl FromWorld for FluidCache {
+    #[allow(clippy::type_complexity)]
     fn from_world(world: &mut World) -> Self {
-        let mut fluids_ = [0; 8];
-        for (fluid, fluid_base) in world.query::<(Entity, &Fluid)>().iter(world) {
-            fluids_[fluid_base.volume as usize] = fluid.index();
+        let mut for_blocks = world.query_filtered::<(
+            Entity,
+            Option<&Fluid>,
+            Option<&block::Air>,
+            Option<&block::Replaceable>,
+        ), With<block::Base>>();
+
+        let mut air: Option<u32> = None;
+        let mut cells = vec![];
+        let mut fluids = HashMap::<Entity, Vec<Option<u32>>>::new();
+        for (block, fluid, block_air, block_replaceable) in for_blocks.iter(world) {
+            let block_id = block.index();
+            if block_air.is_some() {
+                air = Some(air.map_or(block_id, |air| air.min(block_id)));
+            }
+
+            let cell = if let Some(fluid) = fluid {
+                let fluid_blocks = fluids.entry(fluid.kind).or_default();
+                let volume = fluid.volume as usize;
+                if volume >= fluid_blocks.len() {
+                    fluid_blocks.resize(volume + 1, None);
+                }
+                fluid_blocks[volume] = Some(block_id);
+                Cell::Fluid(fluid.kind, fluid.volume + 1)
+            } else if block_replaceable.is_some() {
+                Cell::Replaceable
+            } else {
+                Cell::Solid
+            };
+            if block_id as usize >= cells.len() {
+                cells.resize(block_id as usize + 1, Cell::Solid);
+            }
+            cells[block_id as usize] = cell;
         }
-        FluidCache(fluids_)
+        if air.is_none() {
+            warn!("No air block exists, fluids can't drain");
+        }
+
+        Self { air, cells, fluids }
+    }
+}
+
+impl FluidCache {
+    fn cell(&self, block: u32) -> Cell {
+        self.cells
+            .get(block as usize)
+            .copied()
+            .unwrap_or(Cell::Solid)
+    }
+
+    /// Returns the block for the volume of the fluid kind, which is air for a
+    /// volume of 0
+    fn block(&self, kind: Entity, volume: u8) -> Option<u32> {
+        if volume == 0 {
+            return self.air;
+        }
+
+        self.fluids
+            .get(&kind)?
+            .get(volume as usize - 1)
+            .copied()
+            .flatten()
     }
 }
 
+/// Schedules fluid ticks for changed fluid blocks and fluid blocks next to
+/// changed blocks, and lets the fluids flow when their ticks are due
+#[allow(clippy::type_complexity)]
 pub fn update_fluids(
     fluid_cache: Local<FluidCache>,
+    mut fluid_rng: ResMut<FluidRng>,
 
-    blocks_access: Query<&block::Base>,
-    fluids_access: Query<(Entity, &Fluid)>,
+    level_access: Query<&level::AgeAndTime>,
+    fluid_kind_access: Query<&FluidKind>,
+    mut level_blocks: level::LevelBlocks,
 
-    mut for_chunks: Query<(&mut level::chunk::Data, &level::chunk::UpdateQueue)>,
+    mut for_chunks: Query<(
+        Entity,
+        &ParentWithIndex<IVec2>,
+        &level::chunk::UpdateQueue,
+        &level::chunk::NeighborUpdates,
+        &mut level::chunk::ScheduledTicks,
+    )>,
 ) {
-    for (mut chunk_data, chunk_queued_updates) in for_chunks.iter_mut() {
-        if chunk_queued_updates.0.is_empty() {
+    for (chunk, indexed_chunk, chunk_queued_updates, chunk_neighbor_updates, mut scheduled_ticks) in
+        for_chunks.iter_mut()
+    {
+        let level = indexed_chunk.parent;
+        let Ok(level_age_and_time) = level_access.get(level) else {
+            continue;
+        };
+        let Some(y_offset) = level_blocks
+            .data(chunk)
+            .map(|chunk_data| chunk_data.y_offset)
+        else {
             continue;
+        };
+        let chunk_origin = IVec3::new(
+            indexed_chunk.index.x << 4,
+            -(y_offset as i32 * 16),
+            indexed_chunk.index.y << 4,
+        );
+        let level_position = |position: &level::chunk::BlockPosition| {
+            chunk_origin
+                + IVec3::new(
+                    position.x() as i32,
+                    position.y() as i32,
+                    position.z() as i32,
+                )
+        };
+
+        // fluids which have changed, or are next to changed blocks, flow again
+        for position in chunk_queued_updates.0.iter().chain(
+            chunk_neighbor_updates
+                .0
+                .iter()
+                .map(|neighbor_update| &neighbor_update.position),
+        ) {
+            let Ok(block) = level_blocks.get(level, level_position(position)) else {
+                continue;
+            };
+            let Cell::Fluid(kind, _) = fluid_cache.cell(block) else {
+                continue;
+            };
+            let Ok(fluid_kind) = fluid_kind_access.get(kind) else {
+                continue;
+            };
+            if !scheduled_ticks.is_scheduled(*position, level::chunk::TickKind::Fluid) {
+                scheduled_ticks.schedule(level::chunk::ScheduledTick {
+                    position: *position,
+                    kind: level::chunk::TickKind::Fluid,
+                    block,
+                    due: level_age_and_time.age + fluid_kind.flow_delay,
+                    priority: 0,
+                });
+            }
         }
 
-        for queued_update in &chunk_queued_updates.0 {
-            let y = queued_update.y();
-            if y == 0 {
+        for scheduled_tick in &scheduled_ticks.due {
+            if scheduled_tick.kind != level::chunk::TickKind::Fluid {
                 continue;
             }
 
-            let x = queued_update.x();
-            let z = queued_update.z();
-            if let Ok((_, fluid_base)) =
-                fluids_access.get(Entity::from_raw(chunk_data.get(x, y, z)))
-            {
-                let value2volume = |value| {
-                    fluids_access.get(Entity::from_raw(value)).map_or_else(
-                        |_| {
-                            if blocks_access
-                                .get(Entity::from_raw(value))
-                                .map_or(false, |replaceable| true)
-                            {
-                                u8::MAX
-                            } else {
-                                0
-                            }
-                        },
-                        |(_, fluid_base)| fluid_base.volume + 1,
-                    )
-                };
-                let volume2value = |volume| match volume {
-                    0 => Some(0),
-                    VOLUME_MIN..=VOLUME_MAX => Some(fluid_cache.0[(volume - 1) as usize]),
-                    _ => None,
-                };
-
-                // falling
-                let mut volume = fluid_base.volume + 1;
-                let mut volume_below = value2volume(chunk_data.get(x, y - 1, z));
-                if volume_below < VOLUME_MAX {
-                    volume_below += volume;
-                    if volume_below > VOLUME_MAX {
-                        volume = volume_below - VOLUME_MAX;
-                        volume_below = VOLUME_MAX;
-                    } else {
-                        volume = 0;
-                    }
-                    chunk_data.set(x, y - 1, z, fluid_cache.0[(volume_below - 1) as usize]);
-                }
+            let position = level_position(&scheduled_tick.position);
+            let cell_at = |position| {
+                level_blocks
+                    .get(level, position)
+                    .map_or(Cell::Solid, |block| fluid_cache.cell(block))
+            };
+            let Cell::Fluid(kind, _) = cell_at(position) else {
+                continue;
+            };
+            let Ok(fluid_kind) = fluid_kind_access.get(kind) else {
+                continue;
+            };
 
-                // spreading
-                let mut xz_positions = [
-                    (x.wrapping_sub(1), z),
-                    (x + 1, z),
-                    (x, z.wrapping_sub(1)),
-                    (x, z + 1),
-                ];
-                xz_positions.shuffle(&mut thread_rng());
-                let mut volumes = xz_positions.map(|xz_position| {
-                    value2volume(chunk_data.get(xz_position.0, y, xz_position.1))
-                });
+            let flowed = flow(kind, fluid_kind, position, cell_at, &mut fluid_rng.0);
+            let blocks = flowed
+                .into_iter()
+                .filter_map(|(position, volume)| {
+                    fluid_cache
+                        .block(kind, volume)
+                        .map(|block| (position, block))
+                })
+                .collect::<Vec<_>>();
+            // blocks which can't be accessed are solid, therefore all blocks can be set
+            let _ = level_blocks.set_many(level, blocks);
+        }
+    }
+}
 
-                let mut volume_index = 0;
-                let mut spread = false;
-                while volume > VOLUME_MIN {
-                    if volumes[volume_index] < volume {
-                        volumes[volume_index] += 1;
-                        volume -= 1;
-                        spread = true;
-                    }
-
-                    volume_index += 1;
-                    if volume_index >= volumes.len() {
-                        volume_index = 0;
-                        if !spread {
-                            break;
-                        }
-                        spread = false;
-                    }
-                }
+/// Lets the fluid at the position fall and spread, and returns the new volumes
+/// of all changed blocks, where a volume of 0 is no fluid
+fn flow(
+    kind: Entity,
+    fluid_kind: &FluidKind,
+    position: IVec3,
+    cell_at: impl Fn(IVec3) -> Cell,
+    rng: &mut impl Rng,
+) -> Vec<(IVec3, u8)> {
+    // fluids can only flow into replaceable blocks and fluids of the same kind
+    let volume_at = |position| match cell_at(position) {
+        Cell::Solid => None,
+        Cell::Replaceable => Some(0),
+        Cell::Fluid(other_kind, volume) => (other_kind == kind).then_some(volume),
+    };
 
-                if let Some(value) = volume2value(volume) {
-                    chunk_data.set(x, y, z, value);
-                }
-                for ((x, z), volume) in xz_positions.zip(volumes) {
-                    if let Some(value) = volume2value(volume) {
-                        chunk_data.set(x, y, z, value);
-                    }
-                }
+    let Some(volume_before) = volume_at(position).filter(|&volume| volume != 0) else {
+        return vec![];
+    };
+    let mut volume = volume_before;
+    let mut flowed = vec![];
+
+    let position_below = position - IVec3::Y;
+    let mut xz_positions = [
+        position - IVec3::X,
+        position + IVec3::X,
+        position - IVec3::Z,
+        position + IVec3::Z,
+    ];
+
+    // infinite sources
+    if fluid_kind.infinite_source && volume < fluid_kind.volume_max {
+        let full_neighbors = xz_positions
+            .iter()
+            .filter(|&&xz_position| volume_at(xz_position) == Some(fluid_kind.volume_max))
+            .count();
+        let supported = cell_at(position_below) == Cell::Solid
+            || volume_at(position_below) == Some(fluid_kind.volume_max);
+        if full_neighbors >= 2 && supported {
+            volume = fluid_kind.volume_max;
+        }
+    }
+
+    // falling
+    if let Some(volume_below) = volume_at(position_below) {
+        if volume_below < fluid_kind.volume_max {
+            let volume_falling = volume.min(fluid_kind.volume_max - volume_below);
+            volume -= volume_falling;
+            flowed.push((position_below, volume_below + volume_falling));
+        }
+    }
+
+    // spreading, until the volume differs at most by one from all neighbors, as
+    // otherwise the fluid would flow back and forth
+    xz_positions.shuffle(rng);
+    let xz_volumes_before = xz_positions.map(volume_at);
+    let mut xz_volumes = xz_volumes_before;
+    let mut spread = true;
+    while spread {
+        spread = false;
+        for xz_volume in xz_volumes.iter_mut().flatten() {
+            if *xz_volume + 1 < volume {
+                *xz_volume += 1;
+                volume -= 1;
+                spread = true;
+            }
+        }
+    }
+
+    if volume != volume_before {
+        flowed.push((position, volume));
+    }
+    for ((xz_position, xz_volume_before), xz_volume) in
+        xz_positions.iter().zip(xz_volumes_before).zip(xz_volumes)
+    {
+        if let (Some(xz_volume_before), Some(xz_volume)) = (xz_volume_before, xz_volume) {
+            if xz_volume != xz_volume_before {
+                flowed.push((*xz_position, xz_volume));
+            }
+        }
+    }
+    flowed
+}
+
+//======================================================================================= ACTOR ====
+
+/// Gravity in blocks per tick squared
+const GRAVITY: f64 = 0.08;
+/// Vertical drag per tick
+const DRAG: f64 = 0.98;
+/// Horizontal drag per tick in the air
+const FRICTION_AIR: f64 = 0.91;
+/// Horizontal drag per tick on the ground
+const FRICTION_GROUND: f64 = 0.6 * 0.91;
+/// Velocities below are stopped
+const VELOCITY_MIN: f64 = 0.003;
+/// Tolerance for touching boxes
+const EPSILON: f64 = 1e-7;
+
+/// Moves actors by their velocity until they collide with blocks, and applies
+/// gravity and drag, players are moved by their clients instead
+#[allow(clippy::type_complexity)]
+pub fn update_actors(
+    chunk_access: Query<&ParentWithIndex<IVec2>>,
+    level_shapes: level::LevelShapes,
+
+    mut for_actors: Query<(
+        &Parent,
+        &mut actor::Position,
+        &mut actor::Velocity,
+        &mut actor::OnGround,
+        &actor::BoundingBox,
+    )>,
+) {
+    for (chunk, mut position, mut velocity, mut on_ground, bounding_box) in for_actors.iter_mut() {
+        let Ok(indexed_chunk) = chunk_access.get(chunk.get()) else {
+            continue;
+        };
+        let level = indexed_chunk.parent;
+
+        let aabb = bounding_box.at(position.0);
+        let mut colliders = vec![];
+        for block_position in aabb.expand(velocity.0).blocks() {
+            match level_shapes.collision(level, block_position) {
+                Ok(collision) => colliders.extend(collision.at(block_position)),
+                Err(level::BlockAccessError::OutOfBounds(_)) => {}
+                // unloaded blocks collide, to not fall through them
+                Err(_) => colliders.extend(VoxelShape::full().at(block_position)),
+            }
+        }
+
+        let motion = velocity.0;
+        let moved = sweep(aabb, motion, &colliders);
+        let collided = moved.cmpne(motion);
+
+        // only changed components are replicated, therefore they are only written
+        // when they change
+        if moved != DVec3::ZERO {
+            position.0 += moved;
+        }
+        let now_on_ground = collided.y && motion.y < 0.0;
+        if on_ground.0 != now_on_ground {
+            on_ground.0 = now_on_ground;
+        }
+
+        let mut new_velocity = DVec3::select(collided, DVec3::ZERO, motion);
+        new_velocity.y = (new_velocity.y - GRAVITY) * DRAG;
+        let friction = if now_on_ground {
+            FRICTION_GROUND
+        } else {
+            FRICTION_AIR
+        };
+        new_velocity.x *= friction;
+        new_velocity.z *= friction;
+        new_velocity = DVec3::select(
+            new_velocity.abs().cmplt(DVec3::splat(VELOCITY_MIN)),
+            DVec3::ZERO,
+            new_velocity,
+        );
+        if velocity.0 != new_velocity {
+            velocity.0 = new_velocity;
+        }
+    }
+}
+
+/// Moves the box by the motion until it collides, axis by axis starting with
+/// the vertical axis, and returns how far it has been moved
+fn sweep(mut aabb: Aabb, motion: DVec3, colliders: &[Aabb]) -> DVec3 {
+    let mut moved = DVec3::ZERO;
+    for axis in [1, 0, 2] {
+        let mut distance = motion[axis];
+        if distance == 0.0 {
+            continue;
+        }
+
+        for collider in colliders {
+            // only colliders which overlap on the other axes are in the way
+            if (0..3).any(|other_axis| {
+                other_axis != axis
+                    && (collider.max[other_axis] <= aabb.min[other_axis]
+                        || collider.min[other_axis] >= aabb.max[other_axis])
+            }) {
+                continue;
+            }
+
+            if distance > 0.0 && collider.min[axis] >= aabb.max[axis] - EPSILON {
+                distance = distance.min((collider.min[axis] - aabb.max[axis]).max(0.0));
+            } else if distance < 0.0 && collider.max[axis] <= aabb.min[axis] + EPSILON {
+                distance = distance.max((collider.max[axis] - aabb.min[axis]).min(0.0));
             }
         }
+
+        aabb.min[axis] += distance;
+        aabb.max[axis] += distance;
+        moved[axis] = distance;
     }
+    moved
 }
 
-const VOLUME_MIN: u8 = 1;
-const VOLUME_MAX: u8 = 8;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WATER: FluidKind = FluidKind {
+        volume_max: 8,
+        flow_delay: 5,
+        infinite_source: true,
+    };
+    const LAVA: FluidKind = FluidKind {
+        volume_max: 4,
+        flow_delay: 30,
+        infinite_source: false,
+    };
+
+    fn water() -> Entity {
+        Entity::from_raw(0)
+    }
+
+    fn lava() -> Entity {
+        Entity::from_raw(1)
+    }
+
+    /// Blocks in a flat world, which is solid below y=0 and replaceable
+    /// elsewhere
+    fn cells(blocks: &[(IVec3, Cell)]) -> impl Fn(IVec3) -> Cell + '_ {
+        move |position| {
+            blocks
+                .iter()
+                .find(|(block_position, _)| *block_position == position)
+                .map_or(
+                    if position.y < 0 {
+                        Cell::Solid
+                    } else {
+                        Cell::Replaceable
+                    },
+                    |&(_, cell)| cell,
+                )
+        }
+    }
+
+    fn total_volume(flowed: &[(IVec3, u8)]) -> u32 {
+        flowed.iter().map(|&(_, volume)| volume as u32).sum()
+    }
+
+    #[test]
+    fn falls() {
+        let flowed = flow(
+            water(),
+            &WATER,
+            IVec3::new(0, 1, 0),
+            cells(&[(IVec3::new(0, 1, 0), Cell::Fluid(water(), 8))]),
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert_eq!(
+            flowed,
+            vec![(IVec3::new(0, 0, 0), 8), (IVec3::new(0, 1, 0), 0)]
+        );
+    }
+
+    #[test]
+    fn falls_into_partial_fluid_and_spreads_the_rest() {
+        let flowed = flow(
+            water(),
+            &WATER,
+            IVec3::new(0, 1, 0),
+            cells(&[
+                (IVec3::new(0, 1, 0), Cell::Fluid(water(), 8)),
+                (IVec3::new(0, 0, 0), Cell::Fluid(water(), 6)),
+            ]),
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert!(flowed.contains(&(IVec3::new(0, 0, 0), 8)));
+        assert_eq!(total_volume(&flowed), 8 + 6);
+    }
+
+    #[test]
+    fn spreads_at_the_bottom_of_the_level() {
+        let flowed = flow(
+            water(),
+            &WATER,
+            IVec3::ZERO,
+            cells(&[(IVec3::ZERO, Cell::Fluid(water(), 8))]),
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert_eq!(flowed.len(), 5);
+        assert_eq!(total_volume(&flowed), 8);
+        for (_, volume) in flowed {
+            assert!((1..=2).contains(&volume));
+        }
+    }
+
+    #[test]
+    fn spreads_deterministically() {
+        let blocks = [
+            (IVec3::ZERO, Cell::Fluid(water(), 3)),
+            (IVec3::new(1, 0, 0), Cell::Solid),
+        ];
+        let flowed = flow(
+            water(),
+            &WATER,
+            IVec3::ZERO,
+            cells(&blocks),
+            &mut StdRng::seed_from_u64(1),
+        );
+        for _ in 0..8 {
+            assert_eq!(
+                flow(
+                    water(),
+                    &WATER,
+                    IVec3::ZERO,
+                    cells(&blocks),
+                    &mut StdRng::seed_from_u64(1),
+                ),
+                flowed
+            );
+        }
+        assert_eq!(total_volume(&flowed), 3);
+        assert!(!flowed
+            .iter()
+            .any(|&(position, _)| position == IVec3::new(1, 0, 0)));
+    }
+
+    #[test]
+    fn settles() {
+        let flowed = flow(
+            water(),
+            &WATER,
+            IVec3::ZERO,
+            cells(&[
+                (IVec3::ZERO, Cell::Fluid(water(), 2)),
+                (IVec3::new(-1, 0, 0), Cell::Fluid(water(), 1)),
+                (IVec3::new(1, 0, 0), Cell::Fluid(water(), 1)),
+                (IVec3::new(0, 0, -1), Cell::Fluid(water(), 1)),
+                (IVec3::new(0, 0, 1), Cell::Fluid(water(), 1)),
+            ]),
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert!(flowed.is_empty());
+    }
+
+    #[test]
+    fn does_not_flow_into_other_fluids() {
+        let flowed = flow(
+            lava(),
+            &LAVA,
+            IVec3::new(0, 1, 0),
+            cells(&[
+                (IVec3::new(0, 1, 0), Cell::Fluid(lava(), 4)),
+                (IVec3::ZERO, Cell::Fluid(water(), 1)),
+                (IVec3::new(-1, 1, 0), Cell::Fluid(water(), 1)),
+                (IVec3::new(1, 1, 0), Cell::Solid),
+                (IVec3::new(0, 1, -1), Cell::Solid),
+            ]),
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert_eq!(
+            flowed,
+            vec![(IVec3::new(0, 1, 0), 2), (IVec3::new(0, 1, 1), 2)]
+        );
+    }
+
+    #[test]
+    fn range_depends_on_kind() {
+        let flowed = flow(
+            lava(),
+            &LAVA,
+            IVec3::ZERO,
+            cells(&[(IVec3::ZERO, Cell::Fluid(lava(), 4))]),
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert_eq!(total_volume(&flowed), 4);
+        assert_eq!(
+            flowed
+                .iter()
+                .filter(|&&(position, volume)| position != IVec3::ZERO && volume != 0)
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn infinite_source() {
+        let blocks = [
+            (IVec3::ZERO, Cell::Fluid(water(), 1)),
+            (IVec3::new(-1, 0, 0), Cell::Fluid(water(), 8)),
+            (IVec3::new(1, 0, 0), Cell::Fluid(water(), 8)),
+            (IVec3::new(0, 0, -1), Cell::Solid),
+            (IVec3::new(0, 0, 1), Cell::Solid),
+        ];
+        let flowed = flow(
+            water(),
+            &WATER,
+            IVec3::ZERO,
+            cells(&blocks),
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert_eq!(flowed, vec![(IVec3::ZERO, 8)]);
+
+        // not without support, and not for fluids without infinite sources
+        let mut unsupported_blocks = blocks.to_vec();
+        unsupported_blocks.push((IVec3::new(0, -1, 0), Cell::Replaceable));
+        assert_ne!(
+            flow(
+                water(),
+                &WATER,
+                IVec3::ZERO,
+                cells(&unsupported_blocks),
+                &mut StdRng::seed_from_u64(0),
+            ),
+            vec![(IVec3::ZERO, 8)]
+        );
+        let lava_blocks = blocks.map(|(position, cell)| {
+            (
+                position,
+                match cell {
+                    Cell::Fluid(_, volume) => Cell::Fluid(lava(), volume.min(4)),
+                    cell => cell,
+                },
+            )
+        });
+        assert!(flow(
+            lava(),
+            &LAVA,
+            IVec3::ZERO,
+            cells(&lava_blocks),
+            &mut StdRng::seed_from_u64(0),
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn lands_on_block() {
+        let aabb = actor::BoundingBox {
+            width: 0.6,
+            height: 1.8,
+        }
+        .at(DVec3::new(0.5, 1.5, 0.5));
+        assert_eq!(
+            sweep(
+                aabb,
+                DVec3::new(0.0, -1.0, 0.0),
+                &VoxelShape::full().at(IVec3::ZERO).collect::<Vec<_>>()
+            ),
+            DVec3::new(0.0, -0.5, 0.0)
+        );
+    }
+
+    #[test]
+    fn slides_along_wall() {
+        let aabb = actor::BoundingBox {
+            width: 0.6,
+            height: 1.8,
+        }
+        .at(DVec3::new(0.5, 0.0, 0.5));
+        let colliders = aabb
+            .expand(DVec3::new(1.0, 0.0, 1.0))
+            .blocks()
+            .filter(|block_position| block_position.x == 1)
+            .flat_map(|block_position| VoxelShape::full().at(block_position).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let moved = sweep(aabb, DVec3::new(1.0, 0.0, 1.0), &colliders);
+        assert!((moved.x - 0.2).abs() < EPSILON);
+        assert_eq!(moved.z, 1.0);
+    }
+
+    #[test]
+    fn blocks_of_box() {
+        assert_eq!(
+            VoxelShape::full()
+                .at(IVec3::ONE)
+                .flat_map(|aabb| aabb.blocks())
+                .collect::<Vec<_>>(),
+            vec![IVec3::ONE]
+        );
+        assert_eq!(
+            actor::BoundingBox {
+                width: 0.5,
+                height: 1.0,
+            }
+            .at(DVec3::new(0.0, 0.5, 0.0))
+            .blocks()
+            .count(),
+            2 * 2 * 2
+        );
+    }
+
+    #[test]
+    fn lands_on_slab() {
+        let slab = VoxelShape(vec![Aabb::new(DVec3::ZERO, DVec3::new(1.0, 0.5, 1.0))]);
+        let aabb = actor::BoundingBox {
+            width: 0.6,
+            height: 1.8,
+        }
+        .at(DVec3::new(0.5, 1.0, 0.5));
+        assert_eq!(
+            sweep(
+                aabb,
+                DVec3::new(0.0, -1.0, 0.0),
+                &slab.at(IVec3::ZERO).collect::<Vec<_>>()
+            ),
+            DVec3::new(0.0, -0.5, 0.0)
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use bevy::{math::DVec3, prelude::*};
 use uuid::Uuid;
 
+use crate::shape::Aabb;
+
 pub mod player;
 
 /// All required components to describe an actor
@@ -27,3 +29,31 @@ pub struct Rotation {
     pub pitch: f32,
     pub yaw: f32,
 }
+
+/// Velocity of the actor in blocks per tick, actors without are not moved by
+/// physics (part of Actor)
+#[derive(Component, Default)]
+pub struct Velocity(pub DVec3);
+
+/// Whether the actor is standing on a block (part of Actor)
+#[derive(Component, Default)]
+pub struct OnGround(pub bool);
+
+/// Size of the bounding box, which is horizontally centered around the
+/// position and starts at the position (part of Actor)
+#[derive(Component)]
+pub struct BoundingBox {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl BoundingBox {
+    /// Returns the box at the position of the actor
+    pub fn at(&self, position: DVec3) -> Aabb {
+        let half_width = self.width / 2.0;
+        Aabb::new(
+            position - DVec3::new(half_width, 0.0, half_width),
+            position + DVec3::new(half_width, self.height, half_width),
+        )
+    }
+}
@@ -2,7 +2,7 @@ use bevy::prelude::*;
 
 use tesseract_java_protocol::types::Direction;
 
-use crate::actor;
+use crate::{actor, item};
 
 #[derive(Bundle)]
 pub struct PlayerBundle {
@@ -23,6 +23,7 @@ pub enum Interaction {
     #[default]
     None,
     BreakBlock(IVec3),
-    UseItemOn(IVec3, Direction),
+    /// Uses the item in the slot on the face of the block
+    UseItemOn(IVec3, Direction, item::Slot),
     UseItem,
 }
@@ -1,12 +1,51 @@
 use bevy::prelude::*;
 
+use crate::shape::VoxelShape;
+
 /// Required properties (part of Block)
 #[derive(Component)]
 pub struct Base;
 
+/// Light emitted by the block, and how much light is absorbed when passing
+/// through it, blocks without are opaque (part of Block)
+#[derive(Component)]
+pub struct Light {
+    pub emission: u8,
+    pub opacity: u8,
+}
+
+/// Blocks which are ignored by all heightmaps (part of Block)
+#[derive(Component)]
+pub struct Air;
+
+/// Blocks which neither block motion nor contain fluids, and are therefore
+/// ignored by the motion blocking heightmap (part of Block)
+#[derive(Component)]
+pub struct Passable;
+
+/// Blocks which can be replaced when placing a block, like air or water (part
+/// of Block)
+#[derive(Component)]
+pub struct Replaceable;
+
+/// Blocks which are randomly ticked, like crops or leaves (part of Block)
+#[derive(Component)]
+pub struct RandomlyTicked;
+
+/// Shape which actors collide with, blocks without have a full shape, except
+/// air, passable and replaceable blocks, which have no shape (part of Block)
+#[derive(Component)]
+pub struct Collision(pub VoxelShape);
+
+/// Shape which is targeted by players, blocks without use their collision shape
+/// (part of Block)
+#[derive(Component)]
+pub struct Outline(pub VoxelShape);
+
 //==================================================================================== INSTANCE ====
 
-/// Instance of a block (part of Block instance)
+/// Instance of a block, like chests or signs, which is a child of the chunk
+/// indexed by its position in the level (part of Block instance)
 #[derive(Component)]
 pub struct Instance {
     pub block: Entity,
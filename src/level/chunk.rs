@@ -1,13 +1,14 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use bevy::prelude::*;
+use rand::prelude::*;
 
-use tesseract_java_protocol::types::PalettedContainer;
+use tesseract_java_protocol::types::{BitStorage, Direction, PalettedContainer};
 
 use crate::{
-    actor,
+    actor, block,
     hierarchy::{EntityCommandsExt, IndexedChildren, ParentWithIndex},
-    replication,
+    level, replication,
 };
 
 /// All required components to describe a chunk
@@ -15,6 +16,9 @@ use crate::{
 pub struct ChunkBundle {
     pub base: Base,
     pub update_queue: UpdateQueue,
+    pub neighbor_updates: NeighborUpdates,
+    pub scheduled_ticks: ScheduledTicks,
+    pub random_ticks: RandomTicks,
 
     pub replication: replication::Replication, // TODO: see Replication
 }
@@ -57,6 +61,9 @@ pub fn update_hierarchy(
                     .spawn(ChunkBundle {
                         base: Base,
                         update_queue: Default::default(),
+                        neighbor_updates: Default::default(),
+                        scheduled_ticks: Default::default(),
+                        random_ticks: Default::default(),
                         replication: Default::default(),
                     })
                     .add_child(actor)
@@ -142,6 +149,193 @@ impl Data {
     }
 }
 
+//================================================================================== HEIGHTMAPS ====
+
+/// Highest blocks of each column (part of Chunk)
+#[derive(Component)]
+pub struct Heightmaps {
+    pub motion_blocking: Heightmap,
+    pub world_surface: Heightmap,
+}
+
+impl Heightmaps {
+    fn get(&self, kind: HeightmapKind) -> &Heightmap {
+        match kind {
+            HeightmapKind::MotionBlocking => &self.motion_blocking,
+            HeightmapKind::WorldSurface => &self.world_surface,
+        }
+    }
+
+    fn get_mut(&mut self, kind: HeightmapKind) -> &mut Heightmap {
+        match kind {
+            HeightmapKind::MotionBlocking => &mut self.motion_blocking,
+            HeightmapKind::WorldSurface => &mut self.world_surface,
+        }
+    }
+}
+
+/// Height of the highest block above the bottom of the level plus one, or
+/// zero if the column is empty, ordered by z and then x
+pub struct Heightmap(pub [u16; 16 * 16]);
+
+impl Heightmap {
+    pub fn get(&self, x: u8, z: u8) -> u16 {
+        self.0[((z & 0xF) << 4 | (x & 0xF)) as usize]
+    }
+
+    pub fn set(&mut self, x: u8, z: u8, value: u16) {
+        self.0[((z & 0xF) << 4 | (x & 0xF)) as usize] = value;
+    }
+
+    /// Packs the heights like Minecraft: Java Edition, with as few bits as
+    /// needed for the height of the level
+    pub fn to_data(&self, height: u32) -> Vec<u64> {
+        let mut storage = BitStorage::new(16 * 16, heightmap_bits(height));
+        for (index, &value) in self.0.iter().enumerate() {
+            storage.set(index as u32, value as u32);
+        }
+        storage.data().to_vec()
+    }
+
+    /// Unpacks the heights, returns none if the data doesn't fit the height
+    /// of the level
+    pub fn from_data(height: u32, data: Vec<u64>) -> Option<Self> {
+        let bits = heightmap_bits(height);
+        if data.len() != BitStorage::new(16 * 16, bits).data().len() {
+            return None;
+        }
+
+        let storage = BitStorage::from_data(16 * 16, data);
+        if storage.bits() != bits {
+            return None;
+        }
+
+        let mut heightmap = Self([0; 16 * 16]);
+        for (index, value) in heightmap.0.iter_mut().enumerate() {
+            *value = storage.get(index as u32).min(height) as u16;
+        }
+        Some(heightmap)
+    }
+}
+
+fn heightmap_bits(height: u32) -> u32 {
+    u32::BITS - height.leading_zeros()
+}
+
+#[derive(Clone, Copy)]
+enum HeightmapKind {
+    MotionBlocking,
+    WorldSurface,
+}
+
+/// Heightmaps which include the block by block id
+pub struct HeightmapCache(Vec<(bool, bool)>);
+
+impl FromWorld for HeightmapCache {
+    fn from_world(world: &mut World) -> Self {
+        let mut for_blocks = world.query_filtered::<
+            (Entity, Option<&block::Air>, Option<&block::Passable>),
+            With<block::Base>,
+        >();
+
+        let mut blocks = vec![];
+        for (block, air, passable) in for_blocks.iter(world) {
+            let block_id = block.index() as usize;
+            if block_id >= blocks.len() {
+                blocks.resize(block_id + 1, (true, true));
+            }
+            blocks[block_id] = (air.is_none() && passable.is_none(), air.is_none());
+        }
+        Self(blocks)
+    }
+}
+
+impl HeightmapCache {
+    fn includes(&self, kind: HeightmapKind, block: u32) -> bool {
+        self.0
+            .get(block as usize)
+            .map_or(true, |&(motion_blocking, world_surface)| match kind {
+                HeightmapKind::MotionBlocking => motion_blocking,
+                HeightmapKind::WorldSurface => world_surface,
+            })
+    }
+
+    /// Finds the highest included block below the given height
+    fn scan(&self, kind: HeightmapKind, data: &Data, x: u8, height: u16, z: u8) -> u16 {
+        let mut y = height.min(data.sections.len() as u16 * 16) as i32 - 1;
+        while y >= 0 {
+            // skip sections which only consist of a single excluded block
+            if let PalettedContainer::Single(block) = data.sections[y as usize >> 4].block_states {
+                if !self.includes(kind, block) {
+                    y = (y & !0xF) - 1;
+                    continue;
+                }
+            }
+
+            if self.includes(kind, data.get(x, y as u16, z)) {
+                return y as u16 + 1;
+            }
+            y -= 1;
+        }
+        0
+    }
+}
+
+/// Computes the heightmaps of chunks which have none yet
+pub fn initialize_heightmaps(
+    mut commands: Commands,
+    heightmap_cache: Local<HeightmapCache>,
+
+    for_chunks: Query<(Entity, &Data), Without<Heightmaps>>,
+) {
+    for (chunk, chunk_data) in for_chunks.iter() {
+        let height = chunk_data.sections.len() as u16 * 16;
+        let mut heightmaps = Heightmaps {
+            motion_blocking: Heightmap([0; 16 * 16]),
+            world_surface: Heightmap([0; 16 * 16]),
+        };
+        for kind in [HeightmapKind::MotionBlocking, HeightmapKind::WorldSurface] {
+            let heightmap = heightmaps.get_mut(kind);
+            for x in 0..16 {
+                for z in 0..16 {
+                    heightmap.set(x, z, heightmap_cache.scan(kind, chunk_data, x, height, z));
+                }
+            }
+        }
+
+        commands.entity(chunk).insert(heightmaps);
+    }
+}
+
+/// Updates the heightmaps of the columns with changed blocks
+pub fn update_heightmaps(
+    heightmap_cache: Local<HeightmapCache>,
+
+    mut for_chunks: Query<(&Data, &UpdateQueue, &mut Heightmaps), Changed<UpdateQueue>>,
+) {
+    for (chunk_data, queued_updates, mut heightmaps) in for_chunks.iter_mut() {
+        for queued_update in &queued_updates.0 {
+            let (x, y, z) = (queued_update.x(), queued_update.y(), queued_update.z());
+            let block = chunk_data.get(x, y, z);
+            for kind in [HeightmapKind::MotionBlocking, HeightmapKind::WorldSurface] {
+                let height = heightmaps.get(kind).get(x, z);
+                if heightmap_cache.includes(kind, block) {
+                    if y + 1 > height {
+                        heightmaps.get_mut(kind).set(x, z, y + 1);
+                    }
+                } else if y + 1 == height {
+                    // highest block has been removed
+                    heightmaps.get_mut(kind).set(
+                        x,
+                        z,
+                        heightmap_cache.scan(kind, chunk_data, x, y, z),
+                    );
+                }
+            }
+        }
+    }
+}
+
 //================================================================================ UPDATE QUEUE ====
 
 #[derive(Component, Default)]
@@ -168,12 +362,288 @@ pub fn queue_updates(mut for_chunks: Query<(&Data, &mut UpdateQueue), Changed<Da
     }
 }
 
+//============================================================================ NEIGHBOR UPDATES ====
+
+/// Notifies a block that one of its neighbors has changed
+#[derive(Clone, Copy, Debug)]
+pub struct NeighborUpdate {
+    pub position: BlockPosition,
+    /// Direction in which the changed block is
+    pub direction: Direction,
+}
+
+/// Neighbor updates for the blocks next to the changed blocks, which also
+/// includes changes in neighboring chunks (part of Chunk)
+#[derive(Component, Default)]
+pub struct NeighborUpdates(pub Vec<NeighborUpdate>);
+
+/// Queues neighbor updates for all six faces of the changed blocks
+pub fn queue_neighbor_updates(
+    level_access: Query<&IndexedChildren<IVec2>>,
+    mut neighbor_updates_access: Query<&mut NeighborUpdates>,
+
+    for_chunks: Query<(Entity, &ParentWithIndex<IVec2>, &Data, &UpdateQueue), Changed<UpdateQueue>>,
+) {
+    for mut neighbor_updates in neighbor_updates_access.iter_mut() {
+        if !neighbor_updates.0.is_empty() {
+            neighbor_updates.0.clear();
+        }
+    }
+
+    let mut neighbor_updates_by_chunk = HashMap::<Entity, Vec<NeighborUpdate>>::new();
+    for (chunk, indexed_chunk, chunk_data, queued_updates) in for_chunks.iter() {
+        let Ok(indexed_chunks) = level_access.get(indexed_chunk.parent) else {
+            continue;
+        };
+
+        let height = chunk_data.sections.len() as i32 * 16;
+        for queued_update in &queued_updates.0 {
+            let position = IVec3::new(
+                queued_update.x() as i32,
+                queued_update.y() as i32,
+                queued_update.z() as i32,
+            );
+            for direction in [
+                Direction::Down,
+                Direction::Up,
+                Direction::North,
+                Direction::South,
+                Direction::West,
+                Direction::East,
+            ] {
+                let neighbor_position = position + direction.vector();
+                if neighbor_position.y < 0 || neighbor_position.y >= height {
+                    continue;
+                }
+
+                // neighbors outside of the chunk are in the neighboring chunk
+                let chunk_offset = IVec2::new(neighbor_position.x >> 4, neighbor_position.z >> 4);
+                let neighbor_chunk = if chunk_offset == IVec2::ZERO {
+                    chunk
+                } else if let Some(&neighbor_chunk) =
+                    indexed_chunks.0.get(&(indexed_chunk.index + chunk_offset))
+                {
+                    neighbor_chunk
+                } else {
+                    continue;
+                };
+
+                neighbor_updates_by_chunk
+                    .entry(neighbor_chunk)
+                    .or_default()
+                    .push(NeighborUpdate {
+                        position: BlockPosition::new(
+                            neighbor_position.x as u8,
+                            neighbor_position.y as u16,
+                            neighbor_position.z as u8,
+                        ),
+                        direction: direction.opposite(),
+                    });
+            }
+        }
+    }
+
+    for (chunk, chunk_neighbor_updates) in neighbor_updates_by_chunk {
+        if let Ok(mut neighbor_updates) = neighbor_updates_access.get_mut(chunk) {
+            neighbor_updates.0.extend(chunk_neighbor_updates);
+        }
+    }
+}
+
+//============================================================================= SCHEDULED TICKS ====
+
+/// Block and fluid ticks are scheduled separately
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum TickKind {
+    Block,
+    Fluid,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ScheduledTick {
+    pub position: BlockPosition,
+    pub kind: TickKind,
+    /// Block which has scheduled the tick, it might not be there anymore when
+    /// the tick is due
+    pub block: u32,
+    /// Age of the level at which the tick is due
+    pub due: u64,
+    /// Ticks which are due at the same age are run by priority, lowest first
+    pub priority: i8,
+}
+
+/// Ticks which are scheduled for later, and the ticks which are due in this
+/// tick (part of Chunk)
+#[derive(Component, Default)]
+pub struct ScheduledTicks {
+    scheduled: BTreeMap<(u64, i8, u64), ScheduledTick>,
+    scheduled_positions: HashSet<(BlockPosition, TickKind)>,
+    next_order: u64,
+
+    pub due: Vec<ScheduledTick>,
+}
+
+impl ScheduledTicks {
+    /// Schedules the tick, unless there is already a tick of the same kind
+    /// scheduled for the position
+    pub fn schedule(&mut self, tick: ScheduledTick) -> bool {
+        if !self.scheduled_positions.insert((tick.position, tick.kind)) {
+            return false;
+        }
+
+        self.scheduled
+            .insert((tick.due, tick.priority, self.next_order), tick);
+        self.next_order += 1;
+        true
+    }
+
+    pub fn is_scheduled(&self, position: BlockPosition, kind: TickKind) -> bool {
+        self.scheduled_positions.contains(&(position, kind))
+    }
+
+    /// Returns all scheduled ticks in the order they are going to be run
+    pub fn iter(&self) -> impl Iterator<Item = &ScheduledTick> {
+        self.scheduled.values()
+    }
+
+    /// Replaces the due ticks with the ticks which are due at the age
+    fn queue_due(&mut self, age: u64) {
+        self.due.clear();
+        while let Some(entry) = self.scheduled.first_entry() {
+            if entry.key().0 > age {
+                break;
+            }
+
+            let tick = entry.remove();
+            self.scheduled_positions.remove(&(tick.position, tick.kind));
+            self.due.push(tick);
+        }
+    }
+}
+
+/// Moves the ticks which are due into the due ticks
+pub fn queue_scheduled_ticks(
+    level_access: Query<&level::AgeAndTime>,
+    mut for_chunks: Query<(&ParentWithIndex<IVec2>, &mut ScheduledTicks)>,
+) {
+    for (indexed_chunk, mut scheduled_ticks) in for_chunks.iter_mut() {
+        let Ok(level_age_and_time) = level_access.get(indexed_chunk.parent) else {
+            continue;
+        };
+        let age = level_age_and_time.age;
+
+        // skip chunks without changes, to not trigger change detection
+        if scheduled_ticks.due.is_empty()
+            && scheduled_ticks
+                .scheduled
+                .first_key_value()
+                .map_or(true, |(&(due, ..), _)| due > age)
+        {
+            continue;
+        }
+
+        scheduled_ticks.queue_due(age);
+    }
+}
+
+//================================================================================ RANDOM TICKS ====
+
+/// Positions of the blocks which have been randomly ticked in this tick (part
+/// of Chunk)
+#[derive(Component, Default)]
+pub struct RandomTicks(pub Vec<BlockPosition>);
+
+/// Blocks which are randomly ticked by block id
+pub struct RandomTickCache(Vec<bool>);
+
+impl FromWorld for RandomTickCache {
+    fn from_world(world: &mut World) -> Self {
+        let mut for_blocks =
+            world.query_filtered::<Entity, (With<block::Base>, With<block::RandomlyTicked>)>();
+
+        let mut blocks = vec![];
+        for block in for_blocks.iter(world) {
+            let block_id = block.index() as usize;
+            if block_id >= blocks.len() {
+                blocks.resize(block_id + 1, false);
+            }
+            blocks[block_id] = true;
+        }
+        Self(blocks)
+    }
+}
+
+impl RandomTickCache {
+    fn includes(&self, block: u32) -> bool {
+        self.0.get(block as usize).copied().unwrap_or(false)
+    }
+
+    /// Picks the random positions of each section, and adds the positions of
+    /// randomly ticked blocks
+    fn pick(
+        &self,
+        chunk_data: &Data,
+        random_tick_speed: u32,
+        rng: &mut impl Rng,
+        random_ticks: &mut Vec<BlockPosition>,
+    ) {
+        for (section_y, section) in chunk_data.sections.iter().enumerate() {
+            // skip sections which only consist of a single block, which isn't ticked
+            if let PalettedContainer::Single(block) = section.block_states {
+                if !self.includes(block) {
+                    continue;
+                }
+            }
+
+            for _ in 0..random_tick_speed {
+                let index = rng.gen_range(0..16 * 16 * 16);
+                if self.includes(section.block_states.get(index)) {
+                    random_ticks.push(BlockPosition(
+                        index as u8,
+                        (index >> 8) as u16 | (section_y as u16) << 4,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Picks random positions in each section, like Minecraft does, and queues the
+/// positions of randomly ticked blocks
+pub fn queue_random_ticks(
+    random_tick_cache: Local<RandomTickCache>,
+
+    level_access: Query<Option<&level::RandomTickSpeed>>,
+    mut for_chunks: Query<(&ParentWithIndex<IVec2>, &Data, &mut RandomTicks)>,
+) {
+    let mut rng = thread_rng();
+    for (indexed_chunk, chunk_data, mut random_ticks) in for_chunks.iter_mut() {
+        let Ok(random_tick_speed) = level_access.get(indexed_chunk.parent) else {
+            continue;
+        };
+        let random_tick_speed = random_tick_speed
+            .map_or(level::RandomTickSpeed::default().0, |random_tick_speed| {
+                random_tick_speed.0
+            });
+
+        if !random_ticks.0.is_empty() {
+            random_ticks.0.clear();
+        }
+        random_tick_cache.pick(chunk_data, random_tick_speed, &mut rng, &mut random_ticks.0);
+    }
+}
+
 //====================================================================================== HELPER ====
 
-#[derive(Eq, PartialEq, Hash)]
+/// Position of a block in a chunk, the height starts at the bottom of the chunk
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub struct BlockPosition(u8, u16);
 
 impl BlockPosition {
+    pub fn new(x: u8, y: u16, z: u8) -> Self {
+        Self((z & 0xF) << 4 | (x & 0xF), y)
+    }
+
     pub fn x(&self) -> u8 {
         self.0 & 0xF
     }
@@ -186,3 +656,161 @@ impl BlockPosition {
         self.0 >> 4 & 0xF
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AIR: u32 = 0;
+    const STONE: u32 = 1;
+    const GRASS: u32 = 2;
+
+    fn heightmap_cache() -> HeightmapCache {
+        HeightmapCache(vec![(false, false), (true, true), (false, true)])
+    }
+
+    #[test]
+    fn heightmap_round_trip() {
+        let mut heightmap = Heightmap([0; 16 * 16]);
+        for x in 0..16 {
+            for z in 0..16 {
+                heightmap.set(x, z, (z as u16) << 4 | x as u16);
+            }
+        }
+        heightmap.set(15, 15, 384);
+
+        let data = heightmap.to_data(384);
+        // 9 bits, 7 heights per long
+        assert_eq!(data.len(), 37);
+        let heightmap = Heightmap::from_data(384, data).unwrap();
+        assert_eq!(heightmap.get(0, 0), 0);
+        assert_eq!(heightmap.get(3, 1), 0x13);
+        assert_eq!(heightmap.get(14, 15), 0xFE);
+        assert_eq!(heightmap.get(15, 15), 384);
+    }
+
+    #[test]
+    fn heightmap_flat_data() {
+        // superflat with the top block at y=-61, and the bottom at y=-64
+        let heightmap = Heightmap([4; 16 * 16]);
+        let data = heightmap.to_data(384);
+        assert!(data[..36].iter().all(|&value| value == 0x100804020100804));
+        assert_eq!(data[36], 0x20100804);
+    }
+
+    #[test]
+    fn heightmap_invalid_data() {
+        let data = Heightmap([4; 16 * 16]).to_data(384);
+        assert!(Heightmap::from_data(128, data.clone()).is_none());
+        assert!(Heightmap::from_data(384, data[1..].to_vec()).is_none());
+    }
+
+    #[test]
+    fn heightmap_scan() {
+        let mut data = Data::new(24, 4, AIR, 0);
+        for y in 0..4 {
+            data.set(0, y, 0, STONE);
+        }
+        data.set(0, 4, 0, GRASS);
+        data.set(1, 100, 1, STONE);
+
+        let heightmap_cache = heightmap_cache();
+        let motion_blocking = HeightmapKind::MotionBlocking;
+        let world_surface = HeightmapKind::WorldSurface;
+        assert_eq!(heightmap_cache.scan(motion_blocking, &data, 0, 384, 0), 4);
+        assert_eq!(heightmap_cache.scan(world_surface, &data, 0, 384, 0), 5);
+        assert_eq!(heightmap_cache.scan(world_surface, &data, 0, 4, 0), 4);
+        assert_eq!(heightmap_cache.scan(world_surface, &data, 1, 384, 1), 101);
+        assert_eq!(heightmap_cache.scan(world_surface, &data, 2, 384, 2), 0);
+    }
+
+    fn scheduled_tick(position: BlockPosition, due: u64, priority: i8) -> ScheduledTick {
+        ScheduledTick {
+            position,
+            kind: TickKind::Block,
+            block: STONE,
+            due,
+            priority,
+        }
+    }
+
+    #[test]
+    fn scheduled_ticks_order() {
+        let mut scheduled_ticks = ScheduledTicks::default();
+        for (x, due, priority) in [(0, 5, 1), (1, 5, -1), (2, 3, 0), (3, 5, -1), (4, 6, -3)] {
+            assert!(scheduled_ticks.schedule(scheduled_tick(
+                BlockPosition::new(x, 0, 0),
+                due,
+                priority
+            )));
+        }
+
+        // by due age, then by priority, then in the order they were scheduled
+        let order = |ticks: &mut dyn Iterator<Item = &ScheduledTick>| {
+            ticks.map(|tick| tick.position.x()).collect::<Vec<_>>()
+        };
+        assert_eq!(order(&mut scheduled_ticks.iter()), [2, 1, 3, 0, 4]);
+
+        scheduled_ticks.queue_due(4);
+        assert_eq!(order(&mut scheduled_ticks.due.iter()), [2]);
+        scheduled_ticks.queue_due(5);
+        assert_eq!(order(&mut scheduled_ticks.due.iter()), [1, 3, 0]);
+        assert_eq!(order(&mut scheduled_ticks.iter()), [4]);
+    }
+
+    #[test]
+    fn scheduled_ticks_dedup() {
+        let position = BlockPosition::new(1, 2, 3);
+        let mut scheduled_ticks = ScheduledTicks::default();
+        assert!(scheduled_ticks.schedule(scheduled_tick(position, 5, 0)));
+        assert!(!scheduled_ticks.schedule(scheduled_tick(position, 3, -1)));
+        // other kinds are scheduled separately
+        assert!(scheduled_ticks.schedule(ScheduledTick {
+            kind: TickKind::Fluid,
+            ..scheduled_tick(position, 3, 0)
+        }));
+        assert!(scheduled_ticks.is_scheduled(position, TickKind::Block));
+        assert!(scheduled_ticks.is_scheduled(position, TickKind::Fluid));
+        assert_eq!(scheduled_ticks.iter().count(), 2);
+
+        // can be scheduled again once due
+        scheduled_ticks.queue_due(5);
+        assert_eq!(scheduled_ticks.due.len(), 2);
+        assert!(!scheduled_ticks.is_scheduled(position, TickKind::Block));
+        assert!(scheduled_ticks.schedule(scheduled_tick(position, 10, 0)));
+    }
+
+    #[test]
+    fn random_ticks() {
+        let random_tick_cache = RandomTickCache(vec![false, false, true]);
+        let mut data = Data::new(4, 0, AIR, 0);
+        // section 1 consists only of ticked blocks, section 2 of a few
+        for y in 16..32 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    data.set(x, y, z, GRASS);
+                }
+            }
+        }
+        data.set(3, 40, 5, GRASS);
+        data.set(4, 40, 5, STONE);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut random_ticks = vec![];
+        for _ in 0..1000 {
+            random_tick_cache.pick(&data, 3, &mut rng, &mut random_ticks);
+        }
+        assert_eq!(
+            random_ticks
+                .iter()
+                .filter(|position| position.y() >> 4 == 1)
+                .count(),
+            3000
+        );
+        assert!(random_ticks.iter().all(|position| data.get(
+            position.x(),
+            position.y(),
+            position.z()
+        ) == GRASS));
+    }
+}
@@ -0,0 +1,747 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use bevy::prelude::*;
+
+use tesseract_java_protocol::types::PalettedContainer;
+
+use crate::{
+    block,
+    hierarchy::{IndexedChildren, ParentWithIndex},
+    level::chunk,
+};
+
+//======================================================================================== DATA ====
+
+/// Sky and block light (part of Chunk)
+#[derive(Component)]
+pub struct Data {
+    pub sections: Vec<DataSection>,
+    /// Sections which have changed since they have been replicated
+    pub section_changes: BTreeSet<usize>,
+}
+
+impl Data {
+    pub fn new(section_count: usize) -> Self {
+        Self {
+            sections: (0..section_count)
+                .map(|_| DataSection {
+                    sky: NibbleArray::new(0),
+                    block: NibbleArray::new(0),
+                })
+                .collect(),
+            section_changes: Default::default(),
+        }
+    }
+
+    fn get(&self, kind: Kind, position: IVec3) -> u8 {
+        let (section_y, index) = index(position);
+        let section = &self.sections[section_y];
+        match kind {
+            Kind::Sky => section.sky.get(index),
+            Kind::Block => section.block.get(index),
+        }
+    }
+
+    fn set(&mut self, kind: Kind, position: IVec3, value: u8) {
+        let (section_y, index) = index(position);
+        let section = &mut self.sections[section_y];
+        match kind {
+            Kind::Sky => section.sky.set(index, value),
+            Kind::Block => section.block.set(index, value),
+        }
+        self.section_changes.insert(section_y);
+    }
+}
+
+pub struct DataSection {
+    pub sky: NibbleArray,
+    pub block: NibbleArray,
+}
+
+/// 4096 values of 4 bits, in the same order as the block states of a section
+#[derive(Clone)]
+pub struct NibbleArray(Vec<u8>);
+
+impl NibbleArray {
+    pub fn new(value: u8) -> Self {
+        Self(vec![value << 4 | value; 2048])
+    }
+
+    pub fn get(&self, index: u16) -> u8 {
+        self.0[index as usize >> 1] >> ((index & 1) << 2) & 0xF
+    }
+
+    pub fn set(&mut self, index: u16, value: u8) {
+        let shift = (index & 1) << 2;
+        let byte = &mut self.0[index as usize >> 1];
+        *byte = *byte & !(0xF << shift) | (value & 0xF) << shift;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&byte| byte == 0)
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+fn index(position: IVec3) -> (usize, u16) {
+    (
+        (position.y >> 4) as usize,
+        ((position.y & 0xF) << 8 | (position.z & 0xF) << 4 | (position.x & 0xF)) as u16,
+    )
+}
+
+//================================================================================= PROPAGATION ====
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Sky,
+    Block,
+}
+
+const DIRECTIONS: [IVec3; 6] = [
+    IVec3::NEG_Y,
+    IVec3::Y,
+    IVec3::NEG_X,
+    IVec3::X,
+    IVec3::NEG_Z,
+    IVec3::Z,
+];
+
+/// Emission and opacity by block id
+pub struct LightCache(Vec<(u8, u8)>);
+
+impl FromWorld for LightCache {
+    fn from_world(world: &mut World) -> Self {
+        let mut blocks = vec![];
+        for (block, block_light) in world.query::<(Entity, &block::Light)>().iter(world) {
+            let block_id = block.index() as usize;
+            if block_id >= blocks.len() {
+                blocks.resize(block_id + 1, (0, 15));
+            }
+            blocks[block_id] = (block_light.emission, block_light.opacity);
+        }
+        Self(blocks)
+    }
+}
+
+impl LightCache {
+    fn emission(&self, block: u32) -> u8 {
+        self.0
+            .get(block as usize)
+            .map_or(0, |&(emission, _)| emission)
+    }
+
+    fn opacity(&self, block: u32) -> u8 {
+        self.0
+            .get(block as usize)
+            .map_or(15, |&(_, opacity)| opacity)
+    }
+}
+
+/// Blocks and light, positions are relative to the bottom of the level
+trait Storage {
+    /// Returns the block and its light, or none if the position is not loaded,
+    /// above the level there is always full sky light
+    fn get(&self, kind: Kind, position: IVec3) -> Option<(u32, u8)>;
+
+    fn set(&mut self, kind: Kind, position: IVec3, value: u8);
+}
+
+/// Removes the light which came from the positions of the decrease queue, and
+/// then spreads the light from the positions of the increase queue
+fn propagate(
+    storage: &mut impl Storage,
+    light_cache: &LightCache,
+    kind: Kind,
+    decrease: &mut VecDeque<(IVec3, u8)>,
+    increase: &mut VecDeque<IVec3>,
+) {
+    while let Some((position, light)) = decrease.pop_front() {
+        for direction in DIRECTIONS {
+            let neighbor_position = position + direction;
+            let Some((neighbor_block, neighbor_light)) = storage.get(kind, neighbor_position)
+            else {
+                continue;
+            };
+            if neighbor_light == 0 {
+                continue;
+            }
+
+            // sky light travels down without loss, and therefore also has to be
+            // removed when it is equal
+            if neighbor_light < light
+                || (kind == Kind::Sky
+                    && direction == IVec3::NEG_Y
+                    && light == 15
+                    && neighbor_light == 15)
+            {
+                storage.set(kind, neighbor_position, 0);
+                decrease.push_back((neighbor_position, neighbor_light));
+
+                if kind == Kind::Block {
+                    let emission = light_cache.emission(neighbor_block);
+                    if emission != 0 {
+                        storage.set(kind, neighbor_position, emission);
+                        increase.push_back(neighbor_position);
+                    }
+                }
+            } else {
+                // light from elsewhere, which has to fill the gap again
+                increase.push_back(neighbor_position);
+            }
+        }
+    }
+
+    while let Some(position) = increase.pop_front() {
+        let Some((_, light)) = storage.get(kind, position) else {
+            continue;
+        };
+        if light <= 1 {
+            continue;
+        }
+
+        for direction in DIRECTIONS {
+            let neighbor_position = position + direction;
+            let Some((neighbor_block, neighbor_light)) = storage.get(kind, neighbor_position)
+            else {
+                continue;
+            };
+
+            let opacity = light_cache.opacity(neighbor_block);
+            let neighbor_new_light =
+                if kind == Kind::Sky && direction == IVec3::NEG_Y && light == 15 && opacity == 0 {
+                    15
+                } else {
+                    light.saturating_sub(opacity.max(1))
+                };
+            if neighbor_new_light > neighbor_light {
+                storage.set(kind, neighbor_position, neighbor_new_light);
+                increase.push_back(neighbor_position);
+            }
+        }
+    }
+}
+
+/// Removes the light at the changed positions, and spreads it again together
+/// with the light from the positions of the increase queue
+fn relight(
+    storage: &mut impl Storage,
+    light_cache: &LightCache,
+    kind: Kind,
+    mut increase: VecDeque<IVec3>,
+    changes: &[IVec3],
+) {
+    let mut decrease = VecDeque::new();
+    for &position in changes {
+        let Some((block, light)) = storage.get(kind, position) else {
+            continue;
+        };
+
+        storage.set(kind, position, 0);
+        decrease.push_back((position, light));
+
+        if kind == Kind::Block {
+            let emission = light_cache.emission(block);
+            if emission != 0 {
+                storage.set(kind, position, emission);
+                increase.push_back(position);
+            }
+        }
+    }
+    propagate(storage, light_cache, kind, &mut decrease, &mut increase);
+}
+
+/// Lit positions on both sides of the faces which the chunk shares with
+/// loaded chunks, as the neighboring chunk might be missing light from this
+/// chunk or the other way round
+fn border(
+    storage: &impl Storage,
+    kind: Kind,
+    chunk_position: IVec2,
+    height: i32,
+) -> VecDeque<IVec3> {
+    let chunk_position = IVec3::new(chunk_position.x, 0, chunk_position.y) * 16;
+
+    let mut border = VecDeque::new();
+    // first position inside of the chunk, direction out of the chunk, and
+    // direction along the face
+    for (inside, outward, along) in [
+        (IVec3::ZERO, IVec3::NEG_X, IVec3::Z),
+        (IVec3::new(15, 0, 0), IVec3::X, IVec3::Z),
+        (IVec3::ZERO, IVec3::NEG_Z, IVec3::X),
+        (IVec3::new(0, 0, 15), IVec3::Z, IVec3::X),
+    ] {
+        let inside = chunk_position + inside;
+        let outside = inside + outward;
+        if storage.get(kind, outside).is_none() {
+            continue;
+        }
+
+        for y in 0..height {
+            for i in 0..16 {
+                for position in [inside, outside] {
+                    let position = position + along * i + IVec3::Y * y;
+                    if matches!(storage.get(kind, position), Some((_, light)) if light != 0) {
+                        border.push_back(position);
+                    }
+                }
+            }
+        }
+    }
+    border
+}
+
+/// Single chunk, used to compute the light of new chunks without any lookups
+struct ChunkStorage<'a> {
+    chunk_data: &'a chunk::Data,
+    light_data: &'a mut Data,
+}
+
+impl Storage for ChunkStorage<'_> {
+    fn get(&self, kind: Kind, position: IVec3) -> Option<(u32, u8)> {
+        let height = self.chunk_data.sections.len() as i32 * 16;
+        if !(0..16).contains(&position.x) || !(0..16).contains(&position.z) || position.y < 0 {
+            None
+        } else if position.y >= height {
+            (kind == Kind::Sky).then_some((u32::MAX, 15))
+        } else {
+            Some((
+                self.chunk_data
+                    .get(position.x as u8, position.y as u16, position.z as u8),
+                self.light_data.get(kind, position),
+            ))
+        }
+    }
+
+    fn set(&mut self, kind: Kind, position: IVec3, value: u8) {
+        if position.y < self.chunk_data.sections.len() as i32 * 16 {
+            self.light_data.set(kind, position, value);
+        }
+    }
+}
+
+/// All chunks of a level, used to propagate light across chunk borders
+struct LevelStorage<'a, 'w, 's> {
+    chunks: &'a IndexedChildren<IVec2>,
+    for_chunks: &'a mut Query<'w, 's, (&'static chunk::Data, &'static mut Data)>,
+}
+
+impl Storage for LevelStorage<'_, '_, '_> {
+    fn get(&self, kind: Kind, position: IVec3) -> Option<(u32, u8)> {
+        let chunk = *self
+            .chunks
+            .0
+            .get(&IVec2::new(position.x >> 4, position.z >> 4))?;
+        let (chunk_data, light_data) = self.for_chunks.get(chunk).ok()?;
+
+        let height = chunk_data.sections.len() as i32 * 16;
+        if position.y < 0 {
+            None
+        } else if position.y >= height {
+            (kind == Kind::Sky).then_some((u32::MAX, 15))
+        } else {
+            Some((
+                chunk_data.get(position.x as u8, position.y as u16, position.z as u8),
+                light_data.get(kind, position),
+            ))
+        }
+    }
+
+    fn set(&mut self, kind: Kind, position: IVec3, value: u8) {
+        let Some(&chunk) = self
+            .chunks
+            .0
+            .get(&IVec2::new(position.x >> 4, position.z >> 4))
+        else {
+            return;
+        };
+        if let Ok((chunk_data, mut light_data)) = self.for_chunks.get_mut(chunk) {
+            if position.y < chunk_data.sections.len() as i32 * 16 {
+                light_data.set(kind, position, value);
+            }
+        }
+    }
+}
+
+//===================================================================================== SYSTEMS ====
+
+/// Computes the light of chunks which have none yet, light from and to
+/// neighboring chunks is propagated once it has been inserted
+pub fn initialize_light(
+    mut commands: Commands,
+    light_cache: Local<LightCache>,
+
+    for_chunks: Query<(Entity, &chunk::Data), Without<Data>>,
+) {
+    for (chunk, chunk_data) in for_chunks.iter() {
+        let mut light_data = Data::new(chunk_data.sections.len());
+        let height = chunk_data.sections.len() as i32 * 16;
+        let mut storage = ChunkStorage {
+            chunk_data,
+            light_data: &mut light_data,
+        };
+
+        // sky light comes from above the level
+        let mut increase = (0..16)
+            .flat_map(|x| (0..16).map(move |z| IVec3::new(x, height, z)))
+            .collect();
+        propagate(
+            &mut storage,
+            &light_cache,
+            Kind::Sky,
+            &mut VecDeque::new(),
+            &mut increase,
+        );
+
+        let mut increase = VecDeque::new();
+        for (section_y, section) in chunk_data.sections.iter().enumerate() {
+            // skip sections which obviously have no emitting blocks
+            if let PalettedContainer::Single(block) = section.block_states {
+                if light_cache.emission(block) == 0 {
+                    continue;
+                }
+            }
+
+            for index in 0..16 * 16 * 16 {
+                let emission = light_cache.emission(section.block_states.get(index));
+                if emission != 0 {
+                    let position = IVec3::new(
+                        (index & 0xF) as i32,
+                        (section_y << 4 | index as usize >> 8) as i32,
+                        (index >> 4 & 0xF) as i32,
+                    );
+                    storage.set(Kind::Block, position, emission);
+                    increase.push_back(position);
+                }
+            }
+        }
+        propagate(
+            &mut storage,
+            &light_cache,
+            Kind::Block,
+            &mut VecDeque::new(),
+            &mut increase,
+        );
+
+        light_data.section_changes.clear();
+        commands.entity(chunk).insert(light_data);
+    }
+}
+
+/// Propagates light across the borders of new chunks, and updates the light
+/// around changed blocks
+#[allow(clippy::type_complexity)]
+pub fn update_light(
+    light_cache: Local<LightCache>,
+
+    levels_access: Query<&IndexedChildren<IVec2>>,
+    mut for_chunks: ParamSet<(
+        Query<
+            (
+                &ParentWithIndex<IVec2>,
+                Ref<Data>,
+                &chunk::Data,
+                &chunk::UpdateQueue,
+            ),
+            Or<(Added<Data>, Changed<chunk::UpdateQueue>)>,
+        >,
+        Query<(&chunk::Data, &mut Data)>,
+    )>,
+) {
+    let mut updates = vec![];
+    for (indexed_chunk, light_data, chunk_data, chunk_update_queue) in for_chunks.p0().iter() {
+        let chunk_position = IVec3::new(indexed_chunk.index.x, 0, indexed_chunk.index.y) * 16;
+        let changes = chunk_update_queue
+            .0
+            .iter()
+            .map(|update| {
+                chunk_position + IVec3::new(update.x() as i32, update.y() as i32, update.z() as i32)
+            })
+            .collect::<Vec<_>>();
+        updates.push((
+            indexed_chunk.parent,
+            indexed_chunk.index,
+            chunk_data.sections.len() as i32 * 16,
+            light_data.is_added(),
+            changes,
+        ));
+    }
+
+    let mut for_chunks = for_chunks.p1();
+    for (level, chunk_position, height, light_added, changes) in updates {
+        let Ok(chunks) = levels_access.get(level) else {
+            continue;
+        };
+        let mut storage = LevelStorage {
+            chunks,
+            for_chunks: &mut for_chunks,
+        };
+
+        for kind in [Kind::Sky, Kind::Block] {
+            let increase = if light_added {
+                border(&storage, kind, chunk_position, height)
+            } else {
+                VecDeque::new()
+            };
+            relight(&mut storage, &light_cache, kind, increase, &changes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::*;
+
+    const AIR: u32 = 0;
+    const STONE: u32 = 1;
+    const GLOWSTONE: u32 = 2;
+    const WATER: u32 = 3;
+
+    fn light_cache() -> LightCache {
+        LightCache(vec![(0, 0), (0, 15), (15, 15), (0, 2)])
+    }
+
+    /// Loaded chunks of air, except for the given blocks
+    struct TestStorage {
+        chunks: HashSet<IVec2>,
+        height: i32,
+        blocks: HashMap<IVec3, u32>,
+        sky: HashMap<IVec3, u8>,
+        block: HashMap<IVec3, u8>,
+    }
+
+    impl TestStorage {
+        fn new(chunks: &[IVec2], height: i32, blocks: &[(IVec3, u32)]) -> Self {
+            Self {
+                chunks: chunks.iter().copied().collect(),
+                height,
+                blocks: blocks.iter().copied().collect(),
+                sky: Default::default(),
+                block: Default::default(),
+            }
+        }
+
+        fn light(&self, kind: Kind, position: IVec3) -> u8 {
+            self.get(kind, position).unwrap().1
+        }
+
+        /// Computes the light of the chunk on its own, like initialize_light
+        fn initialize(&mut self, chunk_position: IVec2) {
+            let chunk_position = IVec3::new(chunk_position.x, 0, chunk_position.y) * 16;
+            let height = self.height;
+            let mut increase = (0..16)
+                .flat_map(|x| (0..16).map(move |z| IVec3::new(x, height, z)))
+                .map(|position| chunk_position + position)
+                .collect();
+            propagate(
+                self,
+                &light_cache(),
+                Kind::Sky,
+                &mut VecDeque::new(),
+                &mut increase,
+            );
+
+            let mut increase = VecDeque::new();
+            for (&position, &block) in self.blocks.clone().iter() {
+                let emission = light_cache().emission(block);
+                if emission != 0
+                    && IVec2::new(position.x >> 4, position.z >> 4)
+                        == IVec2::new(chunk_position.x >> 4, chunk_position.z >> 4)
+                {
+                    self.set(Kind::Block, position, emission);
+                    increase.push_back(position);
+                }
+            }
+            propagate(
+                self,
+                &light_cache(),
+                Kind::Block,
+                &mut VecDeque::new(),
+                &mut increase,
+            );
+        }
+    }
+
+    impl Storage for TestStorage {
+        fn get(&self, kind: Kind, position: IVec3) -> Option<(u32, u8)> {
+            if !self
+                .chunks
+                .contains(&IVec2::new(position.x >> 4, position.z >> 4))
+                || position.y < 0
+            {
+                None
+            } else if position.y >= self.height {
+                (kind == Kind::Sky).then_some((u32::MAX, 15))
+            } else {
+                let light = match kind {
+                    Kind::Sky => &self.sky,
+                    Kind::Block => &self.block,
+                };
+                Some((
+                    self.blocks.get(&position).copied().unwrap_or(AIR),
+                    light.get(&position).copied().unwrap_or_default(),
+                ))
+            }
+        }
+
+        fn set(&mut self, kind: Kind, position: IVec3, value: u8) {
+            if position.y < self.height {
+                match kind {
+                    Kind::Sky => &mut self.sky,
+                    Kind::Block => &mut self.block,
+                }
+                .insert(position, value);
+            }
+        }
+    }
+
+    #[test]
+    fn nibble_array() {
+        let mut nibble_array = NibbleArray::new(10);
+        assert!((0..4096).all(|index| nibble_array.get(index) == 10));
+
+        nibble_array.set(0, 3);
+        nibble_array.set(1, 5);
+        nibble_array.set(4095, 16);
+        assert_eq!(nibble_array.get(0), 3);
+        assert_eq!(nibble_array.get(1), 5);
+        assert_eq!(nibble_array.get(2), 10);
+        assert_eq!(nibble_array.get(4095), 0);
+        assert_eq!(nibble_array.data()[0], 0x53);
+        assert_eq!(nibble_array.data()[2047], 0x0A);
+
+        assert!(!nibble_array.is_empty());
+        assert!(NibbleArray::new(0).is_empty());
+    }
+
+    #[test]
+    fn nibble_index() {
+        assert_eq!(index(IVec3::new(1, 2, 3)), (0, 2 << 8 | 3 << 4 | 1));
+        assert_eq!(index(IVec3::new(-1, 17, 16)), (1, 1 << 8 | 15));
+    }
+
+    #[test]
+    fn block_light_decays_per_step() {
+        let mut storage = TestStorage::new(&[IVec2::ZERO], 32, &[(IVec3::new(8, 8, 8), GLOWSTONE)]);
+        storage.initialize(IVec2::ZERO);
+
+        assert_eq!(storage.light(Kind::Block, IVec3::new(8, 8, 8)), 15);
+        assert_eq!(storage.light(Kind::Block, IVec3::new(9, 8, 8)), 14);
+        assert_eq!(storage.light(Kind::Block, IVec3::new(10, 9, 9)), 11);
+        assert_eq!(storage.light(Kind::Block, IVec3::new(8, 8, 15)), 8);
+        assert_eq!(storage.light(Kind::Block, IVec3::new(8, 23, 8)), 0);
+    }
+
+    #[test]
+    fn opacity() {
+        let mut storage = TestStorage::new(
+            &[IVec2::ZERO],
+            32,
+            &[
+                (IVec3::new(8, 8, 8), GLOWSTONE),
+                (IVec3::new(9, 8, 8), WATER),
+                (IVec3::new(7, 8, 8), STONE),
+            ],
+        );
+        storage.initialize(IVec2::ZERO);
+
+        assert_eq!(storage.light(Kind::Block, IVec3::new(9, 8, 8)), 13);
+        assert_eq!(storage.light(Kind::Block, IVec3::new(7, 8, 8)), 0);
+        // around the stone instead of through it
+        assert_eq!(storage.light(Kind::Block, IVec3::new(6, 8, 8)), 11);
+    }
+
+    #[test]
+    fn sky_light_straight_down() {
+        let mut storage = TestStorage::new(&[IVec2::ZERO], 32, &[(IVec3::new(3, 20, 3), STONE)]);
+        storage.initialize(IVec2::ZERO);
+
+        assert_eq!(storage.light(Kind::Sky, IVec3::new(8, 0, 8)), 15);
+        assert_eq!(storage.light(Kind::Sky, IVec3::new(3, 21, 3)), 15);
+        assert_eq!(storage.light(Kind::Sky, IVec3::new(3, 20, 3)), 0);
+        // below the stone the light comes from the side
+        assert_eq!(storage.light(Kind::Sky, IVec3::new(3, 19, 3)), 14);
+        assert_eq!(storage.light(Kind::Sky, IVec3::new(3, 0, 3)), 14);
+    }
+
+    #[test]
+    fn remove_broken_light_source() {
+        let mut storage = TestStorage::new(&[IVec2::ZERO], 32, &[(IVec3::new(8, 8, 8), GLOWSTONE)]);
+        storage.initialize(IVec2::ZERO);
+
+        storage.blocks.clear();
+        relight(
+            &mut storage,
+            &light_cache(),
+            Kind::Block,
+            VecDeque::new(),
+            &[IVec3::new(8, 8, 8)],
+        );
+        assert!(storage.block.values().all(|&light| light == 0));
+    }
+
+    #[test]
+    fn remove_sky_light_below_placed_block() {
+        let mut storage = TestStorage::new(&[IVec2::ZERO], 32, &[]);
+        storage.initialize(IVec2::ZERO);
+
+        storage.blocks.insert(IVec3::new(3, 20, 3), STONE);
+        relight(
+            &mut storage,
+            &light_cache(),
+            Kind::Sky,
+            VecDeque::new(),
+            &[IVec3::new(3, 20, 3)],
+        );
+        assert_eq!(storage.light(Kind::Sky, IVec3::new(3, 20, 3)), 0);
+        assert_eq!(storage.light(Kind::Sky, IVec3::new(3, 19, 3)), 14);
+        assert_eq!(storage.light(Kind::Sky, IVec3::new(3, 0, 3)), 14);
+    }
+
+    #[test]
+    fn propagate_across_chunk_borders() {
+        let mut storage =
+            TestStorage::new(&[IVec2::ZERO], 32, &[(IVec3::new(14, 8, 8), GLOWSTONE)]);
+        storage.initialize(IVec2::ZERO);
+
+        // the new chunk is computed on its own first, like initialize_light
+        storage.chunks.insert(IVec2::X);
+        storage.chunks.remove(&IVec2::ZERO);
+        storage.initialize(IVec2::X);
+        storage.chunks.insert(IVec2::ZERO);
+        assert_eq!(storage.light(Kind::Block, IVec3::new(16, 8, 8)), 0);
+
+        for kind in [Kind::Sky, Kind::Block] {
+            let border = border(&storage, kind, IVec2::X, 32);
+            assert!(border
+                .iter()
+                .all(|position| (position.x == 15 || position.x == 16)
+                    && storage.light(kind, *position) != 0));
+            relight(&mut storage, &light_cache(), kind, border, &[]);
+        }
+        assert_eq!(storage.light(Kind::Block, IVec3::new(16, 8, 8)), 13);
+        assert_eq!(storage.light(Kind::Block, IVec3::new(20, 8, 8)), 9);
+        assert_eq!(storage.light(Kind::Sky, IVec3::new(16, 0, 8)), 15);
+    }
+
+    #[test]
+    fn border_only_faces_with_neighbors() {
+        let mut storage = TestStorage::new(&[IVec2::ZERO], 16, &[]);
+        storage.initialize(IVec2::ZERO);
+        assert!(border(&storage, Kind::Sky, IVec2::ZERO, 16).is_empty());
+        assert!(border(&storage, Kind::Block, IVec2::ZERO, 16).is_empty());
+
+        storage.chunks.insert(IVec2::NEG_Y);
+        storage.initialize(IVec2::NEG_Y);
+        let border = border(&storage, Kind::Sky, IVec2::ZERO, 16);
+        assert_eq!(border.len(), 2 * 16 * 16);
+        assert!(border
+            .iter()
+            .all(|position| position.z == 0 || position.z == -1));
+    }
+}
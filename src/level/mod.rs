@@ -1,8 +1,11 @@
 use std::borrow::Cow;
 
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{block, hierarchy::IndexedChildren, shape::VoxelShape};
 
 pub mod chunk;
+pub mod light;
 
 /// All required components to describe a level
 #[derive(Bundle)]
@@ -52,3 +55,390 @@ pub fn update_time(mut levels: Query<&mut AgeAndTime>) {
         time.time += 1;
     }
 }
+
+//================================================================================ RANDOM TICKS ====
+
+/// Number of randomly picked blocks per section and tick, levels without use
+/// the default of 3 (part of Level)
+#[derive(Component)]
+pub struct RandomTickSpeed(pub u32);
+
+impl Default for RandomTickSpeed {
+    fn default() -> Self {
+        Self(3)
+    }
+}
+
+//====================================================================================== BLOCKS ====
+
+/// Reason why a block couldn't be accessed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockAccessError {
+    /// Level doesn't exist, or has no chunks
+    UnknownLevel(Entity),
+    /// Chunk is not spawned, or its data is not loaded yet
+    Unloaded(IVec2),
+    /// Position is above or below the level
+    OutOfBounds(IVec3),
+}
+
+/// Blocks of levels by their position in the level, across chunks
+#[derive(SystemParam)]
+pub struct LevelBlocks<'w, 's> {
+    level_access: Query<'w, 's, &'static IndexedChildren<IVec2>>,
+    chunk_access: Query<'w, 's, &'static mut chunk::Data>,
+}
+
+impl LevelBlocks<'_, '_> {
+    /// Finds the chunk which contains the position, its data is not
+    /// necessarily loaded
+    pub fn chunk(&self, level: Entity, position: IVec3) -> Result<Entity, BlockAccessError> {
+        find_chunk(&self.level_access, level, position)
+    }
+
+    /// Returns the data of the chunk, if it is loaded
+    pub fn data(&self, chunk: Entity) -> Option<&chunk::Data> {
+        self.chunk_access.get(chunk).ok()
+    }
+
+    pub fn get(&self, level: Entity, position: IVec3) -> Result<u32, BlockAccessError> {
+        let (chunk, x, y, z) = self.locate(level, position)?;
+        Ok(self.chunk_access.get(chunk).unwrap().get(x, y, z))
+    }
+
+    pub fn set(
+        &mut self,
+        level: Entity,
+        position: IVec3,
+        value: u32,
+    ) -> Result<(), BlockAccessError> {
+        let (chunk, x, y, z) = self.locate(level, position)?;
+        self.chunk_access
+            .get_mut(chunk)
+            .unwrap()
+            .set(x, y, z, value);
+        Ok(())
+    }
+
+    /// Sets all blocks, but only if all of them can be accessed, otherwise none
+    /// of them are set
+    pub fn set_many(
+        &mut self,
+        level: Entity,
+        blocks: impl IntoIterator<Item = (IVec3, u32)>,
+    ) -> Result<(), BlockAccessError> {
+        let mut located_blocks = blocks
+            .into_iter()
+            .map(|(position, value)| Ok((self.locate(level, position)?, value)))
+            .collect::<Result<Vec<_>, BlockAccessError>>()?;
+
+        // chunks are only looked up once
+        located_blocks.sort_by_key(|&((chunk, ..), _)| chunk);
+        let mut located_blocks = located_blocks.into_iter().peekable();
+        while let Some(&((chunk, ..), _)) = located_blocks.peek() {
+            let mut chunk_data = self.chunk_access.get_mut(chunk).unwrap();
+            while let Some(((_, x, y, z), value)) =
+                located_blocks.next_if(|&((other_chunk, ..), _)| other_chunk == chunk)
+            {
+                chunk_data.set(x, y, z, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the chunk and the position inside of the chunk
+    fn locate(
+        &self,
+        level: Entity,
+        position: IVec3,
+    ) -> Result<(Entity, u8, u16, u8), BlockAccessError> {
+        let chunk = self.chunk(level, position)?;
+        let chunk_data = self.chunk_access.get(chunk).map_err(|_| {
+            BlockAccessError::Unloaded(IVec2::new(position.x >> 4, position.z >> 4))
+        })?;
+        let (x, y, z) = locate_in_chunk(chunk_data, position)?;
+        Ok((chunk, x, y, z))
+    }
+}
+
+/// Read-only counterpart of [LevelBlocks], which can run in parallel with
+/// other systems reading chunk data
+#[derive(SystemParam)]
+pub struct LevelBlocksRef<'w, 's> {
+    level_access: Query<'w, 's, &'static IndexedChildren<IVec2>>,
+    chunk_access: Query<'w, 's, &'static chunk::Data>,
+}
+
+impl LevelBlocksRef<'_, '_> {
+    /// Finds the chunk which contains the position, its data is not
+    /// necessarily loaded
+    pub fn chunk(&self, level: Entity, position: IVec3) -> Result<Entity, BlockAccessError> {
+        find_chunk(&self.level_access, level, position)
+    }
+
+    /// Returns the data of the chunk, if it is loaded
+    pub fn data(&self, chunk: Entity) -> Option<&chunk::Data> {
+        self.chunk_access.get(chunk).ok()
+    }
+
+    pub fn get(&self, level: Entity, position: IVec3) -> Result<u32, BlockAccessError> {
+        let chunk = self.chunk(level, position)?;
+        let chunk_data = self.chunk_access.get(chunk).map_err(|_| {
+            BlockAccessError::Unloaded(IVec2::new(position.x >> 4, position.z >> 4))
+        })?;
+        let (x, y, z) = locate_in_chunk(chunk_data, position)?;
+        Ok(chunk_data.get(x, y, z))
+    }
+}
+
+fn find_chunk(
+    level_access: &Query<&IndexedChildren<IVec2>>,
+    level: Entity,
+    position: IVec3,
+) -> Result<Entity, BlockAccessError> {
+    let chunk_position = IVec2::new(position.x >> 4, position.z >> 4);
+    level_access
+        .get(level)
+        .map_err(|_| BlockAccessError::UnknownLevel(level))?
+        .0
+        .get(&chunk_position)
+        .copied()
+        .ok_or(BlockAccessError::Unloaded(chunk_position))
+}
+
+/// Finds the position inside of the chunk
+fn locate_in_chunk(
+    chunk_data: &chunk::Data,
+    position: IVec3,
+) -> Result<(u8, u16, u8), BlockAccessError> {
+    let y = position.y + chunk_data.y_offset as i32 * 16;
+    if y < 0 || y >= chunk_data.sections.len() as i32 * 16 {
+        return Err(BlockAccessError::OutOfBounds(position));
+    }
+
+    Ok(((position.x & 0xF) as u8, y as u16, (position.z & 0xF) as u8))
+}
+
+//====================================================================================== SHAPES ====
+
+/// Collision and outline shapes by block id
+pub struct ShapeCache {
+    collision: Vec<VoxelShape>,
+    outline: Vec<VoxelShape>,
+    full: VoxelShape,
+}
+
+impl FromWorld for ShapeCache {
+    #[allow(clippy::type_complexity)]
+    fn from_world(world: &mut World) -> Self {
+        let mut for_blocks = world.query_filtered::<(
+            Entity,
+            Option<&block::Collision>,
+            Option<&block::Outline>,
+            Option<&block::Air>,
+            Option<&block::Passable>,
+            Option<&block::Replaceable>,
+        ), With<block::Base>>();
+
+        let mut collision = vec![];
+        let mut outline = vec![];
+        for (block, block_collision, block_outline, block_air, block_passable, block_replaceable) in
+            for_blocks.iter(world)
+        {
+            let block_id = block.index() as usize;
+            if block_id >= collision.len() {
+                collision.resize(block_id + 1, VoxelShape::full());
+                outline.resize(block_id + 1, VoxelShape::full());
+            }
+
+            let block_collision = block_collision.map_or_else(
+                || {
+                    if block_air.is_some()
+                        || block_passable.is_some()
+                        || block_replaceable.is_some()
+                    {
+                        VoxelShape::empty()
+                    } else {
+                        VoxelShape::full()
+                    }
+                },
+                |block_collision| block_collision.0.clone(),
+            );
+            outline[block_id] = block_outline.map_or_else(
+                || block_collision.clone(),
+                |block_outline| block_outline.0.clone(),
+            );
+            collision[block_id] = block_collision;
+        }
+
+        Self {
+            collision,
+            outline,
+            full: VoxelShape::full(),
+        }
+    }
+}
+
+/// Collision and outline shapes of blocks by their position in the level,
+/// across chunks
+#[derive(SystemParam)]
+pub struct LevelShapes<'w, 's> {
+    shape_cache: Local<'s, ShapeCache>,
+    level_blocks: LevelBlocksRef<'w, 's>,
+}
+
+impl LevelShapes<'_, '_> {
+    /// Returns the collision shape of the block, relative to its position
+    pub fn collision(
+        &self,
+        level: Entity,
+        position: IVec3,
+    ) -> Result<&VoxelShape, BlockAccessError> {
+        let block = self.level_blocks.get(level, position)?;
+        Ok(self
+            .shape_cache
+            .collision
+            .get(block as usize)
+            .unwrap_or(&self.shape_cache.full))
+    }
+
+    /// Returns the outline shape of the block, relative to its position
+    pub fn outline(&self, level: Entity, position: IVec3) -> Result<&VoxelShape, BlockAccessError> {
+        let block = self.level_blocks.get(level, position)?;
+        Ok(self
+            .shape_cache
+            .outline
+            .get(block as usize)
+            .unwrap_or(&self.shape_cache.full))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bevy::{ecs::system::SystemState, math::DVec3};
+
+    use super::*;
+    use crate::shape::Aabb;
+
+    const AIR: u32 = 0;
+    const STONE: u32 = 1;
+
+    /// Spawns a level with chunks of 2 sections, and a chunk without data at
+    /// the unloaded position
+    fn spawn_level(world: &mut World, chunk_positions: &[IVec2], unloaded: IVec2) -> Entity {
+        let mut chunks = chunk_positions
+            .iter()
+            .map(|&chunk_position| {
+                (
+                    chunk_position,
+                    world.spawn(chunk::Data::new(2, 0, AIR, 0)).id(),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        chunks.insert(unloaded, world.spawn_empty().id());
+        world.spawn(IndexedChildren(chunks)).id()
+    }
+
+    #[test]
+    fn set_many_unloaded_sets_nothing() {
+        let mut world = World::new();
+        let level = spawn_level(&mut world, &[IVec2::ZERO], IVec2::X);
+
+        let mut system_state = SystemState::<LevelBlocks>::new(&mut world);
+        let mut level_blocks = system_state.get_mut(&mut world);
+        assert_eq!(
+            level_blocks.set_many(
+                level,
+                [(IVec3::new(0, 0, 0), STONE), (IVec3::new(16, 0, 0), STONE)]
+            ),
+            Err(BlockAccessError::Unloaded(IVec2::X))
+        );
+        assert_eq!(
+            level_blocks.set_many(
+                level,
+                [(IVec3::new(0, 0, 0), STONE), (IVec3::new(0, 32, 0), STONE)]
+            ),
+            Err(BlockAccessError::OutOfBounds(IVec3::new(0, 32, 0)))
+        );
+        assert_eq!(level_blocks.get(level, IVec3::new(0, 0, 0)), Ok(AIR));
+
+        let chunk = level_blocks.chunk(level, IVec3::ZERO).unwrap();
+        assert!(level_blocks
+            .data(chunk)
+            .unwrap()
+            .sections
+            .iter()
+            .all(|section| section.block_state_changes.is_empty()));
+    }
+
+    #[test]
+    fn set_many_across_chunks() {
+        let mut world = World::new();
+        let level = spawn_level(&mut world, &[IVec2::ZERO, IVec2::NEG_X], IVec2::X);
+
+        let positions = [
+            IVec3::new(0, 0, 0),
+            IVec3::new(-1, 31, 15),
+            IVec3::new(15, 5, 15),
+            IVec3::new(-16, 16, 0),
+        ];
+        let mut system_state = SystemState::<LevelBlocks>::new(&mut world);
+        let mut level_blocks = system_state.get_mut(&mut world);
+        assert_eq!(
+            level_blocks.set_many(level, positions.map(|position| (position, STONE))),
+            Ok(())
+        );
+        for position in positions {
+            assert_eq!(level_blocks.get(level, position), Ok(STONE));
+        }
+        assert_eq!(level_blocks.get(level, IVec3::new(1, 0, 0)), Ok(AIR));
+
+        // also visible to read-only access
+        let mut system_state = SystemState::<LevelBlocksRef>::new(&mut world);
+        let level_blocks = system_state.get(&world);
+        for position in positions {
+            assert_eq!(level_blocks.get(level, position), Ok(STONE));
+        }
+    }
+
+    #[test]
+    fn shapes_of_bottom_slab() {
+        let mut world = World::new();
+        // chunks are filled with air
+        assert_eq!(world.spawn((block::Base, block::Air)).id().index(), AIR);
+        let stone = world.spawn(block::Base).id().index();
+        let half = VoxelShape(vec![Aabb::new(DVec3::ZERO, DVec3::new(1.0, 0.5, 1.0))]);
+        let slab = world
+            .spawn((block::Base, block::Collision(half.clone())))
+            .id()
+            .index();
+        let level = spawn_level(&mut world, &[IVec2::ZERO], IVec2::X);
+
+        let mut system_state = SystemState::<LevelBlocks>::new(&mut world);
+        let mut level_blocks = system_state.get_mut(&mut world);
+        assert_eq!(level_blocks.set(level, IVec3::new(0, 0, 0), stone), Ok(()));
+        assert_eq!(level_blocks.set(level, IVec3::new(0, 1, 0), slab), Ok(()));
+
+        let mut system_state = SystemState::<LevelShapes>::new(&mut world);
+        let level_shapes = system_state.get(&world);
+        assert_eq!(
+            level_shapes.collision(level, IVec3::new(0, 0, 0)),
+            Ok(&VoxelShape::full())
+        );
+        assert_eq!(
+            level_shapes.collision(level, IVec3::new(0, 1, 0)),
+            Ok(&half)
+        );
+        assert_eq!(level_shapes.outline(level, IVec3::new(0, 1, 0)), Ok(&half));
+        assert_eq!(
+            level_shapes.collision(level, IVec3::new(0, 2, 0)),
+            Ok(&VoxelShape::empty())
+        );
+        assert_eq!(
+            level_shapes.collision(level, IVec3::X * 16),
+            Err(BlockAccessError::Unloaded(IVec2::X))
+        );
+    }
+}
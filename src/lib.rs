@@ -3,6 +3,7 @@ pub mod block;
 pub mod hierarchy;
 pub mod item;
 pub mod level;
+pub mod shape;
 
 pub mod persistence {
     use bevy::prelude::*;
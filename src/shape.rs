@@ -0,0 +1,60 @@
+use bevy::{math::DVec3, prelude::*};
+
+/// Axis-aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: DVec3,
+    pub max: DVec3,
+}
+
+impl Aabb {
+    pub fn new(min: DVec3, max: DVec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Returns the box moved by the offset
+    pub fn offset(&self, offset: DVec3) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    /// Returns the box which covers the whole way of the box moved by the
+    /// motion
+    pub fn expand(&self, motion: DVec3) -> Self {
+        Self {
+            min: self.min + motion.min(DVec3::ZERO),
+            max: self.max + motion.max(DVec3::ZERO),
+        }
+    }
+
+    /// Returns the positions of all blocks which intersect with the box
+    pub fn blocks(&self) -> impl Iterator<Item = IVec3> {
+        let min = self.min.floor().as_ivec3();
+        let max = self.max.ceil().as_ivec3();
+        (min.y..max.y).flat_map(move |y| {
+            (min.z..max.z).flat_map(move |z| (min.x..max.x).map(move |x| IVec3::new(x, y, z)))
+        })
+    }
+}
+
+/// Shape made of boxes, relative to the position of the block
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VoxelShape(pub Vec<Aabb>);
+
+impl VoxelShape {
+    pub fn empty() -> Self {
+        Self(vec![])
+    }
+
+    pub fn full() -> Self {
+        Self(vec![Aabb::new(DVec3::ZERO, DVec3::ONE)])
+    }
+
+    /// Returns the boxes of the shape at the position of the block
+    pub fn at(&self, position: IVec3) -> impl Iterator<Item = Aabb> + '_ {
+        let offset = position.as_dvec3();
+        self.0.iter().map(move |aabb| aabb.offset(offset))
+    }
+}